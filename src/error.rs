@@ -11,6 +11,8 @@ pub enum ErrorKind {
     GeneralError(String),
     #[cfg(feature = "image-loading")]
     ImageError(::image::ImageError),
+    #[cfg(feature = "svg")]
+    SvgError(usvg::Error),
     IoError(io::Error),
     FontParseError,
     NoFontFound,
@@ -23,6 +25,8 @@ pub enum ErrorKind {
     ImageUpdateOutOfBounds,
     ImageUpdateWithDifferentFormat,
     UnsuportedImageFromat,
+    PathParseError(String),
+    UnsupportedCompositeOperation,
 }
 
 impl Display for ErrorKind {
@@ -38,6 +42,13 @@ impl From<::image::ImageError> for ErrorKind {
     }
 }
 
+#[cfg(feature = "svg")]
+impl From<usvg::Error> for ErrorKind {
+    fn from(error: usvg::Error) -> Self {
+        Self::SvgError(error)
+    }
+}
+
 impl From<io::Error> for ErrorKind {
     fn from(error: io::Error) -> Self {
         Self::IoError(error)
@@ -0,0 +1,451 @@
+//! Image filters and drop shadows.
+//!
+//! Filters are applied by drawing the image into transient render targets with shaders made for them.
+//! Gaussian blurs are done in two passes, one horizontal and one vertical, in which the shader adds up
+//! the texels around every pixel weighted by the gaussian. Wide blurs are done at a lower resolution.
+//! Color filters take a pass each, in which the shader applies a color matrix and a lookup table.
+
+use imgref::Img;
+use rgb::RGBA8;
+
+use crate::{
+    Canvas, Color, Command, CommandType, CompositeOperation, CompositeOperationState, ErrorKind, ImageFlags, ImageId,
+    ImageSource, Paint, PaintFlavor, Path, PictureCommand, RenderTarget, Renderer, State, Transform2D,
+};
+
+// Largest standard deviation in pixels a blur pass is done with, wider blurs are done at a lower resolution.
+// The kernel of a pass reaches three standard deviations and has room for 15 pixels on each side.
+const MAX_SIGMA: f32 = 4.0;
+
+/// Filters applied to images with [Canvas::filter_image](struct.Canvas.html#method.filter_image).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    /// Blurs the image with a gaussian of the given standard deviation in pixels.
+    GaussianBlur { sigma: f32 },
+}
+
+/// Filters that change the color of every pixel of an image on its own.
+///
+/// They are applied to images with [Canvas::apply_image_filters](struct.Canvas.html#method.apply_image_filters)
+/// and to layers with [Canvas::set_layer_filters](struct.Canvas.html#method.set_layer_filters), in a chain
+/// like the CSS `filter` property. Amounts work like the ones of the CSS filter functions of the same name.
+/// Filters apply to unpremultiplied colors with channels from 0 to 1, the result of every filter is clamped
+/// to that range.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ImageFilter {
+    /// Multiplies the color, as a column of red, green, blue, alpha and 1, with the row major 4x5 matrix.
+    /// This is the matrix of the SVG `feColorMatrix` element.
+    ColorMatrix([f32; 20]),
+    /// Multiplies the red, green and blue channels. 0 makes the image black, 1 leaves it unchanged.
+    Brightness(f32),
+    /// Scales the difference of the red, green and blue channels to the middle gray. 0 makes the image gray,
+    /// 1 leaves it unchanged.
+    Contrast(f32),
+    /// Scales the saturation. 0 makes the image grayscale, 1 leaves it unchanged and larger amounts
+    /// oversaturate it.
+    Saturate(f32),
+    /// Rotates the hue by the angle in radians.
+    HueRotate(f32),
+    /// Converts the image to grayscale, completely with 1 and not at all with 0.
+    Grayscale(f32),
+    /// Inverts the red, green and blue channels, completely with 1 and not at all with 0.
+    Invert(f32),
+    /// Replaces the value of every channel by the entry of the table at that value. The table has 256 RGBA
+    /// entries, each channel is looked up in its own channel of the entries.
+    LookupTable(Vec<[u8; 4]>),
+}
+
+impl ImageFilter {
+    fn color_matrix(&self) -> [f32; 20] {
+        match *self {
+            Self::ColorMatrix(matrix) => matrix,
+            Self::Brightness(amount) => linear_matrix(amount, 0.0),
+            Self::Contrast(amount) => linear_matrix(amount, 0.5 - 0.5 * amount),
+            Self::Saturate(amount) => saturate_matrix(amount),
+            Self::HueRotate(angle) => hue_rotate_matrix(angle),
+            Self::Grayscale(amount) => saturate_matrix(1.0 - amount.max(0.0).min(1.0)),
+            Self::Invert(amount) => {
+                let amount = amount.max(0.0).min(1.0);
+                linear_matrix(1.0 - 2.0 * amount, amount)
+            }
+            Self::LookupTable(_) => linear_matrix(1.0, 0.0),
+        }
+    }
+}
+
+impl<T> Canvas<T>
+where
+    T: Renderer,
+{
+    /// Draws the `src` image into `dst` with the filter applied.
+    ///
+    /// `dst` is used as a render target and receives premultiplied pixels, so like other images that are
+    /// rendered into it's usually created with `ImageFlags::PREMULTIPLIED | ImageFlags::FLIP_Y`. The source
+    /// is stretched to the size of the destination. Pixels outside of the source repeat its edges, unless
+    /// it has one of the repeat flags. `src` and `dst` may be the same image.
+    ///
+    /// Filtering isn't affected by the canvas state and isn't recorded into pictures.
+    pub fn filter_image(&mut self, src: ImageId, dst: ImageId, filter: Filter) -> Result<(), ErrorKind> {
+        self.image_info(src)?;
+        self.image_info(dst)?;
+
+        self.with_filter_state(|canvas| match filter {
+            Filter::GaussianBlur { sigma } => canvas.blur_image(src, dst, sigma),
+        })
+    }
+
+    /// Draws the `src` image into `dst` through the filters, one after the other.
+    ///
+    /// The images are treated like the ones of [filter_image](#method.filter_image). With no filters the
+    /// image is copied.
+    ///
+    /// Fails with `ErrorKind::GeneralError` if a lookup table doesn't have 256 entries.
+    pub fn apply_image_filters(
+        &mut self,
+        src: ImageId,
+        dst: ImageId,
+        filters: &[ImageFilter],
+    ) -> Result<(), ErrorKind> {
+        self.image_info(src)?;
+        self.image_info(dst)?;
+
+        self.with_filter_state(|canvas| canvas.color_filter_image(src, dst, filters))
+    }
+
+    /// Draws a blurred shadow of the path, as it would be filled with the color.
+    ///
+    /// The shadow is moved by `offset` and blurred by a gaussian with a standard deviation of `blur`, both in
+    /// the current coordinate system. It's drawn with the current scissor, clip, global alpha and composite
+    /// operation, which don't affect the shape it's made from. Draw the path itself afterwards to put it on
+    /// top of its shadow.
+    pub fn drop_shadow(
+        &mut self,
+        path: &mut Path,
+        offset: (f32, f32),
+        blur: f32,
+        color: Color,
+    ) -> Result<(), ErrorKind> {
+        self.record(|| PictureCommand::DropShadow {
+            path: path.clone(),
+            offset,
+            blur,
+            color,
+        });
+
+        // The shadow is made of other drawing, which isn't recorded
+        let picture = self.picture.take();
+        let result = self.draw_drop_shadow(path, offset, blur, color);
+        self.picture = picture;
+
+        result
+    }
+
+    fn draw_drop_shadow(
+        &mut self,
+        path: &mut Path,
+        offset: (f32, f32),
+        blur: f32,
+        color: Color,
+    ) -> Result<(), ErrorKind> {
+        let mut transform = self.state().transform;
+        transform.premultiply(&Transform2D::new_translation(offset.0, offset.1));
+
+        let sigma = blur.max(0.0) * transform.average_scale();
+        let bounds = path.cache(&transform, self.tess_tol, self.dist_tol).bounds;
+
+        // The blur reaches three standard deviations away from the shape, shapes further than that
+        // outside of the render target can't reach into it.
+        let reach = (sigma * 3.0).ceil();
+        let (target_width, target_height) = self.target_size();
+
+        let minx = (bounds.minx - reach).floor().max(-reach);
+        let miny = (bounds.miny - reach).floor().max(-reach);
+        let maxx = (bounds.maxx + reach).ceil().min(target_width as f32 + reach);
+        let maxy = (bounds.maxy + reach).ceil().min(target_height as f32 + reach);
+
+        if maxx <= minx || maxy <= miny {
+            return Ok(());
+        }
+
+        let (width, height) = (maxx - minx, maxy - miny);
+
+        let mask = self.create_transient_image(width as usize, height as usize)?;
+        let shadow = self.create_transient_image(width as usize, height as usize)?;
+
+        // The shape is drawn without the scissor and clip, they cut the shadow only
+        let mut state = State {
+            transform,
+            ..Default::default()
+        };
+        state.transform.multiply(&Transform2D::new_translation(-minx, -miny));
+        self.state_stack.push(state);
+
+        self.push_render_target(RenderTarget::Image(mask));
+        self.clear_target(width, height);
+        self.fill_path(path, Paint::color(color));
+        self.pop_render_target();
+
+        self.state_stack.pop();
+
+        self.with_filter_state(|canvas| canvas.blur_image(mask, shadow, sigma))?;
+
+        // The shadow is drawn in render target pixels
+        let mut state = self.state().clone();
+        state.transform = Transform2D::identity();
+        self.state_stack.push(state);
+
+        let mut rect = Path::new();
+        rect.rect(minx, miny, width, height);
+        self.fill_path(&mut rect, Paint::image(shadow, minx, miny, width, height, 0.0, 1.0));
+
+        self.state_stack.pop();
+
+        Ok(())
+    }
+
+    // Runs the filter passes with a state of their own, in which every pass replaces what it draws to.
+    fn with_filter_state<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let picture = self.picture.take();
+
+        self.state_stack.push(State {
+            composite_operation: CompositeOperationState::new(CompositeOperation::Copy),
+            ..Default::default()
+        });
+
+        let result = f(self);
+
+        self.state_stack.pop();
+        self.picture = picture;
+
+        result
+    }
+
+    fn blur_image(&mut self, src: ImageId, dst: ImageId, sigma: f32) -> Result<(), ErrorKind> {
+        let (width, height) = self.image_size(dst)?;
+
+        // Halve the resolution until the blur is narrow enough
+        let (mut level, mut level_width, mut level_height) = (src, width, height);
+        let mut scale = 1.0;
+
+        while sigma > MAX_SIGMA * scale && level_width > 1 && level_height > 1 {
+            level_width = (level_width + 1) / 2;
+            level_height = (level_height + 1) / 2;
+            scale *= 2.0;
+
+            let half = self.create_transient_image(level_width, level_height)?;
+            self.copy_image(level, half)?;
+            level = half;
+        }
+
+        // Averaging pixels while halving and filtering while scaling back up blur by about half a
+        // pixel of the lower resolution already.
+        let sigma = (sigma * sigma - (scale * scale - 1.0) / 4.0).max(0.0).sqrt() / scale;
+        let kernel = gaussian_kernel(sigma);
+
+        let pass = self.create_transient_image(level_width, level_height)?;
+        self.blur_pass(level, pass, kernel, true)?;
+
+        if level_width == width && level_height == height {
+            self.blur_pass(pass, dst, kernel, false)
+        } else {
+            let blurred = self.create_transient_image(level_width, level_height)?;
+            self.blur_pass(pass, blurred, kernel, false)?;
+            self.copy_image(blurred, dst)
+        }
+    }
+
+    // Clears dst and draws src blurred along one axis with the kernel, see PaintFlavor::BlurImage.
+    fn blur_pass(&mut self, src: ImageId, dst: ImageId, kernel: [f32; 16], horizontal: bool) -> Result<(), ErrorKind> {
+        let (width, height) = self.image_size(dst)?;
+
+        let paint = Paint {
+            flavor: PaintFlavor::BlurImage {
+                id: src,
+                width: width as f32,
+                height: height as f32,
+                horizontal,
+                kernel,
+            },
+            ..Default::default()
+        };
+
+        self.fill_image(dst, paint)
+    }
+
+    // Applies every filter in a pass of its own. dst is only drawn to in the last pass, a pass reading dst draws
+    // to a transient image that is copied to dst instead.
+    fn color_filter_image(&mut self, src: ImageId, dst: ImageId, filters: &[ImageFilter]) -> Result<(), ErrorKind> {
+        let (width, height) = self.image_size(dst)?;
+
+        if filters.is_empty() {
+            // Copying the image to itself leaves it as it is
+            return if src == dst { Ok(()) } else { self.copy_image(src, dst) };
+        }
+
+        let mut level = src;
+
+        for (i, filter) in filters.iter().enumerate() {
+            let target = if i + 1 == filters.len() && level != dst {
+                dst
+            } else {
+                self.create_transient_image(width, height)?
+            };
+
+            let lookup = match filter {
+                ImageFilter::LookupTable(table) => Some(self.create_lookup_image(table)?),
+                _ => None,
+            };
+
+            let paint = Paint {
+                flavor: PaintFlavor::FilterImage {
+                    id: level,
+                    width: width as f32,
+                    height: height as f32,
+                    matrix: filter.color_matrix(),
+                },
+                alpha_mask: lookup,
+                ..Default::default()
+            };
+
+            self.fill_image(target, paint)?;
+            level = target;
+        }
+
+        if level == dst {
+            Ok(())
+        } else {
+            self.copy_image(level, dst)
+        }
+    }
+
+    fn create_lookup_image(&mut self, table: &[[u8; 4]]) -> Result<ImageId, ErrorKind> {
+        if table.len() != 256 {
+            return Err(ErrorKind::GeneralError("Lookup tables need 256 entries".to_string()));
+        }
+
+        let pixels: Vec<_> = table.iter().map(|&[r, g, b, a]| RGBA8::new(r, g, b, a)).collect();
+        let image = self.create_image(ImageSource::Rgba(Img::new(&pixels[..], 256, 1)), ImageFlags::NEAREST)?;
+
+        self.transient_images.push(image);
+
+        Ok(image)
+    }
+
+    // Clears dst and draws src stretched to its size, src can't be dst.
+    fn copy_image(&mut self, src: ImageId, dst: ImageId) -> Result<(), ErrorKind> {
+        let (width, height) = self.image_size(dst)?;
+
+        self.fill_image(dst, Paint::image(src, 0.0, 0.0, width as f32, height as f32, 0.0, 1.0))
+    }
+
+    // Clears dst and fills all of it with the paint, which can't read dst.
+    fn fill_image(&mut self, dst: ImageId, paint: Paint) -> Result<(), ErrorKind> {
+        let (width, height) = self.image_size(dst)?;
+        let (width, height) = (width as f32, height as f32);
+
+        self.push_render_target(RenderTarget::Image(dst));
+        self.clear_target(width, height);
+
+        let mut path = Path::new();
+        path.rect(0.0, 0.0, width, height);
+
+        self.fill_path(&mut path, paint);
+
+        self.pop_render_target();
+
+        Ok(())
+    }
+
+    fn clear_target(&mut self, width: f32, height: f32) {
+        self.append_cmd(Command::new(CommandType::ClearRect {
+            x: 0,
+            y: 0,
+            width: width as u32,
+            height: height as u32,
+            color: Color::clear(),
+        }));
+    }
+}
+
+// Weights of a gaussian sampled at whole pixels from offset 0 out to three standard deviations, normalized
+// so that they add up to 1 with the mirrored negative offsets.
+fn gaussian_kernel(sigma: f32) -> [f32; 16] {
+    let mut kernel = [0.0; 16];
+
+    if sigma < 0.01 {
+        kernel[0] = 1.0;
+        return kernel;
+    }
+
+    let radius = ((sigma * 3.0).ceil() as usize).min(kernel.len() - 1);
+
+    for (offset, weight) in kernel.iter_mut().enumerate().take(radius + 1) {
+        let offset = offset as f32;
+        *weight = (-offset * offset / (2.0 * sigma * sigma)).exp();
+    }
+
+    let total = kernel[0] + 2.0 * kernel[1..].iter().sum::<f32>();
+
+    for weight in &mut kernel {
+        *weight /= total;
+    }
+
+    kernel
+}
+
+// Scales the red, green and blue channels and adds the intercept to them.
+fn linear_matrix(slope: f32, intercept: f32) -> [f32; 20] {
+    #[rustfmt::skip]
+    let matrix = [
+        slope, 0.0, 0.0, 0.0, intercept,
+        0.0, slope, 0.0, 0.0, intercept,
+        0.0, 0.0, slope, 0.0, intercept,
+        0.0, 0.0, 0.0, 1.0, 0.0,
+    ];
+
+    matrix
+}
+
+// The matrices of the filter effects specification, which mix the channels by their luminance.
+fn rgb_matrix(rgb: [[f32; 3]; 3]) -> [f32; 20] {
+    #[rustfmt::skip]
+    let matrix = [
+        rgb[0][0], rgb[0][1], rgb[0][2], 0.0, 0.0,
+        rgb[1][0], rgb[1][1], rgb[1][2], 0.0, 0.0,
+        rgb[2][0], rgb[2][1], rgb[2][2], 0.0, 0.0,
+        0.0, 0.0, 0.0, 1.0, 0.0,
+    ];
+
+    matrix
+}
+
+fn saturate_matrix(s: f32) -> [f32; 20] {
+    rgb_matrix([
+        [0.213 + 0.787 * s, 0.715 - 0.715 * s, 0.072 - 0.072 * s],
+        [0.213 - 0.213 * s, 0.715 + 0.285 * s, 0.072 - 0.072 * s],
+        [0.213 - 0.213 * s, 0.715 - 0.715 * s, 0.072 + 0.928 * s],
+    ])
+}
+
+fn hue_rotate_matrix(angle: f32) -> [f32; 20] {
+    let (sin, cos) = angle.sin_cos();
+
+    rgb_matrix([
+        [
+            0.213 + cos * 0.787 - sin * 0.213,
+            0.715 - cos * 0.715 - sin * 0.715,
+            0.072 - cos * 0.072 + sin * 0.928,
+        ],
+        [
+            0.213 - cos * 0.213 + sin * 0.143,
+            0.715 + cos * 0.285 + sin * 0.140,
+            0.072 - cos * 0.072 - sin * 0.283,
+        ],
+        [
+            0.213 - cos * 0.213 - sin * 0.787,
+            0.715 - cos * 0.715 + sin * 0.715,
+            0.072 + cos * 0.928 + sin * 0.072,
+        ],
+    ])
+}
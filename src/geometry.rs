@@ -65,7 +65,7 @@ pub fn normalize(x: &mut f32, y: &mut f32) -> f32 {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
-#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Transform2D(pub [f32; 6]);
 
 // TODO: Implement std::ops::* on this
@@ -271,6 +271,7 @@ impl std::ops::Div<f32> for Size {
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Rect {
     pub x: f32,
     pub y: f32,
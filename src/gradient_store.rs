@@ -0,0 +1,134 @@
+use fnv::FnvHashMap;
+use imgref::Img;
+use rgb::RGBA8;
+
+use crate::{Color, ErrorKind, ImageFlags, ImageId, ImageInfo, ImageStore, PixelFormat, Renderer};
+
+/// Width in pixels of a gradient color ramp.
+const RAMP_SIZE: usize = 256;
+
+// Stops are keyed by the bit patterns of their offsets and colors.
+type GradientKey = Vec<u32>;
+
+/// Color ramp textures used by multi stop gradients.
+///
+/// Ramps are created the first time a set of stops is drawn and are kept alive for as long as they are
+/// used in consecutive frames.
+#[derive(Default)]
+pub(crate) struct GradientStore {
+    prev_frame: FnvHashMap<GradientKey, ImageId>,
+    this_frame: FnvHashMap<GradientKey, ImageId>,
+}
+
+impl GradientStore {
+    /// Returns the ramp image for the given stops, creating it if needed.
+    pub fn lookup_or_insert<R: Renderer>(
+        &mut self,
+        stops: &[(f32, Color)],
+        images: &mut ImageStore<R::Image>,
+        renderer: &mut R,
+    ) -> Result<ImageId, ErrorKind> {
+        let key = gradient_key(stops);
+
+        if let Some(id) = self.this_frame.get(&key) {
+            return Ok(*id);
+        }
+
+        if let Some(id) = self.prev_frame.remove(&key) {
+            self.this_frame.insert(key, id);
+            return Ok(id);
+        }
+
+        let info = ImageInfo::new(ImageFlags::PREMULTIPLIED, RAMP_SIZE, 1, PixelFormat::Rgba8);
+        let id = images.alloc(renderer, info)?;
+
+        let ramp = color_ramp(stops);
+
+        if let Err(err) = images.update(renderer, id, Img::new(&ramp[..], RAMP_SIZE, 1).into(), 0, 0) {
+            images.remove(renderer, id);
+            return Err(err);
+        }
+
+        self.this_frame.insert(key, id);
+
+        Ok(id)
+    }
+
+    /// Frees the ramps that were not used since the previous call. Called once per frame after rendering.
+    pub fn release_old_gradients<R: Renderer>(&mut self, images: &mut ImageStore<R::Image>, renderer: &mut R) {
+        for (_, id) in self.prev_frame.drain() {
+            images.remove(renderer, id);
+        }
+
+        std::mem::swap(&mut self.prev_frame, &mut self.this_frame);
+    }
+}
+
+fn gradient_key(stops: &[(f32, Color)]) -> GradientKey {
+    stops
+        .iter()
+        .flat_map(|(offset, color)| {
+            vec![
+                offset.to_bits(),
+                color.r.to_bits(),
+                color.g.to_bits(),
+                color.b.to_bits(),
+                color.a.to_bits(),
+            ]
+        })
+        .collect()
+}
+
+/// Renders premultiplied colors of the stops, which must be sorted by offset.
+///
+/// Colors are interpolated premultiplied, the same way the shader mixes two color gradients.
+fn color_ramp(stops: &[(f32, Color)]) -> Vec<RGBA8> {
+    let stops: Vec<(f32, [f32; 4])> = stops
+        .iter()
+        .map(|(offset, color)| (*offset, color.premultiplied().to_array()))
+        .collect();
+
+    let to_u8 = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+
+    (0..RAMP_SIZE)
+        .map(|i| {
+            let color = color_at(&stops, i as f32 / (RAMP_SIZE - 1) as f32);
+
+            RGBA8::new(to_u8(color[0]), to_u8(color[1]), to_u8(color[2]), to_u8(color[3]))
+        })
+        .collect()
+}
+
+fn color_at(stops: &[(f32, [f32; 4])], t: f32) -> [f32; 4] {
+    let (first_offset, first_color) = stops[0];
+
+    if t <= first_offset {
+        return first_color;
+    }
+
+    for pair in stops.windows(2) {
+        let (start_offset, start_color) = pair[0];
+        let (end_offset, end_color) = pair[1];
+
+        if t <= end_offset {
+            let range = end_offset - start_offset;
+
+            // Stops sharing an offset make a hard transition
+            if range <= std::f32::EPSILON {
+                return end_color;
+            }
+
+            let f = (t - start_offset) / range;
+
+            let mut color = start_color;
+
+            for (c, end) in color.iter_mut().zip(end_color.iter()) {
+                *c += (end - *c) * f;
+            }
+
+            return color;
+        }
+    }
+
+    stops[stops.len() - 1].1
+}
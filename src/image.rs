@@ -15,6 +15,23 @@ use crate::{ErrorKind, Renderer, Size};
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct ImageId(pub Index);
 
+// Serialized as the raw arena index, which only identifies the image on the canvas it was created on.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ImageId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.0.into_raw_parts(), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ImageId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (index, generation) = serde::Deserialize::deserialize(deserializer)?;
+
+        Ok(Self(Index::from_raw_parts(index, generation)))
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum PixelFormat {
     Rgb8,
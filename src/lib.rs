@@ -2,8 +2,10 @@
 #[macro_use]
 extern crate serde;
 
+use std::borrow::Cow;
 use std::ops::Range;
 use std::path::Path as FilePath;
+use std::sync::Arc;
 
 use imgref::ImgVec;
 use rgb::RGBA8;
@@ -26,9 +28,9 @@ mod text;
 mod error;
 pub use error::ErrorKind;
 
-pub use text::{Align, Baseline, FontId, FontMetrics, TextMetrics};
+pub use text::{Align, Baseline, FontId, FontMetrics, Paragraph, TextDirection, TextLine, TextMetrics};
 
-use text::{RenderMode, TextContext};
+use text::{RenderMode, ShapedGlyph, TextContext};
 
 mod image;
 pub use crate::image::{ImageFlags, ImageId, ImageInfo, ImageSource, ImageStore, PixelFormat};
@@ -39,19 +41,32 @@ pub use color::Color;
 pub mod renderer;
 pub use renderer::{CommandEncoder, MtlStencilTexture, MtlTexture, RenderTarget, Renderer};
 
-use renderer::{Command, CommandType, Drawable, Params, ShaderType, Vertex};
+use renderer::{Command, CommandType, Drawable, Params, ShaderType, VectorGlyph, VectorShape, Vertex};
 
 pub(crate) mod geometry;
 use geometry::*;
-pub use geometry::{Size, Transform2D};
+pub use geometry::{Rect, Size, Transform2D};
 
 mod paint;
 pub use paint::Paint;
 use paint::PaintFlavor;
 
+mod gradient_store;
+use gradient_store::GradientStore;
+
 mod path;
-use path::Convexity;
-pub use path::{Path, Solidity};
+use path::{Convexity, PathCache};
+pub use path::{Path, Solidity, Verb};
+
+mod picture;
+pub use picture::Picture;
+use picture::PictureCommand;
+
+mod filter;
+pub use filter::{Filter, ImageFilter};
+
+#[cfg(feature = "svg")]
+pub mod svg;
 
 #[macro_use]
 extern crate static_assertions;
@@ -76,6 +91,7 @@ impl Default for FillRule {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BlendFactor {
     Zero,
     One,
@@ -91,6 +107,7 @@ pub enum BlendFactor {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CompositeOperation {
     SourceOver,
     SourceIn,
@@ -144,6 +161,22 @@ impl Default for CompositeOperationState {
     }
 }
 
+// A layer started by begin_layer. The rectangle it covers is in the pixels of the render target that was
+// current when it was started.
+#[derive(Clone, Debug)]
+struct Layer {
+    image: ImageId,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    alpha: f32,
+    composite_operation: CompositeOperation,
+    filters: Vec<ImageFilter>,
+    // Length of the state stack when the layer was started, end_layer restores it
+    state_depth: usize,
+}
+
 #[derive(Copy, Clone, Debug)]
 struct Scissor {
     transform: Transform2D,
@@ -187,11 +220,55 @@ impl Default for LineJoin {
     }
 }
 
-#[derive(Copy, Clone)]
+/// How a gradient is continued beyond its start and end.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SpreadMode {
+    /// The start and end colors are extended.
+    Pad,
+    /// The gradient starts over.
+    Repeat,
+    /// The gradient is mirrored every other repetition.
+    Reflect,
+}
+
+impl Default for SpreadMode {
+    fn default() -> Self {
+        Self::Pad
+    }
+}
+
+impl SpreadMode {
+    pub(crate) fn to_f32(self) -> f32 {
+        match self {
+            Self::Pad => 0.0,
+            Self::Repeat => 1.0,
+            Self::Reflect => 2.0,
+        }
+    }
+
+    pub(crate) fn from_f32(value: f32) -> Self {
+        match value as u32 {
+            1 => Self::Repeat,
+            2 => Self::Reflect,
+            _ => Self::Pad,
+        }
+    }
+}
+
+#[derive(Clone)]
 struct State {
     composite_operation: CompositeOperationState,
     transform: Transform2D,
     scissor: Scissor,
+    // Flattened in device space, with contours marked solid or hole so it fills the same with either rule
+    clip: Option<Arc<Path>>,
+    // The part of the scissor that isn't a rectangle, from intersecting scissors with different rotations.
+    // Kept like the clip, apart from it so that reset_scissor doesn't affect the clip.
+    scissor_clip: Option<Arc<Path>>,
+    // The clip intersected with the scissor clip, what drawing is cut to. Updated when either changes,
+    // so that drawing doesn't have to intersect them again.
+    clip_area: Option<Arc<Path>>,
     alpha: f32,
 }
 
@@ -201,11 +278,23 @@ impl Default for State {
             composite_operation: Default::default(),
             transform: Transform2D::identity(),
             scissor: Default::default(),
+            clip: None,
+            scissor_clip: None,
+            clip_area: None,
             alpha: 1.0,
         }
     }
 }
 
+impl State {
+    fn update_clip_area(&mut self) {
+        self.clip_area = match (&self.clip, &self.scissor_clip) {
+            (Some(clip), Some(scissor_clip)) => Some(Arc::new(clip.device_intersect(scissor_clip, FillRule::NonZero))),
+            (clip, scissor_clip) => clip.clone().or_else(|| scissor_clip.clone()),
+        };
+    }
+}
+
 pub trait DynamicImageExt {
     fn convert_rgb_if_needed(self) -> Self;
 }
@@ -236,12 +325,16 @@ pub struct Canvas<T: Renderer + 'static> {
     commands: Vec<Command>,
     verts: Vec<Vertex>,
     images: ImageStore<T::Image>,
+    gradients: GradientStore,
+    transient_images: Vec<ImageId>,
     fringe_width: f32,
     device_px_ratio: f32,
     tess_tol: f32,
     dist_tol: f32,
     frame: usize,
     cache: T::BufferCache,
+    picture: Option<Picture>,
+    layers: Vec<Layer>,
 
     render_target_stack: ngds::Stack<RenderTarget>,
 }
@@ -262,11 +355,15 @@ where
             commands: Default::default(),
             verts: Default::default(),
             images: ImageStore::new(),
+            gradients: Default::default(),
+            transient_images: Default::default(),
             fringe_width: 1.0,
             device_px_ratio: 1.0,
             tess_tol: 0.25,
             dist_tol: 0.01,
             frame: 0,
+            picture: None,
+            layers: Vec::new(),
             render_target_stack: ngds::Stack::new(),
         };
 
@@ -300,6 +397,14 @@ where
     }
 
     pub fn clear_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) {
+        self.record(|| PictureCommand::ClearRect {
+            x,
+            y,
+            width,
+            height,
+            color,
+        });
+
         let cmd = Command::new(CommandType::ClearRect {
             x,
             y,
@@ -327,9 +432,31 @@ where
     pub fn flush(&mut self) {
         self.renderer
             .render(&self.images, &mut self.cache, &self.verts, &self.commands);
+        self.gradients
+            .release_old_gradients(&mut self.images, &mut self.renderer);
+
+        // The images of open layers are still drawn to, they're deleted by the first flush after their layer ends
+        let layers = &self.layers;
+        let images = &mut self.images;
+        let renderer = &mut self.renderer;
+
+        self.transient_images.retain(|id| {
+            if layers.iter().any(|layer| layer.image == *id) {
+                return true;
+            }
+
+            images.remove(renderer, *id);
+            false
+        });
+
         self.commands.clear();
         self.verts.clear();
         self.frame += 1;
+
+        // Drawing in an open layer continues on its image
+        if !self.layers.is_empty() {
+            self.append_cmd(Command::new(CommandType::SetRenderTarget(self.current_render_target)));
+        }
     }
 
     pub fn screenshot(&mut self) -> Result<ImgVec<RGBA8>, ErrorKind> {
@@ -337,13 +464,31 @@ where
         self.renderer.screenshot(&self.images)
     }
 
+    // Recording
+
+    /// Starts recording the calls made to the canvas into a [Picture](struct.Picture.html), discarding any
+    /// recording in progress.
+    ///
+    /// The calls are still drawn as usual. Layers are recorded, but other changes of the render target and custom
+    /// commands are not, drawing with an image that was rendered to shows up in the picture as a use of that image.
+    pub fn begin_recording(&mut self) {
+        self.picture = Some(Picture::default());
+    }
+
+    /// Stops recording and returns what was recorded since begin_recording, None if nothing was being recorded.
+    pub fn end_recording(&mut self) -> Option<Picture> {
+        self.picture.take()
+    }
+
     // State Handling
 
     /// Pushes and saves the current render state into a state stack.
     ///
     /// A matching restore() must be used to restore the state.
     pub fn save(&mut self) {
-        let state = self.state_stack.last().map_or_else(State::default, |state| *state);
+        self.record(|| PictureCommand::Save);
+
+        let state = self.state_stack.last().map_or_else(State::default, State::clone);
 
         self.state_stack.push(state);
     }
@@ -352,15 +497,19 @@ where
     ///
     /// Restoring the initial/first state will just reset it to the defaults
     pub fn restore(&mut self) {
+        self.record(|| PictureCommand::Restore);
+
         if self.state_stack.len() > 1 {
             self.state_stack.pop();
         } else {
-            self.reset();
+            *self.state_mut() = Default::default();
         }
     }
 
     /// Resets current state to default values. Does not affect the state stack.
     pub fn reset(&mut self) {
+        self.record(|| PictureCommand::Reset);
+
         *self.state_mut() = Default::default();
     }
 
@@ -396,11 +545,15 @@ where
     ///
     /// Already transparent paths will get proportionally more transparent as well.
     pub fn set_global_alpha(&mut self, alpha: f32) {
+        self.record(|| PictureCommand::SetGlobalAlpha(alpha));
+
         self.state_mut().alpha = alpha;
     }
 
     /// Sets the composite operation.
     pub fn global_composite_operation(&mut self, op: CompositeOperation) {
+        self.record(|| PictureCommand::GlobalCompositeOperation(op));
+
         self.state_mut().composite_operation = CompositeOperationState::new(op);
     }
 
@@ -417,6 +570,13 @@ where
         src_alpha: BlendFactor,
         dst_alpha: BlendFactor,
     ) {
+        self.record(|| PictureCommand::GlobalCompositeBlendFuncSeparate {
+            src_rgb,
+            dst_rgb,
+            src_alpha,
+            dst_alpha,
+        });
+
         self.state_mut().composite_operation = CompositeOperationState {
             src_rgb,
             src_alpha,
@@ -449,6 +609,160 @@ where
         }
     }
 
+    // Layers
+
+    /// Starts a layer, drawing after this call goes to a transient image until the matching end_layer composites
+    /// it back with the given alpha and composite operation.
+    ///
+    /// Shapes drawn in a layer blend with each other first, so that the alpha applies to the group as a whole.
+    /// `bounds` is the area the layer covers in the current coordinate system, drawing outside of it is lost.
+    /// The layer starts with the current transform and scissor, end_layer restores the state this was called in.
+    /// Layers nest and stay open when the canvas is flushed.
+    pub fn begin_layer(&mut self, bounds: Rect, alpha: f32, op: CompositeOperation) -> Result<(), ErrorKind> {
+        // Layers cover whole pixels of the render target, the one of a parent layer or the canvas
+        let device = transformed_bounds(&self.state().transform, bounds);
+        let (target_width, target_height) = self.target_size();
+
+        let minx = device.x.floor().max(0.0).min(target_width as f32);
+        let miny = device.y.floor().max(0.0).min(target_height as f32);
+        let maxx = (device.x + device.w).ceil().max(minx).min(target_width as f32);
+        let maxy = (device.y + device.h).ceil().max(miny).min(target_height as f32);
+
+        let (x, y) = (minx as u32, miny as u32);
+        let (width, height) = ((maxx - minx) as u32, (maxy - miny) as u32);
+
+        // Empty layers still need an image for their drawing to go to
+        let (image_width, image_height) = (width.max(1), height.max(1));
+        let image = self.create_transient_image(image_width as usize, image_height as usize)?;
+
+        self.record(|| PictureCommand::BeginLayer { bounds, alpha, op });
+
+        self.layers.push(Layer {
+            image,
+            x,
+            y,
+            width,
+            height,
+            alpha,
+            composite_operation: op,
+            filters: Vec::new(),
+            state_depth: self.state_stack.len(),
+        });
+
+        // Move the origin of the render target to the corner of the layer
+        let offset = Transform2D::new_translation(-(x as f32), -(y as f32));
+
+        let mut state = self.state().clone();
+        state.transform.multiply(&offset);
+        state.scissor.transform.multiply(&offset);
+
+        for clip in [&mut state.clip, &mut state.scissor_clip].iter_mut() {
+            if let Some(path) = clip.as_ref() {
+                **clip = Some(Arc::new(self.flatten(&mut path.as_ref().clone(), &offset)));
+            }
+        }
+
+        state.update_clip_area();
+        state.alpha = 1.0;
+        state.composite_operation = Default::default();
+        self.state_stack.push(state);
+
+        self.push_render_target(RenderTarget::Image(image));
+
+        self.append_cmd(Command::new(CommandType::ClearRect {
+            x: 0,
+            y: 0,
+            width: image_width,
+            height: image_height,
+            color: Color::clear(),
+        }));
+
+        Ok(())
+    }
+
+    /// Ends the last layer started with begin_layer and composites it onto the render target it was started on.
+    pub fn end_layer(&mut self) {
+        let layer = match self.layers.pop() {
+            Some(layer) => layer,
+            None => return,
+        };
+
+        self.record(|| PictureCommand::EndLayer);
+
+        self.pop_render_target();
+
+        // Also drops the states saved in the layer and not restored
+        self.state_stack.truncate(layer.state_depth.max(1));
+
+        if layer.width == 0 || layer.height == 0 {
+            return;
+        }
+
+        let mut image = layer.image;
+
+        // The filters are applied to a copy of the layer, drawn unfiltered if that fails
+        if !layer.filters.is_empty() {
+            let (width, height) = (layer.width as usize, layer.height as usize);
+
+            if let Ok(filtered) = self.create_transient_image(width, height) {
+                if self.apply_image_filters(layer.image, filtered, &layer.filters).is_ok() {
+                    image = filtered;
+                }
+            }
+        }
+
+        // The layer is drawn in render target pixels with the global alpha and scissor of the restored state
+        let mut state = self.state().clone();
+        state.transform = Transform2D::identity();
+        state.composite_operation = CompositeOperationState::new(layer.composite_operation);
+        self.state_stack.push(state);
+
+        let (x, y) = (layer.x as f32, layer.y as f32);
+        let (width, height) = (layer.width as f32, layer.height as f32);
+
+        let mut path = Path::new();
+        path.rect(x, y, width, height);
+
+        let paint = Paint::image(image, x, y, width, height, 0.0, layer.alpha);
+
+        // Already recorded as part of the layer
+        let picture = self.picture.take();
+        self.fill_path(&mut path, paint);
+        self.picture = picture;
+
+        self.state_stack.pop();
+    }
+
+    // Size of the render target in pixels, the one of the image drawn to, such as a layer, or the canvas
+    fn target_size(&self) -> (u32, u32) {
+        match self.current_render_target {
+            RenderTarget::Image(id) => self.images.info(id).map_or((self.width, self.height), |info| {
+                (info.width() as u32, info.height() as u32)
+            }),
+            _ => (self.width, self.height),
+        }
+    }
+
+    // Creates an image to render into for intermediate results, such as layers and filter passes
+    fn create_transient_image(&mut self, width: usize, height: usize) -> Result<ImageId, ErrorKind> {
+        let flags = ImageFlags::PREMULTIPLIED | ImageFlags::FLIP_Y;
+        let image = self.create_image_empty(width, height, PixelFormat::Rgba8, flags)?;
+
+        // The image is still needed when the commands are rendered, it's deleted on flush.
+        self.transient_images.push(image);
+
+        Ok(image)
+    }
+
+    /// Sets the filters the last layer started with begin_layer is drawn through when it's ended, see ImageFilter.
+    pub fn set_layer_filters(&mut self, filters: &[ImageFilter]) {
+        self.record(|| PictureCommand::SetLayerFilters(filters.to_vec()));
+
+        if let Some(layer) = self.layers.last_mut() {
+            layer.filters = filters.to_vec();
+        }
+    }
+
     fn append_cmd(&mut self, cmd: Command) {
         self.commands.push(cmd);
     }
@@ -583,16 +897,22 @@ where
     //     self.state_mut().transform.premultiply(&transform);
     // }
     pub fn set_transform(&mut self, transform: Transform2D) {
+        self.record(|| PictureCommand::SetTransform(transform));
+
         // self.state_mut().transform.premultiply(&transform)
         self.state_mut().transform = transform;
     }
 
     pub fn premultiply_transform(&mut self, transform: Transform2D) {
+        self.record(|| PictureCommand::PremultiplyTransform(transform));
+
         self.state_mut().transform.premultiply(&transform);
     }
 
     /// Resets current transform to a identity matrix.
     pub fn reset_transform(&mut self) {
+        self.record(|| PictureCommand::ResetTransform);
+
         self.state_mut().transform = Transform2D::identity();
     }
 
@@ -600,47 +920,47 @@ where
     pub fn translate(&mut self, x: f32, y: f32) {
         let mut t = Transform2D::identity();
         t.translate(x, y);
-        self.state_mut().transform.premultiply(&t);
+        self.premultiply_transform(t);
     }
 
     pub fn flip_x(&mut self) {
         let mut t = Transform2D::identity();
         t.flip_x();
-        self.state_mut().transform.premultiply(&t);
+        self.premultiply_transform(t);
     }
 
     pub fn flip_y(&mut self) {
         let mut t = Transform2D::identity();
         t.flip_y();
-        self.state_mut().transform.premultiply(&t);
+        self.premultiply_transform(t);
     }
 
     /// Rotates the current coordinate system. Angle is specified in radians.
     pub fn rotate(&mut self, angle: f32) {
         let mut t = Transform2D::identity();
         t.rotate(angle);
-        self.state_mut().transform.premultiply(&t);
+        self.premultiply_transform(t);
     }
 
     /// Skews the current coordinate system along X axis. Angle is specified in radians.
     pub fn skew_x(&mut self, angle: f32) {
         let mut t = Transform2D::identity();
         t.skew_x(angle);
-        self.state_mut().transform.premultiply(&t);
+        self.premultiply_transform(t);
     }
 
     /// Skews the current coordinate system along Y axis. Angle is specified in radians.
     pub fn skew_y(&mut self, angle: f32) {
         let mut t = Transform2D::identity();
         t.skew_y(angle);
-        self.state_mut().transform.premultiply(&t);
+        self.premultiply_transform(t);
     }
 
     /// Scales the current coordinate system.
     pub fn scale(&mut self, x: f32, y: f32) {
         let mut t = Transform2D::identity();
         t.scale(x, y);
-        self.state_mut().transform.premultiply(&t);
+        self.premultiply_transform(t);
     }
 
     // Scissoring
@@ -649,38 +969,37 @@ where
     ///
     /// The scissor rectangle is transformed by the current transform.
     pub fn scissor(&mut self, x: f32, y: f32, w: f32, h: f32) {
-        let state = self.state_mut();
+        self.record(|| PictureCommand::Scissor { x, y, w, h });
 
-        let w = w.max(0.0);
-        let h = h.max(0.0);
-
-        let mut transform = Transform2D::new_translation(x + w * 0.5, y + h * 0.5);
-        transform.multiply(&state.transform);
-        state.scissor.transform = transform;
+        let state = self.state_mut();
+        state.scissor_clip = None;
+        state.update_clip_area();
 
-        state.scissor.extent = Some([w * 0.5, h * 0.5]);
+        self.set_scissor(x, y, w, h);
     }
 
     /// Intersects current scissor rectangle with the specified rectangle.
     ///
     /// The scissor rectangle is transformed by the current transform.
     /// Note: in case the rotation of previous scissor rect differs from
-    /// the current one, the intersection will be done between the specified
-    /// rectangle and the previous scissor rectangle transformed in the current
-    /// transform space. The resulting shape is always rectangle.
+    /// the current one, the intersection is no longer a rectangle. The
+    /// scissor is then set to the specified rectangle and the previous
+    /// one is kept as a clip area, see clip_path.
     pub fn intersect_scissor(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        self.record(|| PictureCommand::IntersectScissor { x, y, w, h });
+
         let state = self.state_mut();
 
         // If no previous scissor has been set, set the scissor as current scissor.
         if state.scissor.extent.is_none() {
-            self.scissor(x, y, w, h);
+            self.set_scissor(x, y, w, h);
             return;
         }
 
         let extent = state.scissor.extent.unwrap();
 
         // Transform the current scissor rect into current transform space.
-        // If there is difference in rotation, this will be approximation.
+        // If there is difference in rotation, its bounds are larger than the rect.
 
         let mut pxform = state.scissor.transform;
 
@@ -698,12 +1017,82 @@ where
         let rect = Rect::new(pxform[4] - tex, pxform[5] - tey, tex * 2.0, tey * 2.0);
         let res = rect.intersect(Rect::new(x, y, w, h));
 
-        self.scissor(res.x, res.y, res.w, res.h);
+        // The bounds are exact if the sides of the current scissor rect are parallel to the axes
+        let tolerance = 1e-6 * (pxform[0].abs() + pxform[1].abs() + pxform[2].abs() + pxform[3].abs());
+        let aligned = (pxform[1].abs() <= tolerance && pxform[2].abs() <= tolerance)
+            || (pxform[0].abs() <= tolerance && pxform[3].abs() <= tolerance);
+
+        if !aligned {
+            let scissor_transform = state.scissor.transform;
+
+            let mut quad = Path::new();
+            quad.rect(-ex, -ey, ex * 2.0, ey * 2.0);
+
+            let quad = self.flatten(&mut quad, &scissor_transform);
+
+            let scissor_clip = match &self.state().scissor_clip {
+                Some(scissor_clip) => quad.device_intersect(scissor_clip, FillRule::NonZero),
+                None => quad,
+            };
+
+            let state = self.state_mut();
+            state.scissor_clip = Some(Arc::new(scissor_clip));
+            state.update_clip_area();
+        }
+
+        self.set_scissor(res.x, res.y, res.w, res.h);
     }
 
     /// Reset and disables scissoring.
     pub fn reset_scissor(&mut self) {
-        self.state_mut().scissor = Scissor::default();
+        self.record(|| PictureCommand::ResetScissor);
+
+        let state = self.state_mut();
+        state.scissor = Scissor::default();
+        state.scissor_clip = None;
+        state.update_clip_area();
+    }
+
+    // Clipping
+
+    /// Intersects the current clip area with the area the path covers when filled with the fill rule.
+    ///
+    /// The path is transformed by the current transform. Like the scissor, the clip area is part of the state that is
+    /// saved and restored. Drawing is clipped by cutting the shapes to the clip area before they are rendered, so
+    /// clipped edges are antialiased and clipping works the same with every renderer. Vector renderers are given
+    /// the clip instead and leave the cutting to the viewer.
+    ///
+    /// Cutting runs a boolean operation on the CPU for every clipped fill and stroke, which takes time growing with
+    /// the number of edges of the shape and the clip. The result is kept with the path and reused while its
+    /// transform, the clip and the stroke style stay the same, so paths and clips built again every frame pay the
+    /// cost every frame. Prefer an axis aligned scissor for rectangular clips, it's applied by the renderer for free.
+    pub fn clip_path(&mut self, path: &mut Path, fill_rule: FillRule) {
+        self.record(|| PictureCommand::ClipPath {
+            path: path.clone(),
+            fill_rule,
+        });
+
+        let transform = self.state().transform;
+        let flattened = self.flatten(path, &transform);
+
+        // Boolean operations give contours marked solid or hole, which then fill the same with either rule
+        let clip = match &self.state().clip {
+            Some(clip) => flattened.device_intersect(clip, fill_rule),
+            None => flattened.device_union(&Path::new(), fill_rule),
+        };
+
+        let state = self.state_mut();
+        state.clip = Some(Arc::new(clip));
+        state.update_clip_area();
+    }
+
+    /// Removes the clip area set with clip_path.
+    pub fn reset_clip(&mut self) {
+        self.record(|| PictureCommand::ResetClip);
+
+        let state = self.state_mut();
+        state.clip = None;
+        state.update_clip_area();
     }
 
     // Paths
@@ -727,6 +1116,37 @@ where
         path_cache.contains_point(x, y, fill_rule)
     }
 
+    /// Returns true if the specified point (x,y) is covered by stroking the provided path with `paint`,
+    /// and false otherwise.
+    ///
+    /// The stroke line width, joins, caps and dash pattern are taken into account, but not antialiasing.
+    pub fn stroke_contains_point(&mut self, path: &mut Path, paint: &Paint, x: f32, y: f32) -> bool {
+        let transform = self.state().transform;
+
+        // The path cache saves a flattened and transformed version of the path.
+        let mut path_cache = path.cache(&transform, self.tess_tol, self.dist_tol);
+
+        let mut dashed_cache = path_cache.dashed_by(paint, transform.average_scale());
+
+        if let Some(dashed) = &mut dashed_cache {
+            path_cache = dashed;
+        }
+
+        let line_width = (paint.line_width * transform.average_scale()).max(0.0);
+
+        path_cache.expand_stroke(
+            line_width * 0.5,
+            0.0,
+            paint.line_cap_start,
+            paint.line_cap_end,
+            paint.line_join,
+            paint.miter_limit,
+            self.tess_tol,
+        );
+
+        path_cache.stroke_contains_point(x, y)
+    }
+
     pub fn path_bbox(&self, path: &mut Path) -> Bounds {
         let transform = self.state().transform;
 
@@ -737,35 +1157,91 @@ where
     }
 
     /// Fills the current path with current fill style.
-    pub fn fill_path(&mut self, path: &mut Path, mut paint: Paint) {
+    pub fn fill_path(&mut self, path: &mut Path, paint: Paint) {
+        self.record(|| PictureCommand::FillPath {
+            path: path.clone(),
+            paint,
+        });
+
+        self.fill_path_as(path, paint, None);
+    }
+
+    // Fills the outline of a glyph, renderers that embed fonts show the glyph instead.
+    pub(crate) fn fill_glyph(&mut self, path: &mut Path, paint: Paint, glyph: VectorGlyph) {
+        self.fill_path_as(path, paint, Some(glyph));
+    }
+
+    fn fill_path_as(&mut self, path: &mut Path, mut paint: Paint, glyph: Option<VectorGlyph>) {
         let transform = self.state().transform;
 
+        // Transform paint, any transform it already has is relative to the path
+        paint.transform.multiply(&transform);
+
+        // Apply global alpha
+        paint.mul_alpha(self.state().alpha);
+
+        match self.clip_area() {
+            // Vector renderers cut the shape to the clip area themselves, others pay for a boolean operation
+            // whenever the path, transform or clip changed since the path was last drawn
+            Some(clip) if !T::is_vector() => {
+                let (tess_tol, dist_tol) = (self.tess_tol, self.dist_tol);
+                let fill_rule = paint.fill_rule;
+
+                let clipped = path.clipped(&transform, &clip, fill_rule as u64, |path| {
+                    let flattened = path.cache(&transform, tess_tol, dist_tol).to_path(dist_tol);
+
+                    flattened.device_intersect(&clip, fill_rule)
+                });
+
+                self.fill_device_path(clipped, &Transform2D::identity(), paint, None);
+            }
+            _ => self.fill_device_path(path, &transform, paint, glyph),
+        }
+    }
+
+    // Fills a path with a paint that is already in device space, with the global alpha applied.
+    fn fill_device_path(
+        &mut self,
+        path: &mut Path,
+        transform: &Transform2D,
+        mut paint: Paint,
+        glyph: Option<VectorGlyph>,
+    ) {
+        // Vector renderers get the curves, wound the way the path cache winds the flattened contours
+        let vector_path = if T::is_vector() {
+            Some(path.transformed(transform, true))
+        } else {
+            None
+        };
+
         // The path cache saves a flattened and transformed version of the path.
-        let path_cache = path.cache(&transform, self.tess_tol, self.dist_tol);
+        let path_cache = path.cache(transform, self.tess_tol, self.dist_tol);
+
+        // Early out if path is outside the render target
+        let (target_width, target_height) = self.target_size();
 
-        // Early out if path is outside the canvas bounds
         if path_cache.bounds.maxx < 0.0
-            || path_cache.bounds.minx > self.width()
+            || path_cache.bounds.minx > target_width as f32
             || path_cache.bounds.maxy < 0.0
-            || path_cache.bounds.miny > self.height()
+            || path_cache.bounds.miny > target_height as f32
         {
             return;
         }
 
-        // Transform paint
-        paint.transform = transform;
-
-        // Apply global alpha
-        paint.mul_alpha(self.state().alpha);
-
         let scissor = self.state().scissor;
 
         // Calculate fill vertices.
         // expand_fill will fill path_cache.contours[].{stroke, fill} with vertex data for the GPU
         // fringe_with is the size of the strip of triangles generated at the path border used for AA
-        let fringe_with = if paint.anti_alias() { self.fringe_width } else { 0.0 };
+        let fringe_with = if paint.anti_alias() && !T::is_vector() {
+            self.fringe_width
+        } else {
+            0.0
+        };
         path_cache.expand_fill(fringe_with, LineJoin::Miter, 2.4);
 
+        let image = self.paint_image(&mut paint);
+
         // GPU uniforms
         let flavor = if path_cache.contours.len() == 1 && path_cache.contours[0].convexity == Convexity::Convex {
             let params = Params::new(
@@ -803,8 +1279,21 @@ where
         cmd.fill_rule = paint.fill_rule;
         cmd.composite_operation = self.state().composite_operation;
 
-        if let PaintFlavor::Image { id, .. } = paint.flavor {
-            cmd.image = Some(id);
+        cmd.image = image;
+
+        if let Some(path) = vector_path {
+            cmd.vector_shape = Some(Box::new(VectorShape {
+                path,
+                paint,
+                stroke: false,
+                clip: self.clip_area(),
+                glyph,
+            }));
+        }
+
+        // Image filters bind their lookup table in place of the alpha mask
+        if let PaintFlavor::FilterImage { .. } = paint.flavor {
+            cmd.alpha_mask = paint.alpha_mask();
         }
 
         // All verts from all shapes are kept in a single buffer here in the canvas.
@@ -871,24 +1360,32 @@ where
 
     /// Strokes the provided Path using Paint.
     pub fn stroke_path(&mut self, path: &mut Path, mut paint: Paint) {
+        self.record(|| PictureCommand::StrokePath {
+            path: path.clone(),
+            paint,
+        });
+
         let transform = self.state().transform;
 
         // The path cache saves a flattened and transformed version of the path.
-        let path_cache = path.cache(&transform, self.tess_tol, self.dist_tol);
+        let bounds = path.cache(&transform, self.tess_tol, self.dist_tol).bounds;
 
-        // Early out if path is outside the canvas bounds
-        if path_cache.bounds.maxx < 0.0
-            || path_cache.bounds.minx > self.width()
-            || path_cache.bounds.maxy < 0.0
-            || path_cache.bounds.miny > self.height()
+        // Early out if path is outside the render target
+        let (target_width, target_height) = self.target_size();
+
+        if bounds.maxx < 0.0
+            || bounds.minx > target_width as f32
+            || bounds.maxy < 0.0
+            || bounds.miny > target_height as f32
         {
             return;
         }
 
+        let dash_scale = transform.average_scale();
         let scissor = self.state().scissor;
 
-        // Transform paint
-        paint.transform = transform;
+        // Transform paint, any transform it already has is relative to the path
+        paint.transform.multiply(&transform);
 
         // Scale stroke width by current transform scale.
         // Note: I don't know why the original author clamped the max stroke width to 200, but it didn't
@@ -897,7 +1394,7 @@ where
         //paint.set_stroke_width((paint.stroke_width() * transform.average_scale()).max(0.0).min(200.0));
         paint.line_width = (paint.line_width * transform.average_scale()).max(0.0);
 
-        if paint.line_width < self.fringe_width {
+        if paint.line_width < self.fringe_width && !T::is_vector() {
             // If the stroke width is less than pixel size, use alpha to emulate coverage.
             // Since coverage is area, scale by alpha*alpha.
             let alpha = (paint.line_width / self.fringe_width).max(0.0).min(1.0);
@@ -909,9 +1406,62 @@ where
         // Apply global alpha
         paint.mul_alpha(self.state().alpha);
 
+        // Clipped strokes are cut to the clip area as outlines and then filled, vector renderers cut them
+        // themselves. Like fills, the boolean operation is repeated only when the path, transform, clip or
+        // stroke style changed since the path was last drawn.
+        if let Some(clip) = self.clip_area().filter(|_| !T::is_vector()) {
+            let (tess_tol, dist_tol) = (self.tess_tol, self.dist_tol);
+
+            let clipped = path.clipped(&transform, &clip, paint.stroke_key(), |path| {
+                let mut path_cache = path.cache(&transform, tess_tol, dist_tol);
+                let mut dashed_cache = path_cache.dashed_by(&paint, dash_scale);
+
+                if let Some(dashed) = &mut dashed_cache {
+                    path_cache = dashed;
+                }
+
+                path_cache.expand_stroke(
+                    paint.line_width * 0.5,
+                    0.0,
+                    paint.line_cap_start,
+                    paint.line_cap_end,
+                    paint.line_join,
+                    paint.miter_limit,
+                    tess_tol,
+                );
+
+                let strips = path_cache.contours.iter().map(|contour| &contour.stroke[..]);
+                let outline = Path::from_triangle_strips(strips, dist_tol);
+
+                outline.device_intersect(&clip, FillRule::NonZero)
+            });
+
+            self.fill_device_path(clipped, &Transform2D::identity(), paint, None);
+            return;
+        }
+
+        let vector_path = if T::is_vector() {
+            Some(path.transformed(&transform, false))
+        } else {
+            None
+        };
+
+        let mut path_cache = path.cache(&transform, self.tess_tol, self.dist_tol);
+
+        // Split the flattened contours into dashes. The cached path is left untouched since it's shared with fills.
+        let mut dashed_cache = path_cache.dashed_by(&paint, dash_scale);
+
+        if let Some(dashed) = &mut dashed_cache {
+            path_cache = dashed;
+        }
+
         // Calculate stroke vertices.
         // expand_stroke will fill path_cache.contours[].stroke with vertex data for the GPU
-        let fringe_with = if paint.anti_alias() { self.fringe_width } else { 0.0 };
+        let fringe_with = if paint.anti_alias() && !T::is_vector() {
+            self.fringe_width
+        } else {
+            0.0
+        };
         path_cache.expand_stroke(
             paint.line_width * 0.5,
             fringe_with,
@@ -922,6 +1472,8 @@ where
             self.tess_tol,
         );
 
+        let image = self.paint_image(&mut paint);
+
         // GPU uniforms
         let params = Params::new(
             &self.images,
@@ -954,8 +1506,24 @@ where
         let mut cmd = Command::new(flavor);
         cmd.composite_operation = self.state().composite_operation;
 
-        if let PaintFlavor::Image { id, .. } = paint.flavor {
-            cmd.image = Some(id);
+        cmd.image = image;
+
+        if let Some(path) = vector_path {
+            let mut paint = paint;
+
+            for len in &mut paint.line_dash[..paint.line_dash_len] {
+                *len *= dash_scale;
+            }
+
+            paint.line_dash_offset *= dash_scale;
+
+            cmd.vector_shape = Some(Box::new(VectorShape {
+                path,
+                paint,
+                stroke: true,
+                clip: self.clip_area(),
+                glyph: None,
+            }));
         }
 
         // All verts from all shapes are kept in a single buffer here in the canvas.
@@ -1015,7 +1583,7 @@ where
 
         if let Some(Some(id)) = paint.font_ids.get(0) {
             if let Some(font) = self.text_context.font(*id) {
-                return Ok(font.metrics(paint.font_size));
+                return Ok(font.metrics(paint.font_size, &paint.font_variations));
             }
         }
 
@@ -1065,6 +1633,39 @@ where
         Ok(res)
     }
 
+    /// Breaks the text into lines that fit max_width and lays them out below each other, starting with
+    /// the top of the first line at y.
+    ///
+    /// Lines break at unicode line break opportunities and at newlines. The paint's text align positions each
+    /// line inside max_width and its line height multiplies the distance between lines.
+    pub fn measure_paragraph<S: AsRef<str>>(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        text: S,
+        paint: Paint,
+    ) -> Result<Paragraph, ErrorKind> {
+        self.measure_paragraph_spans(x, y, max_width, &[(text, paint)])
+    }
+
+    /// Lays out a paragraph made of spans of text with different paints like measure_paragraph does.
+    ///
+    /// Lines break across spans and the spans on a line share its baseline. The text align of the first span
+    /// aligns all lines.
+    pub fn measure_paragraph_spans<S: AsRef<str>>(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        spans: &[(S, Paint)],
+    ) -> Result<Paragraph, ErrorKind> {
+        let (mut paragraph, _) = self.layout_paragraph(x, y, max_width, spans)?;
+        paragraph.scale(1.0 / (self.font_scale() * self.device_px_ratio));
+
+        Ok(paragraph)
+    }
+
     pub fn fill_text<S: AsRef<str>>(
         &mut self,
         x: f32,
@@ -1072,7 +1673,16 @@ where
         text: S,
         paint: Paint,
     ) -> Result<TextMetrics, ErrorKind> {
-        self.draw_text(x, y, text.as_ref(), paint, RenderMode::Fill)
+        let text = text.as_ref();
+
+        self.record(|| PictureCommand::FillText {
+            x,
+            y,
+            text: text.to_string(),
+            paint,
+        });
+
+        self.draw_text(x, y, text, paint, RenderMode::Fill)
     }
 
     pub fn stroke_text<S: AsRef<str>>(
@@ -1082,11 +1692,136 @@ where
         text: S,
         paint: Paint,
     ) -> Result<TextMetrics, ErrorKind> {
-        self.draw_text(x, y, text.as_ref(), paint, RenderMode::Stroke)
+        let text = text.as_ref();
+
+        self.record(|| PictureCommand::StrokeText {
+            x,
+            y,
+            text: text.to_string(),
+            paint,
+        });
+
+        self.draw_text(x, y, text, paint, RenderMode::Stroke)
+    }
+
+    /// Fills the text broken into lines like measure_paragraph does.
+    pub fn fill_paragraph<S: AsRef<str>>(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        text: S,
+        paint: Paint,
+    ) -> Result<Paragraph, ErrorKind> {
+        let text = text.as_ref();
+
+        self.record(|| PictureCommand::FillParagraph {
+            x,
+            y,
+            max_width,
+            text: text.to_string(),
+            paint,
+        });
+
+        self.draw_paragraph(x, y, max_width, &[(text, paint)], RenderMode::Fill)
+    }
+
+    /// Strokes the text broken into lines like measure_paragraph does.
+    pub fn stroke_paragraph<S: AsRef<str>>(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        text: S,
+        paint: Paint,
+    ) -> Result<Paragraph, ErrorKind> {
+        let text = text.as_ref();
+
+        self.record(|| PictureCommand::StrokeParagraph {
+            x,
+            y,
+            max_width,
+            text: text.to_string(),
+            paint,
+        });
+
+        self.draw_paragraph(x, y, max_width, &[(text, paint)], RenderMode::Stroke)
+    }
+
+    /// Fills each span of the paragraph with its paint, laid out like measure_paragraph_spans does.
+    pub fn fill_paragraph_spans<S: AsRef<str>>(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        spans: &[(S, Paint)],
+    ) -> Result<Paragraph, ErrorKind> {
+        self.record(|| PictureCommand::FillParagraphSpans {
+            x,
+            y,
+            max_width,
+            spans: spans
+                .iter()
+                .map(|(text, paint)| (text.as_ref().to_string(), *paint))
+                .collect(),
+        });
+
+        self.draw_paragraph(x, y, max_width, spans, RenderMode::Fill)
+    }
+
+    /// Strokes each span of the paragraph with its paint, laid out like measure_paragraph_spans does.
+    pub fn stroke_paragraph_spans<S: AsRef<str>>(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        spans: &[(S, Paint)],
+    ) -> Result<Paragraph, ErrorKind> {
+        self.record(|| PictureCommand::StrokeParagraphSpans {
+            x,
+            y,
+            max_width,
+            spans: spans
+                .iter()
+                .map(|(text, paint)| (text.as_ref().to_string(), *paint))
+                .collect(),
+        });
+
+        self.draw_paragraph(x, y, max_width, spans, RenderMode::Stroke)
     }
 
     // Private
 
+    fn record(&mut self, command: impl FnOnce() -> PictureCommand) {
+        if let Some(picture) = &mut self.picture {
+            picture.record(command(), &self.text_context);
+        }
+    }
+
+    // Returns the area drawing is cut to in device space, the clip and the non rectangular part of the scissor.
+    fn clip_area(&self) -> Option<Arc<Path>> {
+        self.state().clip_area.clone()
+    }
+
+    // Returns the path flattened into polygons in device space, see PathCache::to_path.
+    fn flatten(&self, path: &mut Path, transform: &Transform2D) -> Path {
+        path.cache(transform, self.tess_tol, self.dist_tol)
+            .to_path(self.dist_tol)
+    }
+
+    fn set_scissor(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        let state = self.state_mut();
+
+        let w = w.max(0.0);
+        let h = h.max(0.0);
+
+        let mut transform = Transform2D::new_translation(x + w * 0.5, y + h * 0.5);
+        transform.multiply(&state.transform);
+        state.scissor.transform = transform;
+
+        state.scissor.extent = Some([w * 0.5, h * 0.5]);
+    }
+
     fn transform_text_paint(&self, paint: &mut Paint) {
         let scale = self.font_scale() * self.device_px_ratio;
         paint.font_size *= scale;
@@ -1102,6 +1837,73 @@ where
         mut paint: Paint,
         render_mode: RenderMode,
     ) -> Result<TextMetrics, ErrorKind> {
+        let scale = self.font_scale() * self.device_px_ratio;
+        let invscale = 1.0 / scale;
+
+        self.transform_text_paint(&mut paint);
+
+        let mut layout = text::shape(x * scale, y * scale, &mut self.text_context, &paint, text, None)?;
+        //let layout = self.layout_text(x, y, text, paint)?;
+
+        self.render_glyphs(&layout.glyphs, paint, render_mode)?;
+
+        layout.scale(invscale);
+
+        Ok(layout)
+    }
+
+    // Returns the paragraph laid out in device pixels and the paints of the spans scaled to device pixels.
+    fn layout_paragraph<S: AsRef<str>>(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        spans: &[(S, Paint)],
+    ) -> Result<(Paragraph, Vec<Paint>), ErrorKind> {
+        let scale = self.font_scale() * self.device_px_ratio;
+
+        let spans: Vec<_> = spans
+            .iter()
+            .map(|(text, paint)| {
+                let mut paint = *paint;
+                self.transform_text_paint(&mut paint);
+
+                (text.as_ref(), paint)
+            })
+            .collect();
+
+        let paragraph =
+            text::layout_paragraph(x * scale, y * scale, &mut self.text_context, &spans, max_width * scale)?;
+
+        Ok((paragraph, spans.into_iter().map(|(_, paint)| paint).collect()))
+    }
+
+    fn draw_paragraph<S: AsRef<str>>(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        spans: &[(S, Paint)],
+        render_mode: RenderMode,
+    ) -> Result<Paragraph, ErrorKind> {
+        let (mut paragraph, paints) = self.layout_paragraph(x, y, max_width, spans)?;
+
+        for (index, paint) in paints.into_iter().enumerate() {
+            self.render_glyphs(&paragraph.span_glyphs(index), paint, render_mode)?;
+        }
+
+        paragraph.scale(1.0 / (self.font_scale() * self.device_px_ratio));
+
+        Ok(paragraph)
+    }
+
+    // Draws glyphs positioned in device pixels with a paint whose text sizes are scaled to device pixels.
+    fn render_glyphs(
+        &mut self,
+        glyphs: &[ShapedGlyph],
+        mut paint: Paint,
+        render_mode: RenderMode,
+    ) -> Result<(), ErrorKind> {
         let transform = self.state().transform;
         // println!("transform {:?}", transform);
 
@@ -1111,18 +1913,37 @@ where
         let scale = self.font_scale() * self.device_px_ratio;
         let invscale = 1.0 / scale;
 
-        self.transform_text_paint(&mut paint);
-
-        let mut layout = text::shape(x * scale, y * scale, &mut self.text_context, &paint, text, None)?;
-        //let layout = self.layout_text(x, y, text, paint)?;
-
         // TODO: Early out if text is outside the canvas bounds, or maybe even check for each character in layout.
 
         // if paint.font_size > 10.0 {
-        if paint.font_size > 92.0 {
-            text::render_direct(self, &layout, &paint, render_mode, invscale)?;
+        if paint.font_size > 92.0 || T::is_vector() {
+            // The glyph paths are part of the recorded text call
+            let picture = self.picture.take();
+            let res = text::render_direct(self, glyphs, &paint, render_mode, invscale);
+            self.picture = picture;
+            res?;
         } else {
-            let cmds = text::render_atlas(self, &layout, &paint, render_mode)?;
+            // Under a clip only the glyphs across the edge of the clip area are cut to it as paths, the
+            // ones inside of it are drawn from the atlas like unclipped text.
+            let (glyphs, edge_glyphs) = match self.clip_area() {
+                Some(clip) => {
+                    let clip = PathCache::new(clip.verbs(), &Transform2D::identity(), self.tess_tol, self.dist_tol);
+
+                    let (inside, edge) = text::clip_glyphs(glyphs, &paint, render_mode, &transform, invscale, &clip);
+
+                    (Cow::Owned(inside), edge)
+                }
+                None => (Cow::Borrowed(glyphs), Vec::new()),
+            };
+
+            if !edge_glyphs.is_empty() {
+                let picture = self.picture.take();
+                let res = text::render_direct(self, &edge_glyphs, &paint, render_mode, invscale);
+                self.picture = picture;
+                res?;
+            }
+
+            let cmds = text::render_atlas(self, &glyphs, &paint, render_mode)?;
 
             for cmd in &cmds {
                 let mut verts = Vec::with_capacity(cmd.quads.len() * 6);
@@ -1154,23 +1975,22 @@ where
             }
         }
 
-        layout.scale(invscale);
-
-        Ok(layout)
+        Ok(())
     }
 
     fn render_triangles(&mut self, verts: &[Vertex], paint: &Paint) {
         let scissor = self.state().scissor;
 
-        let params = Params::new(&self.images, paint, &scissor, 1.0, 1.0, -1.0);
+        let mut paint = *paint;
+        let image = self.paint_image(&mut paint);
+
+        let params = Params::new(&self.images, &paint, &scissor, 1.0, 1.0, -1.0);
 
         let mut cmd = Command::new(CommandType::Triangles { params });
         cmd.composite_operation = self.state().composite_operation;
         cmd.alpha_mask = paint.alpha_mask();
 
-        if let PaintFlavor::Image { id, .. } = paint.flavor {
-            cmd.image = Some(id);
-        }
+        cmd.image = image;
 
         cmd.triangles_verts = Some((self.verts.len(), verts.len()));
         self.append_cmd(cmd);
@@ -1178,6 +1998,35 @@ where
         self.verts.extend_from_slice(verts);
     }
 
+    // Returns the image sampled by the paint, multi stop gradients sample a color ramp.
+    //
+    // Must be called before the params of the paint are computed: if the ramp can't be created the gradient
+    // falls back to one that doesn't need it, see Paint::without_color_ramp.
+    fn paint_image(&mut self, paint: &mut Paint) -> Option<ImageId> {
+        match &paint.flavor {
+            PaintFlavor::Image { id, .. } | PaintFlavor::FilterImage { id, .. } | PaintFlavor::BlurImage { id, .. } => {
+                Some(*id)
+            }
+            PaintFlavor::LinearGradient { colors, .. }
+            | PaintFlavor::BoxGradient { colors, .. }
+            | PaintFlavor::RadialGradient { colors, .. }
+            | PaintFlavor::ConicGradient { colors, .. } => {
+                let stops = colors.stops()?;
+
+                let ramp = self
+                    .gradients
+                    .lookup_or_insert(stops, &mut self.images, &mut self.renderer);
+
+                if ramp.is_err() {
+                    paint.without_color_ramp();
+                }
+
+                ramp.ok()
+            }
+            PaintFlavor::Color(_) => None,
+        }
+    }
+
     fn font_scale(&self) -> f32 {
         let avg_scale = self.state().transform.average_scale();
 
@@ -1195,6 +2044,27 @@ where
     }
 }
 
+// Returns the axis aligned bounds of the rectangle after transforming it.
+fn transformed_bounds(transform: &Transform2D, rect: Rect) -> Rect {
+    let corners = [
+        transform.transform_point(rect.x, rect.y),
+        transform.transform_point(rect.x + rect.w, rect.y),
+        transform.transform_point(rect.x + rect.w, rect.y + rect.h),
+        transform.transform_point(rect.x, rect.y + rect.h),
+    ];
+
+    let (mut minx, mut miny, mut maxx, mut maxy) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+
+    for (x, y) in &corners {
+        minx = minx.min(*x);
+        miny = miny.min(*y);
+        maxx = maxx.max(*x);
+        maxy = maxy.max(*y);
+    }
+
+    Rect::new(minx, miny, maxx - minx, maxy - miny)
+}
+
 impl<T: Renderer> Drop for Canvas<T> {
     fn drop(&mut self) {
         self.images.clear(&mut self.renderer);
@@ -1,14 +1,17 @@
 // TODO: prefix paint creation functions with make_ or new_
 // so that they are easier to find when autocompleting
 
+use std::hash::{Hash, Hasher};
+
+use fnv::FnvHasher;
+
 use crate::geometry::Transform2D;
-use crate::{Align, Baseline, Color, FillRule, FontId, ImageId, LineCap, LineJoin};
+use crate::{Align, Baseline, Color, FillRule, FontId, ImageId, LineCap, LineJoin, SpreadMode, TextDirection};
 
 #[derive(Copy, Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub(crate) enum PaintFlavor {
     Color(Color),
-    #[cfg_attr(feature = "serde", serde(skip))]
     Image {
         id: ImageId,
         cx: f32,
@@ -23,8 +26,7 @@ pub(crate) enum PaintFlavor {
         start_y: f32,
         end_x: f32,
         end_y: f32,
-        start_color: Color,
-        end_color: Color,
+        colors: GradientColors,
     },
     BoxGradient {
         x: f32,
@@ -33,19 +35,131 @@ pub(crate) enum PaintFlavor {
         height: f32,
         radius: f32,
         feather: f32,
-        inner_color: Color,
-        outer_color: Color,
+        colors: GradientColors,
     },
     RadialGradient {
         cx: f32,
         cy: f32,
         in_radius: f32,
         out_radius: f32,
-        inner_color: Color,
-        outer_color: Color,
+        colors: GradientColors,
+    },
+    ConicGradient {
+        cx: f32,
+        cy: f32,
+        start_angle: f32,
+        colors: GradientColors,
+    },
+    // Drawn by image filters only, see filter.rs. The row major 4x5 color matrix applies to
+    // unpremultiplied colors, a lookup table is passed as the alpha mask.
+    FilterImage {
+        id: ImageId,
+        width: f32,
+        height: f32,
+        matrix: [f32; 20],
+    },
+    // A pass of a gaussian blur, drawn by image filters only. The kernel holds the weights of the offsets
+    // from 0 to 15 pixels along the direction of the pass, it's mirrored for the negative offsets.
+    BlurImage {
+        id: ImageId,
+        width: f32,
+        height: f32,
+        horizontal: bool,
+        kernel: [f32; 16],
+    },
+}
+
+/// Maximum number of color stops a gradient can have, extra stops are ignored.
+pub(crate) const MAX_GRADIENT_STOPS: usize = 16;
+
+// Stops are stored inline to keep Paint Copy
+#[allow(clippy::large_enum_variant)]
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) enum GradientColors {
+    TwoStop {
+        start_color: Color,
+        end_color: Color,
+    },
+    MultiStop {
+        stops: [(f32, Color); MAX_GRADIENT_STOPS],
+        len: usize,
     },
 }
 
+impl GradientColors {
+    fn from_stops(stops: &[(f32, Color)]) -> Self {
+        match stops {
+            [] => Self::TwoStop {
+                start_color: Color::clear(),
+                end_color: Color::clear(),
+            },
+            [(_, color)] => Self::TwoStop {
+                start_color: *color,
+                end_color: *color,
+            },
+            [(start, start_color), (end, end_color)] if *start <= 0.0 && *end >= 1.0 => Self::TwoStop {
+                start_color: *start_color,
+                end_color: *end_color,
+            },
+            _ => Self::multi_stop(stops),
+        }
+    }
+
+    /// Like `from_stops` but never falls back to a two color gradient, for gradients that are always
+    /// looked up from a color ramp.
+    fn multi_stop(stops: &[(f32, Color)]) -> Self {
+        let mut sorted = [(0.0, Color::clear()); MAX_GRADIENT_STOPS];
+        let len = stops.len().min(MAX_GRADIENT_STOPS);
+
+        for (i, (offset, color)) in stops.iter().take(len).enumerate() {
+            sorted[i] = (offset.max(0.0).min(1.0), *color);
+        }
+
+        // Stable, so stops sharing an offset keep their order and produce a hard transition.
+        sorted[..len].sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        // A ramp needs at least one color, an empty gradient is transparent
+        Self::MultiStop {
+            stops: sorted,
+            len: len.max(1),
+        }
+    }
+
+    /// Returns the stops sorted by offset, or None for a simple two color gradient.
+    pub(crate) fn stops(&self) -> Option<&[(f32, Color)]> {
+        match self {
+            Self::TwoStop { .. } => None,
+            Self::MultiStop { stops, len } => Some(&stops[..*len]),
+        }
+    }
+
+    // A gradient between the first and the last stop, which doesn't need a color ramp.
+    fn first_to_last(&self) -> Self {
+        match self {
+            Self::TwoStop { .. } => *self,
+            Self::MultiStop { stops, len } => Self::TwoStop {
+                start_color: stops[0].1,
+                end_color: stops[len - 1].1,
+            },
+        }
+    }
+
+    fn mul_alpha(&mut self, a: f32) {
+        match self {
+            Self::TwoStop { start_color, end_color } => {
+                start_color.a *= a;
+                end_color.a *= a;
+            }
+            Self::MultiStop { stops, len } => {
+                for (_, color) in &mut stops[..*len] {
+                    color.a *= a;
+                }
+            }
+        }
+    }
+}
+
 /// Struct controlling how graphical shapes are rendered.
 ///
 /// The Paint struct is a relatively lightweight object which contains all the information needed to
@@ -72,11 +186,11 @@ pub(crate) enum PaintFlavor {
 /// canvas.stroke_path(&mut path, stroke_paint);
 /// ```
 #[derive(Copy, Clone, Debug)]
-#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Paint {
     pub(crate) flavor: PaintFlavor,
     pub(crate) transform: Transform2D,
-    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub(crate) spread_mode: SpreadMode,
     pub(crate) alpha_mask: Option<ImageId>,
     pub(crate) shape_anti_alias: bool,
     pub(crate) stencil_strokes: bool,
@@ -85,12 +199,20 @@ pub struct Paint {
     pub(crate) line_cap_start: LineCap,
     pub(crate) line_cap_end: LineCap,
     pub(crate) line_join: LineJoin,
-    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub(crate) line_dash: [f32; 16],
+    pub(crate) line_dash_len: usize,
+    pub(crate) line_dash_offset: f32,
     pub(crate) font_ids: [Option<FontId>; 8],
     pub(crate) font_size: f32,
     pub(crate) letter_spacing: f32,
+    pub(crate) line_height: f32,
     pub(crate) text_baseline: Baseline,
     pub(crate) text_align: Align,
+    pub(crate) text_direction: TextDirection,
+    pub(crate) text_script: [u8; 4],
+    pub(crate) text_language: [u8; 16],
+    pub(crate) font_features: [Option<([u8; 4], u32)>; 16],
+    pub(crate) font_variations: [Option<([u8; 4], f32)>; 8],
     pub(crate) fill_rule: FillRule,
 }
 
@@ -99,6 +221,7 @@ impl Default for Paint {
         Self {
             flavor: PaintFlavor::Color(Color::white()),
             transform: Default::default(),
+            spread_mode: Default::default(),
             alpha_mask: Default::default(),
             shape_anti_alias: true,
             stencil_strokes: true,
@@ -107,11 +230,20 @@ impl Default for Paint {
             line_cap_start: Default::default(),
             line_cap_end: Default::default(),
             line_join: Default::default(),
+            line_dash: Default::default(),
+            line_dash_len: 0,
+            line_dash_offset: 0.0,
             font_ids: Default::default(),
             font_size: 16.0,
             letter_spacing: 0.0,
+            line_height: 1.0,
             text_baseline: Default::default(),
             text_align: Default::default(),
+            text_direction: Default::default(),
+            text_script: Default::default(),
+            text_language: Default::default(),
+            font_features: Default::default(),
+            font_variations: Default::default(),
             fill_rule: Default::default(),
         }
     }
@@ -178,8 +310,37 @@ impl Paint {
             start_y,
             end_x,
             end_y,
-            start_color,
-            end_color,
+            colors: GradientColors::TwoStop { start_color, end_color },
+        };
+
+        new
+    }
+
+    /// Creates and returns a linear gradient paint with multiple color stops.
+    ///
+    /// Each stop is an offset between 0.0 (start) and 1.0 (end) along the gradient line and the color
+    /// at that offset. Stops don't have to be sorted, but stops sharing an offset are used in the given
+    /// order which makes it possible to create hard color transitions. At most 16 stops are used.
+    ///
+    /// # Example
+    /// ```
+    /// use gpucanvas::{Paint, Color};
+    ///
+    /// let rainbow = Paint::linear_gradient_stops(0.0, 0.0, 100.0, 0.0, &[
+    ///     (0.0, Color::rgb(255, 0, 0)),
+    ///     (0.5, Color::rgb(0, 255, 0)),
+    ///     (1.0, Color::rgb(0, 0, 255)),
+    /// ]);
+    /// ```
+    pub fn linear_gradient_stops(start_x: f32, start_y: f32, end_x: f32, end_y: f32, stops: &[(f32, Color)]) -> Self {
+        let mut new = Self::default();
+
+        new.flavor = PaintFlavor::LinearGradient {
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            colors: GradientColors::from_stops(stops),
         };
 
         new
@@ -211,8 +372,38 @@ impl Paint {
             height,
             radius,
             feather,
-            inner_color,
-            outer_color,
+            colors: GradientColors::TwoStop {
+                start_color: inner_color,
+                end_color: outer_color,
+            },
+        };
+
+        new
+    }
+
+    /// Creates and returns a box gradient with multiple color stops.
+    ///
+    /// Works like [box_gradient](#method.box_gradient) with the colors given as stops, where offset 0.0
+    /// is the inner and 1.0 the outer edge of the feather. See [linear_gradient_stops](#method.linear_gradient_stops).
+    pub fn box_gradient_stops(
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+        feather: f32,
+        stops: &[(f32, Color)],
+    ) -> Self {
+        let mut new = Self::default();
+
+        new.flavor = PaintFlavor::BoxGradient {
+            x,
+            y,
+            width,
+            height,
+            radius,
+            feather,
+            colors: GradientColors::from_stops(stops),
         };
 
         new
@@ -238,8 +429,58 @@ impl Paint {
             cy,
             in_radius,
             out_radius,
-            inner_color,
-            outer_color,
+            colors: GradientColors::TwoStop {
+                start_color: inner_color,
+                end_color: outer_color,
+            },
+        };
+
+        new
+    }
+
+    /// Creates and returns a radial gradient with multiple color stops.
+    ///
+    /// Offset 0.0 is at the inner and 1.0 at the outer radius. See [linear_gradient_stops](#method.linear_gradient_stops).
+    pub fn radial_gradient_stops(cx: f32, cy: f32, in_radius: f32, out_radius: f32, stops: &[(f32, Color)]) -> Self {
+        let mut new = Self::default();
+
+        new.flavor = PaintFlavor::RadialGradient {
+            cx,
+            cy,
+            in_radius,
+            out_radius,
+            colors: GradientColors::from_stops(stops),
+        };
+
+        new
+    }
+
+    /// Creates and returns a conic (sweep) gradient.
+    ///
+    /// Colors sweep clockwise around the center (cx,cy), starting at `start_angle` (in radians, 0.0 pointing
+    /// along the positive x axis). Stop offsets go from 0.0 at the start angle to 1.0 after a full turn,
+    /// see [linear_gradient_stops](#method.linear_gradient_stops) for how stops are handled.
+    /// The gradient is transformed by the current transform when it is passed to fill_paint() or stroke_paint().
+    ///
+    /// # Example
+    /// ```
+    /// use gpucanvas::{Paint, Color};
+    ///
+    /// let color_wheel = Paint::conic_gradient(50.0, 50.0, 0.0, &[
+    ///     (0.0, Color::rgb(255, 0, 0)),
+    ///     (1.0 / 3.0, Color::rgb(0, 255, 0)),
+    ///     (2.0 / 3.0, Color::rgb(0, 0, 255)),
+    ///     (1.0, Color::rgb(255, 0, 0)),
+    /// ]);
+    /// ```
+    pub fn conic_gradient(cx: f32, cy: f32, start_angle: f32, stops: &[(f32, Color)]) -> Self {
+        let mut new = Self::default();
+
+        new.flavor = PaintFlavor::ConicGradient {
+            cx,
+            cy,
+            start_angle,
+            colors: GradientColors::multi_stop(stops),
         };
 
         new
@@ -250,6 +491,18 @@ impl Paint {
         self.flavor = PaintFlavor::Color(color);
     }
 
+    /// Returns how linear and radial gradients are continued beyond their extent.
+    pub fn spread_mode(&self) -> SpreadMode {
+        self.spread_mode
+    }
+
+    /// Sets how linear and radial gradients are continued beyond their extent.
+    ///
+    /// By default it's set to SpreadMode::Pad. Has no effect on other paints.
+    pub fn set_spread_mode(&mut self, mode: SpreadMode) {
+        self.spread_mode = mode;
+    }
+
     pub fn alpha_mask(&self) -> Option<ImageId> {
         self.alpha_mask
     }
@@ -344,6 +597,47 @@ impl Paint {
         self.line_join = join;
     }
 
+    /// Returns the current dash pattern. An empty slice means solid lines.
+    pub fn line_dash(&self) -> &[f32] {
+        &self.line_dash[..self.line_dash_len]
+    }
+
+    /// Sets the dash pattern used when stroking, as alternating dash and gap lengths.
+    ///
+    /// Like the html canvas `setLineDash`, a pattern with an odd number of entries is repeated once to
+    /// make it even, and patterns containing negative or non finite lengths are ignored. Patterns longer
+    /// than 16 entries once made even are ignored too. Pass an empty slice to go back to solid lines.
+    ///
+    /// Dashes of zero length only draw their caps, so `[0.0, gap]` with round caps makes a dotted line.
+    pub fn set_line_dash(&mut self, segments: &[f32]) {
+        if segments.iter().any(|len| *len < 0.0 || !len.is_finite()) {
+            return;
+        }
+
+        let repeat = if segments.len() % 2 == 1 { 2 } else { 1 };
+
+        if segments.len() * repeat > self.line_dash.len() {
+            return;
+        }
+
+        self.line_dash = Default::default();
+        self.line_dash_len = segments.len() * repeat;
+
+        for (i, len) in segments.iter().cycle().take(self.line_dash_len).enumerate() {
+            self.line_dash[i] = *len;
+        }
+    }
+
+    /// Returns the distance into the dash pattern at which strokes start.
+    pub fn line_dash_offset(&self) -> f32 {
+        self.line_dash_offset
+    }
+
+    /// Sets the distance into the dash pattern at which strokes start.
+    pub fn set_line_dash_offset(&mut self, offset: f32) {
+        self.line_dash_offset = offset;
+    }
+
     pub fn set_font(&mut self, font_ids: &[FontId]) {
         self.font_ids = Default::default();
 
@@ -378,6 +672,18 @@ impl Paint {
         self.letter_spacing = spacing;
     }
 
+    /// Returns the current line height multiplier
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    /// Sets the multiplier of the font height that lines of a paragraph take. Defaults to 1.0
+    ///
+    /// Only has effect on canvas paragraph operations
+    pub fn set_line_height(&mut self, line_height: f32) {
+        self.line_height = line_height;
+    }
+
     /// Returns the current vertical align
     pub fn text_baseline(&self) -> Baseline {
         self.text_baseline
@@ -402,6 +708,95 @@ impl Paint {
         self.text_align = align;
     }
 
+    /// Returns the current base text direction
+    pub fn text_direction(&self) -> TextDirection {
+        self.text_direction
+    }
+
+    /// Sets the base direction of the paragraphs in the text, which orders the runs of left-to-right and
+    /// right-to-left text in a line.
+    ///
+    /// Only has effect on canvas text operations
+    pub fn set_text_direction(&mut self, direction: TextDirection) {
+        self.text_direction = direction;
+    }
+
+    /// Returns the script text is shaped as, empty if it is guessed from the text
+    pub fn text_script(&self) -> &str {
+        tag_str(&self.text_script)
+    }
+
+    /// Sets the ISO 15924 tag of the script to shape text as, like "Latn" or "Cyrl". An empty string guesses
+    /// the script from the text. Default value.
+    ///
+    /// Only has effect on canvas text operations
+    pub fn set_text_script(&mut self, script: &str) {
+        self.text_script = Default::default();
+        copy_tag(&mut self.text_script, script);
+    }
+
+    /// Returns the language text is shaped for, empty if none is set
+    pub fn text_language(&self) -> &str {
+        tag_str(&self.text_language)
+    }
+
+    /// Sets the BCP 47 tag of the language to shape text for, like "tr" or "sr-Latn", which selects the
+    /// localized forms of the font. An empty string uses the font's default forms. Default value.
+    ///
+    /// Tags are cut to 16 bytes. Only has effect on canvas text operations
+    pub fn set_text_language(&mut self, language: &str) {
+        self.text_language = Default::default();
+        copy_tag(&mut self.text_language, language);
+    }
+
+    /// Returns the OpenType features set on this paint and their values
+    pub fn font_features(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.font_features
+            .iter()
+            .flatten()
+            .map(|(tag, value)| (tag_str(tag), *value))
+    }
+
+    /// Sets OpenType features to turn on or off when shaping text, as tags and values like ("tnum", 1) or
+    /// ("liga", 0). Features the font applies by default stay on unless they are set to 0. Features with
+    /// alternates, like "salt", select the alternate with their value.
+    ///
+    /// Up to 16 features are used. Only has effect on canvas text operations
+    pub fn set_font_features(&mut self, features: &[(&str, u32)]) {
+        self.font_features = Default::default();
+
+        for (i, (tag, value)) in features.iter().take(16).enumerate() {
+            // Short tags are padded with spaces like OpenType does
+            let mut bytes = [b' '; 4];
+            copy_tag(&mut bytes, tag);
+
+            self.font_features[i] = Some((bytes, *value));
+        }
+    }
+
+    /// Returns the variation axis values set on this paint
+    pub fn font_variations(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.font_variations
+            .iter()
+            .flatten()
+            .map(|(tag, value)| (tag_str(tag), *value))
+    }
+
+    /// Sets the values of variable font axes, as tags and values like ("wght", 650.0) or ("wdth", 85.0).
+    /// Axes that aren't set keep their default value and axes the font doesn't have are ignored.
+    ///
+    /// Up to 8 axes are used. Only has effect on canvas text operations
+    pub fn set_font_variations(&mut self, variations: &[(&str, f32)]) {
+        self.font_variations = Default::default();
+
+        for (i, (tag, value)) in variations.iter().take(8).enumerate() {
+            let mut bytes = [b' '; 4];
+            copy_tag(&mut bytes, tag);
+
+            self.font_variations[i] = Some((bytes, *value));
+        }
+    }
+
     /// Retrieves the current fill rule setting for this paint
     pub fn fill_rule(&self) -> FillRule {
         self.fill_rule
@@ -422,28 +817,99 @@ impl Paint {
             PaintFlavor::Image { alpha, .. } => {
                 *alpha *= a;
             }
-            PaintFlavor::LinearGradient {
-                start_color, end_color, ..
-            } => {
-                start_color.a *= a;
-                end_color.a *= a;
+            PaintFlavor::FilterImage { matrix, .. } => {
+                // The row of the color matrix that makes the alpha
+                for value in &mut matrix[15..] {
+                    *value *= a;
+                }
             }
-            PaintFlavor::BoxGradient {
-                inner_color,
-                outer_color,
-                ..
-            } => {
-                inner_color.a *= a;
-                outer_color.a *= a;
+            PaintFlavor::BlurImage { kernel, .. } => {
+                for weight in kernel {
+                    *weight *= a;
+                }
             }
-            PaintFlavor::RadialGradient {
-                inner_color,
-                outer_color,
-                ..
-            } => {
-                inner_color.a *= a;
-                outer_color.a *= a;
+            PaintFlavor::LinearGradient { colors, .. }
+            | PaintFlavor::BoxGradient { colors, .. }
+            | PaintFlavor::RadialGradient { colors, .. }
+            | PaintFlavor::ConicGradient { colors, .. } => {
+                colors.mul_alpha(a);
             }
         }
     }
+
+    // Identifies the parameters that shape a stroke in cache keys.
+    pub(crate) fn stroke_key(&self) -> u64 {
+        let mut hasher = FnvHasher::default();
+
+        self.line_width.to_bits().hash(&mut hasher);
+        (self.line_cap_start as u8).hash(&mut hasher);
+        (self.line_cap_end as u8).hash(&mut hasher);
+        (self.line_join as u8).hash(&mut hasher);
+        self.miter_limit.to_bits().hash(&mut hasher);
+
+        for len in self.line_dash() {
+            len.to_bits().hash(&mut hasher);
+        }
+
+        self.line_dash_offset.to_bits().hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    // Makes the paint drawable when the color ramp of its gradient can't be created. Multi stop gradients
+    // become a gradient between their first and last color, conic gradients always sample the ramp
+    // and become their first color.
+    pub(crate) fn without_color_ramp(&mut self) {
+        match &mut self.flavor {
+            PaintFlavor::LinearGradient { colors, .. }
+            | PaintFlavor::BoxGradient { colors, .. }
+            | PaintFlavor::RadialGradient { colors, .. } => {
+                *colors = colors.first_to_last();
+            }
+            PaintFlavor::ConicGradient { colors, .. } => {
+                if let GradientColors::TwoStop { start_color, .. } = colors.first_to_last() {
+                    self.flavor = PaintFlavor::Color(start_color);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Replaces the ids of the image, alpha mask and fonts the paint refers to.
+    pub(crate) fn map_ids<E>(
+        &mut self,
+        mut image: impl FnMut(ImageId) -> Result<ImageId, E>,
+        mut font: impl FnMut(FontId) -> Result<FontId, E>,
+    ) -> Result<(), E> {
+        match &mut self.flavor {
+            PaintFlavor::Image { id, .. } | PaintFlavor::FilterImage { id, .. } | PaintFlavor::BlurImage { id, .. } => {
+                *id = image(*id)?
+            }
+            _ => (),
+        }
+
+        if let Some(id) = self.alpha_mask {
+            self.alpha_mask = Some(image(id)?);
+        }
+
+        for id in self.font_ids.iter_mut().flatten() {
+            *id = font(*id)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Copies as much of the tag into the bytes as fits.
+fn copy_tag(bytes: &mut [u8], tag: &str) {
+    for (byte, tag_byte) in bytes.iter_mut().zip(tag.bytes()) {
+        *byte = tag_byte;
+    }
+}
+
+// Returns the tag stored in the bytes without the zero padding, or an empty string if it isn't valid UTF-8.
+fn tag_str(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+
+    std::str::from_utf8(&bytes[..len]).unwrap_or_default()
 }
@@ -1,11 +1,20 @@
 use std::f32::consts::PI;
 use std::slice;
+use std::sync::Arc;
 
 use crate::geometry::{self, Transform2D};
+use crate::renderer::Vertex;
+use crate::{ErrorKind, FillRule, Paint};
 
 mod cache;
+pub(crate) use cache::Overlap;
 pub use cache::{Convexity, PathCache};
 
+mod boolean;
+use boolean::BooleanOp;
+
+mod svg_data;
+
 // Length proportional to radius of a cubic bezier handle for 90deg arcs.
 const KAPPA90: f32 = 0.5522847493;
 
@@ -21,7 +30,7 @@ impl Default for Solidity {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(u8)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PackedVerb {
@@ -78,6 +87,18 @@ pub struct Path {
     dist_tol: f32,
     #[cfg_attr(feature = "serde", serde(skip))]
     pub(crate) cache: Option<(u64, PathCache)>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    clipped: Option<Box<ClippedPath>>,
+}
+
+// The path cut to a clip area in device space. Kept like the path cache, so that drawing the path again
+// under the same transform and clip with the same style doesn't repeat the boolean operation.
+#[derive(Clone, Debug)]
+struct ClippedPath {
+    transform_key: u64,
+    style_key: u64,
+    clip: Arc<Path>,
+    path: Path,
 }
 
 impl Path {
@@ -131,6 +152,157 @@ impl Path {
         &mut self.cache.as_mut().unwrap().1
     }
 
+    /// Returns the path cut to the clip area by `clip_fn`, which is only called if the transform, the clip or
+    /// `style_key` differ from the last call. The style key tells apart fills and strokes with different
+    /// parameters. Clips are compared by their geometry, they are usually created again for every frame.
+    pub(crate) fn clipped(
+        &mut self,
+        transform: &Transform2D,
+        clip: &Arc<Path>,
+        style_key: u64,
+        clip_fn: impl FnOnce(&mut Self) -> Self,
+    ) -> &mut Self {
+        let transform_key = transform.cache_key();
+
+        let needs_rebuild = match self.clipped.as_deref() {
+            Some(clipped) => {
+                clipped.transform_key != transform_key
+                    || clipped.style_key != style_key
+                    || !(Arc::ptr_eq(&clipped.clip, clip) || clipped.clip.same_geometry(clip))
+            }
+            None => true,
+        };
+
+        if needs_rebuild {
+            let path = clip_fn(self);
+
+            self.clipped = Some(Box::new(ClippedPath {
+                transform_key,
+                style_key,
+                clip: clip.clone(),
+                path,
+            }));
+        }
+
+        &mut self.clipped.as_mut().unwrap().path
+    }
+
+    fn same_geometry(&self, other: &Self) -> bool {
+        self.verbs == other.verbs && self.coords == other.coords
+    }
+
+    /// Returns the path transformed with its curves kept, for renderers that write curves instead of
+    /// flattening them. With `wind` every contour is wound the way its solidity says like it is when the path
+    /// is filled, otherwise contours keep the direction they were drawn in as they do when stroked.
+    ///
+    /// Contours that end where they start are closed like the path cache closes them.
+    pub(crate) fn transformed(&self, transform: &Transform2D, wind: bool) -> Path {
+        // Points, segments, whether it's closed and solidity of each contour
+        type Contour = (Vec<(f32, f32)>, Vec<Verb>, bool, Solidity);
+
+        let mut contours: Vec<Contour> = Vec::new();
+
+        for verb in self.verbs() {
+            match (verb, contours.last_mut()) {
+                (Verb::MoveTo(x, y), _) => {
+                    contours.push((
+                        vec![transform.transform_point(x, y)],
+                        Vec::new(),
+                        false,
+                        Solidity::Solid,
+                    ));
+                }
+                (Verb::LineTo(x, y), Some((points, segments, ..))) => {
+                    let (x, y) = transform.transform_point(x, y);
+
+                    points.push((x, y));
+                    segments.push(Verb::LineTo(x, y));
+                }
+                (Verb::BezierTo(c1x, c1y, c2x, c2y, x, y), Some((points, segments, ..))) => {
+                    let (c1x, c1y) = transform.transform_point(c1x, c1y);
+                    let (c2x, c2y) = transform.transform_point(c2x, c2y);
+                    let (x, y) = transform.transform_point(x, y);
+
+                    points.push((x, y));
+                    segments.push(Verb::BezierTo(c1x, c1y, c2x, c2y, x, y));
+                }
+                (Verb::Close, Some((_, _, closed, _))) => *closed = true,
+                (Verb::Solid, Some((.., solidity))) => *solidity = Solidity::Solid,
+                (Verb::Hole, Some((.., solidity))) => *solidity = Solidity::Hole,
+                _ => (),
+            }
+        }
+
+        let mut path = Path::new();
+
+        for (points, segments, closed, solidity) in contours {
+            if segments.is_empty() {
+                continue;
+            }
+
+            let (x0, y0) = points[0];
+            let (xn, yn) = points[points.len() - 1];
+
+            let closed = closed || (xn - x0).powi(2) + (yn - y0).powi(2) < self.dist_tol * self.dist_tol;
+
+            // The area of the contour with its curves flattened a little, only its sign matters
+            let mut area = 0.0;
+            let mut flattened = vec![(x0, y0)];
+
+            for (segment, (sx, sy)) in segments.iter().zip(&points) {
+                match *segment {
+                    Verb::BezierTo(c1x, c1y, c2x, c2y, x, y) => {
+                        for i in 1..=4 {
+                            let t = i as f32 / 4.0;
+                            let mt = 1.0 - t;
+
+                            flattened.push((
+                                mt * mt * mt * sx + 3.0 * mt * mt * t * c1x + 3.0 * mt * t * t * c2x + t * t * t * x,
+                                mt * mt * mt * sy + 3.0 * mt * mt * t * c1y + 3.0 * mt * t * t * c2y + t * t * t * y,
+                            ));
+                        }
+                    }
+                    Verb::LineTo(x, y) => flattened.push((x, y)),
+                    _ => (),
+                }
+            }
+
+            for window in flattened[1..].windows(2) {
+                area += geometry::triarea2(x0, y0, window[0].0, window[0].1, window[1].0, window[1].1) * 0.5;
+            }
+
+            let reverse =
+                wind && ((solidity == Solidity::Solid && area < 0.0) || (solidity == Solidity::Hole && area > 0.0));
+
+            if reverse {
+                path.move_to(xn, yn);
+
+                for (segment, (sx, sy)) in segments.iter().zip(&points[..segments.len()]).rev() {
+                    match *segment {
+                        Verb::BezierTo(c1x, c1y, c2x, c2y, ..) => path.bezier_to(c2x, c2y, c1x, c1y, *sx, *sy),
+                        _ => path.line_to(*sx, *sy),
+                    }
+                }
+            } else {
+                path.move_to(x0, y0);
+
+                for segment in segments {
+                    match segment {
+                        Verb::BezierTo(c1x, c1y, c2x, c2y, x, y) => path.bezier_to(c1x, c1y, c2x, c2y, x, y),
+                        Verb::LineTo(x, y) => path.line_to(x, y),
+                        _ => (),
+                    }
+                }
+            }
+
+            if closed {
+                path.close();
+            }
+        }
+
+        path
+    }
+
     // Path funcs
 
     /// Starts new sub-path with specified point as first point.
@@ -455,6 +627,104 @@ impl Path {
         self.ellipse(cx, cy, r, r);
     }
 
+    // SVG path data
+
+    /// Parses SVG path data, the contents of the `d` attribute of a `<path>` element.
+    ///
+    /// All commands are supported, both absolute and relative. Quadratic curves and elliptical arcs are
+    /// converted to cubic bezier segments.
+    pub fn from_svg_data(data: &str) -> Result<Path, ErrorKind> {
+        svg_data::parse(data)
+    }
+
+    /// Returns the path as SVG path data, using only absolute move, line, cubic curve and close commands.
+    ///
+    /// Solidity is not kept, the result is drawn the way the coordinates wind.
+    pub fn to_svg_data(&self) -> String {
+        svg_data::serialize(self)
+    }
+
+    // Boolean operations
+
+    /// Returns a path covering the area covered by either this path or `other`.
+    ///
+    /// Both paths are filled according to `fill_rule`. Curves are flattened with a tolerance relative to the
+    /// size of the paths, so the result only contains straight line segments, with contours marked as solid
+    /// or hole so it can be filled with either rule.
+    pub fn union(&self, other: &Path, fill_rule: FillRule) -> Path {
+        self.combine(other, fill_rule, BooleanOp::Union)
+    }
+
+    /// Returns a path covering the area covered by both this path and `other`.
+    ///
+    /// See [union](#method.union) for how the paths are interpreted.
+    pub fn intersect(&self, other: &Path, fill_rule: FillRule) -> Path {
+        self.combine(other, fill_rule, BooleanOp::Intersect)
+    }
+
+    /// Returns a path covering the area of this path that is not covered by `other`.
+    ///
+    /// See [union](#method.union) for how the paths are interpreted.
+    pub fn difference(&self, other: &Path, fill_rule: FillRule) -> Path {
+        self.combine(other, fill_rule, BooleanOp::Difference)
+    }
+
+    /// Returns a path covering the area covered by exactly one of this path and `other`.
+    ///
+    /// See [union](#method.union) for how the paths are interpreted.
+    pub fn xor(&self, other: &Path, fill_rule: FillRule) -> Path {
+        self.combine(other, fill_rule, BooleanOp::Xor)
+    }
+
+    // Paths in path units are flattened relative to their size.
+    fn combine(&self, other: &Path, fill_rule: FillRule, op: BooleanOp) -> Path {
+        boolean::apply(self, other, fill_rule, op, boolean::relative_tess_tol(self, other))
+    }
+
+    /// Returns a path covering the area covered by both paths, which are in device space.
+    pub(crate) fn device_intersect(&self, other: &Path, fill_rule: FillRule) -> Path {
+        boolean::apply(self, other, fill_rule, BooleanOp::Intersect, boolean::DEVICE_TESS_TOL)
+    }
+
+    /// Returns a path covering the area covered by either path, which are in device space.
+    pub(crate) fn device_union(&self, other: &Path, fill_rule: FillRule) -> Path {
+        boolean::apply(self, other, fill_rule, BooleanOp::Union, boolean::DEVICE_TESS_TOL)
+    }
+
+    /// Returns a path covering the area that stroking this path with `paint` would cover.
+    ///
+    /// Honors the line width, joins, miter limit, caps and dash pattern of the paint. The paint transform
+    /// is ignored. Like the boolean operations, the outline only contains straight line segments, flattened
+    /// with a tolerance relative to the size of the path and the line width.
+    pub fn stroke_outline(&self, paint: &Paint) -> Path {
+        // Thin strokes need finer round joins and caps than the size of the path alone asks for
+        let tess_tol = boolean::relative_tess_tol(self, self).min(boolean::size_tess_tol(paint.line_width()));
+
+        let mut cache = PathCache::new(self.verbs(), &Transform2D::identity(), tess_tol, self.dist_tol);
+
+        if let Some(dashed) = cache.dashed_by(paint, 1.0) {
+            cache = dashed;
+        }
+
+        // No antialiasing fringe, the outline should end exactly at the stroke edge
+        cache.expand_stroke(
+            paint.line_width() * 0.5,
+            0.0,
+            paint.line_cap_start(),
+            paint.line_cap_end(),
+            paint.line_join(),
+            paint.miter_limit(),
+            tess_tol,
+        );
+
+        Self::from_triangle_strips(cache.contours.iter().map(|contour| &contour.stroke[..]), self.dist_tol)
+    }
+
+    /// Returns a path outlining the area covered by triangle strips, as produced by the stroke expansion.
+    pub(crate) fn from_triangle_strips<'a>(strips: impl IntoIterator<Item = &'a [Vertex]>, dist_tol: f32) -> Path {
+        boolean::strips_outline(strips, dist_tol)
+    }
+
     /// Appends a slice of verbs to the path
     fn append(&mut self, verbs: &[PackedVerb], coords: &[f32]) {
         if coords.len() > 1 {
@@ -0,0 +1,590 @@
+//! Boolean operations on flattened paths.
+//!
+//! Both paths are flattened into polygons and every edge is split where it crosses another edge. The pieces
+//! are then classified by testing which side of them lies inside the result, and the pieces that separate
+//! the inside from the outside are linked back into closed contours.
+//!
+//! The same machinery turns the triangle strips of an expanded stroke into its outline.
+
+use fnv::FnvHashMap;
+
+use crate::geometry::Transform2D;
+use crate::renderer::Vertex;
+use crate::{FillRule, Solidity};
+
+use super::{Path, PathCache};
+
+/// Tesselation tolerance for paths in device space, the same the canvas uses at a device pixel ratio of 1.
+pub(crate) const DEVICE_TESS_TOL: f32 = 0.25;
+
+// Tesselation tolerance for paths in path units, relative to their size. Large paths are still flattened
+// at least as finely as in device space.
+const RELATIVE_TESS_TOL: f32 = 1.0 / 1024.0;
+
+// Relative tolerance for snapping intersections to segment end points.
+const SNAP_EPSILON: f64 = 1e-9;
+
+// Stroke vertices are rounded to a grid this much finer than the size of the stroke, joins and caps produce
+// points that should coincide but differ in the last bits. A 256 unit stroke gets a grid of 1/4096.
+const STROKE_GRID_BITS: i32 = 20;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum BooleanOp {
+    Union,
+    Intersect,
+    Difference,
+    Xor,
+}
+
+impl BooleanOp {
+    fn apply(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            Self::Union => in_a || in_b,
+            Self::Intersect => in_a && in_b,
+            Self::Difference => in_a && !in_b,
+            Self::Xor => in_a != in_b,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Vec2 {
+    x: f64,
+    y: f64,
+}
+
+impl Vec2 {
+    fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+
+    fn scale(self, s: f64) -> Self {
+        Self::new(self.x * s, self.y * s)
+    }
+
+    fn dot(self, other: Self) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    fn cross(self, other: Self) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    fn key(self) -> (u64, u64) {
+        // Adding 0.0 turns -0.0 into 0.0 so both produce the same key
+        ((self.x + 0.0).to_bits(), (self.y + 0.0).to_bits())
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Segment {
+    from: Vec2,
+    to: Vec2,
+}
+
+impl Segment {
+    fn min_x(&self) -> f64 {
+        self.from.x.min(self.to.x)
+    }
+
+    fn max_x(&self) -> f64 {
+        self.from.x.max(self.to.x)
+    }
+
+    fn min_y(&self) -> f64 {
+        self.from.y.min(self.to.y)
+    }
+
+    fn max_y(&self) -> f64 {
+        self.from.y.max(self.to.y)
+    }
+}
+
+pub(crate) fn apply(a: &Path, b: &Path, fill_rule: FillRule, op: BooleanOp, tess_tol: f32) -> Path {
+    let segments_a = segments(&polygons(a, tess_tol));
+    let segments_b = segments(&polygons(b, tess_tol));
+
+    combine(&segments_a, &segments_b, fill_rule, op, a.dist_tol)
+}
+
+/// Returns a tesselation tolerance for operations on two paths in path units, relative to their size.
+pub(crate) fn relative_tess_tol(a: &Path, b: &Path) -> f32 {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+
+    for point in a.coords.chunks_exact(2).chain(b.coords.chunks_exact(2)) {
+        min_x = min_x.min(point[0]);
+        min_y = min_y.min(point[1]);
+        max_x = max_x.max(point[0]);
+        max_y = max_y.max(point[1]);
+    }
+
+    size_tess_tol((max_x - min_x).max(max_y - min_y))
+}
+
+/// Returns a tesselation tolerance for shapes of the given size in path units.
+pub(crate) fn size_tess_tol(size: f32) -> f32 {
+    let tess_tol = size * RELATIVE_TESS_TOL;
+
+    // Empty paths and single points have nothing to flatten
+    if tess_tol > 0.0 {
+        tess_tol.min(DEVICE_TESS_TOL)
+    } else {
+        DEVICE_TESS_TOL
+    }
+}
+
+/// Returns the outline of the area covered by triangle strips, like the stroke vertices of an expanded path cache.
+pub(crate) fn strips_outline<'a>(strips: impl IntoIterator<Item = &'a [Vertex]>, dist_tol: f32) -> Path {
+    let strips: Vec<&[Vertex]> = strips.into_iter().collect();
+
+    let extent = strips
+        .iter()
+        .flat_map(|strip| strip.iter())
+        .map(|v| (v.x as f64).abs().max((v.y as f64).abs()))
+        .fold(f64::MIN_POSITIVE, f64::max);
+
+    let grid = 2f64.powi(extent.log2().floor() as i32 - STROKE_GRID_BITS);
+
+    let mut segments = Vec::new();
+
+    for strip in strips {
+        let verts: Vec<Vec2> = strip
+            .iter()
+            .map(|v| Vec2::new((v.x as f64 / grid).round() * grid, (v.y as f64 / grid).round() * grid))
+            .collect();
+
+        // Strokes are triangle strips, their triangles alternate winding so give them all the same one.
+        for triangle in verts.windows(3) {
+            let (p0, p1, p2) = (triangle[0], triangle[1], triangle[2]);
+            let area = p1.sub(p0).cross(p2.sub(p0));
+
+            // Rounded to the grid, triangles that aren't flat have at least half a grid cell of area
+            if area.abs() < grid * grid * 0.25 {
+                continue;
+            }
+
+            let points = if area > 0.0 { [p0, p1, p2] } else { [p0, p2, p1] };
+
+            for i in 0..3 {
+                segments.push(Segment {
+                    from: points[i],
+                    to: points[(i + 1) % 3],
+                });
+            }
+        }
+    }
+
+    combine(&segments, &[], FillRule::NonZero, BooleanOp::Union, dist_tol)
+}
+
+fn combine(segments_a: &[Segment], segments_b: &[Segment], fill_rule: FillRule, op: BooleanOp, dist_tol: f32) -> Path {
+    let scale = segments_a
+        .iter()
+        .chain(segments_b.iter())
+        .map(|s| s.from.x.abs().max(s.from.y.abs()))
+        .fold(1.0, f64::max);
+
+    let epsilon = scale * SNAP_EPSILON;
+
+    let mut all_segments = segments_a.to_vec();
+    all_segments.extend_from_slice(segments_b);
+
+    // Split everything at intersections and remove duplicates so that edges shared by both paths are
+    // only considered once.
+    let mut pieces = FnvHashMap::default();
+
+    for piece in split_segments(&all_segments, epsilon) {
+        let (from, to) = (piece.from.key(), piece.to.key());
+        let key = if from < to { (from, to) } else { (to, from) };
+
+        pieces.entry(key).or_insert(piece);
+    }
+
+    // Keep the pieces that have the inside of the result on one side and the outside on the other,
+    // oriented so the inside is on their left.
+    let offset = scale * 1e-7;
+    let index_a = WindingIndex::new(segments_a);
+    let index_b = WindingIndex::new(segments_b);
+    let inside = |p: Vec2| op.apply(index_a.is_inside(p, fill_rule), index_b.is_inside(p, fill_rule));
+
+    let mut edges = Vec::new();
+
+    for piece in pieces.values() {
+        let dir = piece.to.sub(piece.from);
+        let len = dir.dot(dir).sqrt();
+
+        if len <= 0.0 {
+            continue;
+        }
+
+        let normal = Vec2::new(-dir.y / len, dir.x / len).scale(offset);
+        let mid = piece.from.add(dir.scale(0.5));
+
+        let left = inside(mid.add(normal));
+        let right = inside(mid.sub(normal));
+
+        if left && !right {
+            edges.push(*piece);
+        } else if right && !left {
+            edges.push(Segment {
+                from: piece.to,
+                to: piece.from,
+            });
+        }
+    }
+
+    // Sort to get a stable output independent of hash map iteration order.
+    edges.sort_by(|a, b| {
+        (a.from.x, a.from.y, a.to.x, a.to.y)
+            .partial_cmp(&(b.from.x, b.from.y, b.to.x, b.to.y))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut result = Path::new();
+    result.set_distance_tolerance(dist_tol);
+
+    for contour in link_edges(&edges) {
+        let contour = remove_collinear(contour, epsilon);
+
+        let area = signed_area(&contour);
+
+        if contour.len() < 3 || area.abs() <= epsilon * scale {
+            continue;
+        }
+
+        result.move_to(contour[0].x as f32, contour[0].y as f32);
+
+        for p in &contour[1..] {
+            result.line_to(p.x as f32, p.y as f32);
+        }
+
+        result.close();
+
+        // With the inside on the left outer contours wind one way and holes the other.
+        if area > 0.0 {
+            result.solidity(Solidity::Solid);
+        } else {
+            result.solidity(Solidity::Hole);
+        }
+    }
+
+    result
+}
+
+fn polygons(path: &Path, tess_tol: f32) -> Vec<Vec<Vec2>> {
+    let cache = PathCache::new(path.verbs(), &Transform2D::identity(), tess_tol, path.dist_tol);
+
+    cache
+        .polygons()
+        .map(|points| points.map(|(x, y)| Vec2::new(x as f64, y as f64)).collect())
+        .collect()
+}
+
+fn segments(polygons: &[Vec<Vec2>]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+
+    for polygon in polygons {
+        // Fills are implicitly closed, so open contours get a closing segment too.
+        for (i, from) in polygon.iter().enumerate() {
+            let to = polygon[(i + 1) % polygon.len()];
+
+            if *from != to {
+                segments.push(Segment { from: *from, to });
+            }
+        }
+    }
+
+    segments
+}
+
+/// Segments sorted into horizontal bands, so winding numbers only need to look at the segments
+/// crossing the band of the tested point.
+struct WindingIndex<'a> {
+    segments: &'a [Segment],
+    bands: Vec<Vec<usize>>,
+    min_y: f64,
+    band_height: f64,
+}
+
+impl<'a> WindingIndex<'a> {
+    fn new(segments: &'a [Segment]) -> Self {
+        let min_y = segments.iter().map(Segment::min_y).fold(f64::INFINITY, f64::min);
+        let max_y = segments.iter().map(Segment::max_y).fold(f64::NEG_INFINITY, f64::max);
+
+        let band_count = ((segments.len() as f64).sqrt() as usize).max(1);
+        let band_height = ((max_y - min_y) / band_count as f64).max(f64::MIN_POSITIVE);
+
+        let mut index = Self {
+            segments,
+            bands: vec![Vec::new(); band_count],
+            min_y,
+            band_height,
+        };
+
+        for (i, segment) in segments.iter().enumerate() {
+            let first = index.band(segment.min_y());
+            let last = index.band(segment.max_y());
+
+            for band in &mut index.bands[first..=last] {
+                band.push(i);
+            }
+        }
+
+        index
+    }
+
+    fn band(&self, y: f64) -> usize {
+        (((y - self.min_y) / self.band_height).max(0.0) as usize).min(self.bands.len() - 1)
+    }
+
+    fn is_inside(&self, p: Vec2, fill_rule: FillRule) -> bool {
+        if self.segments.is_empty() {
+            return false;
+        }
+
+        let mut winding_number: i32 = 0;
+
+        for s in self.bands[self.band(p.y)].iter().map(|i| &self.segments[*i]) {
+            let is_left = s.to.sub(s.from).cross(p.sub(s.from));
+
+            if s.from.y <= p.y {
+                if s.to.y > p.y && is_left > 0.0 {
+                    winding_number += 1;
+                }
+            } else if s.to.y <= p.y && is_left < 0.0 {
+                winding_number -= 1;
+            }
+        }
+
+        match fill_rule {
+            FillRule::NonZero => winding_number != 0,
+            FillRule::EvenOdd => winding_number % 2 != 0,
+        }
+    }
+}
+
+/// Splits the segments at every point where they cross or touch another segment.
+fn split_segments(segments: &[Segment], epsilon: f64) -> Vec<Segment> {
+    let mut splits: Vec<Vec<Vec2>> = segments.iter().map(|s| vec![s.from, s.to]).collect();
+
+    // Sweep along the x axis so only segments with overlapping x ranges are tested against each other.
+    let mut order: Vec<usize> = (0..segments.len()).collect();
+    order.sort_by(|a, b| {
+        segments[*a]
+            .min_x()
+            .partial_cmp(&segments[*b].min_x())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    for (n, i) in order.iter().enumerate() {
+        let s0 = &segments[*i];
+
+        for j in &order[n + 1..] {
+            let s1 = &segments[*j];
+
+            if s1.min_x() > s0.max_x() + epsilon {
+                break;
+            }
+
+            if s1.min_y() > s0.max_y() + epsilon || s1.max_y() < s0.min_y() - epsilon {
+                continue;
+            }
+
+            for p in intersections(s0, s1, epsilon) {
+                splits[*i].push(p);
+                splits[*j].push(p);
+            }
+        }
+    }
+
+    let mut pieces = Vec::new();
+
+    for (segment, mut points) in segments.iter().zip(splits) {
+        let dir = segment.to.sub(segment.from);
+
+        points.sort_by(|a, b| {
+            dir.dot(a.sub(segment.from))
+                .partial_cmp(&dir.dot(b.sub(segment.from)))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        points.dedup();
+
+        for pair in points.windows(2) {
+            pieces.push(Segment {
+                from: pair[0],
+                to: pair[1],
+            });
+        }
+    }
+
+    pieces
+}
+
+/// Returns the points where two segments meet, snapped to segment end points when close to them.
+fn intersections(s0: &Segment, s1: &Segment, epsilon: f64) -> Vec<Vec2> {
+    let r = s0.to.sub(s0.from);
+    let s = s1.to.sub(s1.from);
+    let qp = s1.from.sub(s0.from);
+
+    let denom = r.cross(s);
+    let len_r = r.dot(r).sqrt();
+    let len_s = s.dot(s).sqrt();
+
+    if denom.abs() > epsilon * (len_r + len_s) {
+        let t = qp.cross(s) / denom;
+        let u = qp.cross(r) / denom;
+
+        let eps_t = epsilon / len_r;
+        let eps_u = epsilon / len_s;
+
+        if t < -eps_t || t > 1.0 + eps_t || u < -eps_u || u > 1.0 + eps_u {
+            return Vec::new();
+        }
+
+        let p = if t <= eps_t {
+            s0.from
+        } else if t >= 1.0 - eps_t {
+            s0.to
+        } else if u <= eps_u {
+            s1.from
+        } else if u >= 1.0 - eps_u {
+            s1.to
+        } else {
+            s0.from.add(r.scale(t))
+        };
+
+        return vec![p];
+    }
+
+    // Parallel, check whether the segments lie on the same line and overlap
+    if qp.cross(r).abs() > epsilon * len_r {
+        return Vec::new();
+    }
+
+    let mut points = Vec::new();
+
+    let on_segment = |p: Vec2, seg: &Segment, dir: Vec2, len_sq: f64| {
+        let t = p.sub(seg.from).dot(dir);
+        t > 0.0 && t < len_sq
+    };
+
+    for p in &[s1.from, s1.to] {
+        if on_segment(*p, s0, r, len_r * len_r) {
+            points.push(*p);
+        }
+    }
+
+    for p in &[s0.from, s0.to] {
+        if on_segment(*p, s1, s, len_s * len_s) {
+            points.push(*p);
+        }
+    }
+
+    points
+}
+
+/// Links directed edges sharing end points into closed contours.
+fn link_edges(edges: &[Segment]) -> Vec<Vec<Vec2>> {
+    let mut outgoing: FnvHashMap<(u64, u64), Vec<usize>> = FnvHashMap::default();
+
+    for (i, edge) in edges.iter().enumerate() {
+        outgoing.entry(edge.from.key()).or_default().push(i);
+    }
+
+    // Pop from the back below, so reverse to follow edges in sorted order.
+    for list in outgoing.values_mut() {
+        list.reverse();
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut contours = Vec::new();
+
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+
+        used[start] = true;
+
+        let start_key = edges[start].from.key();
+        let mut contour = vec![edges[start].from];
+        let mut current = edges[start].to;
+
+        while current.key() != start_key {
+            let next = outgoing.get_mut(&current.key()).and_then(|list| {
+                while let Some(i) = list.pop() {
+                    if !used[i] {
+                        return Some(i);
+                    }
+                }
+
+                None
+            });
+
+            let next = match next {
+                Some(next) => next,
+                // Shouldn't happen, but an unmatched chain is still closed when filled.
+                None => break,
+            };
+
+            used[next] = true;
+            contour.push(current);
+            current = edges[next].to;
+        }
+
+        if current.key() != start_key {
+            contour.push(current);
+        }
+
+        contours.push(contour);
+    }
+
+    contours
+}
+
+fn remove_collinear(mut points: Vec<Vec2>, epsilon: f64) -> Vec<Vec2> {
+    let mut i = 0;
+
+    while points.len() > 2 && i < points.len() {
+        let len = points.len();
+        let prev = points[(i + len - 1) % len];
+        let curr = points[i];
+        let next = points[(i + 1) % len];
+
+        let d0 = curr.sub(prev);
+        let d1 = next.sub(curr);
+
+        let len0 = d0.dot(d0).sqrt();
+        let len1 = d1.dot(d1).sqrt();
+
+        if len0 <= epsilon || len1 <= epsilon || (d0.cross(d1).abs() <= epsilon * (len0 + len1) && d0.dot(d1) > 0.0) {
+            points.remove(i);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+
+    points
+}
+
+// Positive when the contour is counter clockwise with the y axis pointing up.
+fn signed_area(points: &[Vec2]) -> f64 {
+    let mut area = 0.0;
+
+    for (i, p0) in points.iter().enumerate() {
+        let p1 = points[(i + 1) % points.len()];
+        area += p0.cross(p1);
+    }
+
+    area * 0.5
+}
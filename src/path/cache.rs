@@ -7,9 +7,9 @@ use bitflags::bitflags;
 use crate::geometry::{self, Bounds, Transform2D};
 use crate::renderer::Vertex;
 use crate::utils::VecRetainMut;
-use crate::{FillRule, LineCap, LineJoin, Solidity};
+use crate::{FillRule, LineCap, LineJoin, Paint, Solidity};
 
-use super::Verb;
+use super::{Path, Verb};
 
 bitflags! {
     #[derive(Default)]
@@ -55,6 +55,14 @@ impl Point {
     }
 }
 
+/// Where a quad lies relative to the area a path covers, see PathCache::quad_overlap.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Overlap {
+    Inside,
+    Outside,
+    Partial,
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Convexity {
     Concave,
@@ -72,6 +80,7 @@ impl Default for Convexity {
 pub struct Contour {
     point_range: Range<usize>,
     closed: bool,
+    reversed: bool,
     bevel: usize,
     solidity: Solidity,
     pub(crate) fill: Vec<Vertex>,
@@ -84,6 +93,7 @@ impl Default for Contour {
         Self {
             point_range: 0..0,
             closed: Default::default(),
+            reversed: Default::default(),
             bevel: Default::default(),
             solidity: Default::default(),
             fill: Default::default(),
@@ -104,12 +114,13 @@ impl Contour {
     fn polygon_area(points: &[Point]) -> f32 {
         let mut area = 0.0;
 
-        for window in points.windows(3) {
-            let p0 = window[0];
-            let p1 = window[1];
-            let p2 = window[2];
+        if let Some(p0) = points.first() {
+            for window in points[1..].windows(2) {
+                let p1 = window[0];
+                let p2 = window[1];
 
-            area += geometry::triarea2(p0.x, p0.y, p1.x, p1.y, p2.x, p2.y);
+                area += geometry::triarea2(p0.x, p0.y, p1.x, p1.y, p2.x, p2.y);
+            }
         }
 
         area * 0.5
@@ -244,10 +255,12 @@ impl PathCache {
 
             if contour.solidity == Solidity::Solid && area < 0.0 {
                 points.reverse();
+                contour.reversed = true;
             }
 
             if contour.solidity == Solidity::Hole && area > 0.0 {
                 points.reverse();
+                contour.reversed = true;
             }
 
             for i in 0..contour.point_count() {
@@ -484,6 +497,37 @@ impl PathCache {
     //     }
     // }
 
+    /// Iterates over the flattened points of each contour, in the winding enforced by their solidity.
+    pub(crate) fn polygons(&self) -> impl Iterator<Item = impl Iterator<Item = (f32, f32)> + '_> + '_ {
+        self.contours
+            .iter()
+            .map(move |contour| self.points[contour.point_range.clone()].iter().map(|p| (p.x, p.y)))
+    }
+
+    /// Returns the flattened contours as a path of straight segments, marked with the solidity that gave them their
+    /// winding so that it's kept when the path is cached again.
+    pub(crate) fn to_path(&self, dist_tol: f32) -> Path {
+        let mut path = Path::new();
+        path.set_distance_tolerance(dist_tol);
+
+        for contour in &self.contours {
+            let points = &self.points[contour.point_range.clone()];
+
+            if let Some((first, rest)) = points.split_first() {
+                path.move_to(first.x, first.y);
+
+                for p in rest {
+                    path.line_to(p.x, p.y);
+                }
+
+                path.close();
+                path.solidity(contour.solidity);
+            }
+        }
+
+        path
+    }
+
     pub fn contains_point(&self, x: f32, y: f32, fill_rule: FillRule) -> bool {
         // Early out if point is outside the bounding rectangle
         // TODO: Make this a method on Bounds
@@ -531,6 +575,250 @@ impl PathCache {
         }
     }
 
+    /// Returns true if the point is covered by the stroke vertices computed by the last call to
+    /// [expand_stroke](#method.expand_stroke).
+    pub(crate) fn stroke_contains_point(&self, x: f32, y: f32) -> bool {
+        self.contours.iter().any(|contour| {
+            contour
+                .stroke
+                .windows(3)
+                .any(|tri| triangle_contains_point(&tri[0], &tri[1], &tri[2], x, y))
+        })
+    }
+
+    /// Tells whether the convex quad lies inside, outside or across the edge of the area covered by the path
+    /// with the nonzero rule. The windings of all contours add up, so holes are respected. Quads touching an
+    /// edge count as across it.
+    pub(crate) fn quad_overlap(&self, quad: &[(f32, f32); 4]) -> Overlap {
+        let minx = quad.iter().fold(f32::MAX, |min, p| min.min(p.0));
+        let miny = quad.iter().fold(f32::MAX, |min, p| min.min(p.1));
+        let maxx = quad.iter().fold(f32::MIN, |max, p| max.max(p.0));
+        let maxy = quad.iter().fold(f32::MIN, |max, p| max.max(p.1));
+
+        if maxx < self.bounds.minx || minx > self.bounds.maxx || maxy < self.bounds.miny || miny > self.bounds.maxy {
+            return Overlap::Outside;
+        }
+
+        // The quad winds either way depending on the transform
+        let orientation = geometry::triarea2(quad[0].0, quad[0].1, quad[1].0, quad[1].1, quad[2].0, quad[2].1).signum();
+
+        let quad_contains = |x: f32, y: f32| {
+            (0..4).all(|i| {
+                let (p0, p1) = (quad[i], quad[(i + 1) % 4]);
+
+                geometry::triarea2(p0.0, p0.1, p1.0, p1.1, x, y) * orientation >= 0.0
+            })
+        };
+
+        let mut winding_number: i32 = 0;
+        let (x, y) = quad[0];
+
+        for contour in &self.contours {
+            for (p0, p1) in contour.point_pairs(&self.points) {
+                let near = p0.x.max(p1.x) >= minx
+                    && p0.x.min(p1.x) <= maxx
+                    && p0.y.max(p1.y) >= miny
+                    && p0.y.min(p1.y) <= maxy;
+
+                if near
+                    && (quad_contains(p1.x, p1.y)
+                        || (0..4).any(|i| segments_cross(quad[i], quad[(i + 1) % 4], (p0.x, p0.y), (p1.x, p1.y))))
+                {
+                    return Overlap::Partial;
+                }
+
+                // Nothing crosses the quad so far, one of its corners tells on which side it is
+
+                if p0.y <= y {
+                    if p1.y > y && Point::is_left(p0, p1, x, y) > 0.0 {
+                        winding_number += 1;
+                    }
+                } else if p1.y <= y && Point::is_left(p0, p1, x, y) < 0.0 {
+                    winding_number -= 1;
+                }
+            }
+        }
+
+        if winding_number != 0 {
+            Overlap::Inside
+        } else {
+            Overlap::Outside
+        }
+    }
+
+    /// Returns a copy of this cache split into the dashes of the paint's dash pattern, or None if the paint
+    /// strokes solid lines.
+    ///
+    /// `scale` is the scale of the transform the cache was built with, the pattern is scaled by it like the
+    /// line width is. Drawing and hit testing strokes both go through this so that they always match.
+    pub(crate) fn dashed_by(&self, paint: &Paint, scale: f32) -> Option<Self> {
+        if paint.line_dash().is_empty() {
+            return None;
+        }
+
+        let pattern: Vec<f32> = paint.line_dash().iter().map(|len| len * scale).collect();
+
+        Some(self.dashed(&pattern, paint.line_dash_offset() * scale))
+    }
+
+    /// Returns a copy of this cache with every contour split into open contours, one per dash.
+    ///
+    /// `pattern` holds alternating dash and gap lengths (with an even number of entries) and `offset` is the
+    /// distance into the pattern at which each contour starts. Both must already be in the transformed space
+    /// of the cache.
+    pub(crate) fn dashed(&self, pattern: &[f32], offset: f32) -> Self {
+        let pattern_len: f32 = pattern.iter().sum();
+
+        if pattern.len() < 2 || pattern_len <= 0.0 {
+            return self.clone();
+        }
+
+        let mut dashed = Self {
+            bounds: self.bounds,
+            ..Default::default()
+        };
+
+        for contour in &self.contours {
+            // Dashes follow the direction the path was drawn in, not the winding enforced for fills.
+            let mut points = self.points[contour.point_range.clone()].to_vec();
+
+            if contour.reversed {
+                points.reverse();
+            }
+
+            // Find where the offset lands in the pattern.
+            let mut index = 0;
+            let mut remaining = pattern[0];
+            let mut skip = offset.rem_euclid(pattern_len);
+
+            // Zero length dashes at the offset are kept, they are drawn as dots when the caps aren't butt.
+            while skip > remaining || (skip == remaining && remaining > 0.0) {
+                skip -= remaining;
+                index = (index + 1) % pattern.len();
+                remaining = pattern[index];
+            }
+
+            remaining -= skip;
+
+            let mut dashes: Vec<Vec<Point>> = Vec::new();
+            let mut current: Option<Vec<Point>> = None;
+            let starts_with_dash = index % 2 == 0;
+
+            let segment_count = if contour.closed { points.len() } else { points.len() - 1 };
+
+            for (i, p0) in points.iter().take(segment_count).enumerate() {
+                let p1 = &points[(i + 1) % points.len()];
+
+                let mut dx = p1.x - p0.x;
+                let mut dy = p1.y - p0.y;
+                let len = geometry::normalize(&mut dx, &mut dy);
+
+                let mut pos = 0.0;
+
+                while pos < len {
+                    let on = index % 2 == 0;
+                    let segment_end = remaining >= len - pos;
+                    let step = if segment_end { len - pos } else { remaining };
+
+                    if on && step > 0.0 {
+                        let dash = current.get_or_insert_with(|| {
+                            vec![Point::new(p0.x + dx * pos, p0.y + dy * pos, PointFlags::CORNER)]
+                        });
+
+                        if segment_end {
+                            dash.push(Point::new(p1.x, p1.y, p1.flags & PointFlags::CORNER));
+                        } else {
+                            dash.push(Point::new(
+                                p0.x + dx * (pos + step),
+                                p0.y + dy * (pos + step),
+                                PointFlags::CORNER,
+                            ));
+                        }
+                    }
+
+                    pos = if segment_end { len } else { pos + step };
+                    remaining -= step;
+
+                    if remaining <= 0.0 {
+                        if let Some(dash) = current.take() {
+                            dashes.push(dash);
+                        } else if on {
+                            dashes.push(vec![dot(p0.x + dx * pos, p0.y + dy * pos, dx, dy)]);
+                        }
+
+                        index = (index + 1) % pattern.len();
+                        remaining = pattern[index];
+                    }
+                }
+            }
+
+            // A zero length dash at the end of an open contour isn't reached by the loop
+            if !contour.closed && current.is_none() && index % 2 == 0 && remaining <= 0.0 {
+                if let [.., p0, p1] = &points[..] {
+                    let mut dx = p1.x - p0.x;
+                    let mut dy = p1.y - p0.y;
+                    geometry::normalize(&mut dx, &mut dy);
+
+                    dashes.push(vec![dot(p1.x, p1.y, dx, dy)]);
+                }
+            }
+
+            if let Some(mut dash) = current.take() {
+                if contour.closed && starts_with_dash {
+                    if dashes.is_empty() {
+                        // The dash covers the whole contour.
+                        dashed.add_dash(dash, contour.solidity, true);
+                        continue;
+                    }
+
+                    // A dash running through the start of a closed contour continues into the first dash.
+                    let first = dashes.remove(0);
+                    dash.extend(first.into_iter().skip(1));
+                }
+
+                dashes.push(dash);
+            }
+
+            for dash in dashes {
+                dashed.add_dash(dash, contour.solidity, false);
+            }
+        }
+
+        dashed
+    }
+
+    fn add_dash(&mut self, mut points: Vec<Point>, solidity: Solidity, closed: bool) {
+        points.dedup_by(|p1, p0| p0.approx_eq(p1, 0.0001));
+
+        if closed && points.len() > 1 && points[0].approx_eq(&points[points.len() - 1], 0.0001) {
+            points.pop();
+        }
+
+        if points.len() > 1 {
+            for i in 0..points.len() {
+                let p1 = points[(i + 1) % points.len()];
+                let p0 = &mut points[i];
+
+                p0.dx = p1.x - p0.x;
+                p0.dy = p1.y - p0.y;
+                p0.len = geometry::normalize(&mut p0.dx, &mut p0.dy);
+            }
+        } else if closed || !points.iter().any(|p| p.dx != 0.0 || p.dy != 0.0) {
+            // Only zero length dashes are a single point, they keep the direction they were given
+            return;
+        }
+
+        let start = self.points.len();
+        self.points.extend(points);
+
+        self.contours.push(Contour {
+            point_range: start..self.points.len(),
+            closed,
+            solidity,
+            ..Default::default()
+        });
+    }
+
     pub(crate) fn expand_fill(&mut self, fringe_width: f32, line_join: LineJoin, miter_limit: f32) {
         let has_fringe = fringe_width > 0.0;
 
@@ -644,6 +932,44 @@ impl PathCache {
         for contour in &mut self.contours {
             contour.stroke.clear();
 
+            // Zero length dashes are only their caps, butt caps draw nothing
+            if !contour.closed && contour.point_count() == 1 {
+                let p = &self.points[contour.point_range.start];
+                let ncap = ncap as usize;
+
+                match line_cap_start {
+                    LineCap::Butt => (),
+                    LineCap::Square => butt_cap_start(
+                        &mut contour.stroke,
+                        p,
+                        p,
+                        stroke_width,
+                        stroke_width - fringe_width,
+                        fringe_width,
+                        u0,
+                        u1,
+                    ),
+                    LineCap::Round => round_cap_start(&mut contour.stroke, p, p, stroke_width, ncap, u0, u1),
+                }
+
+                match line_cap_end {
+                    LineCap::Butt => (),
+                    LineCap::Square => butt_cap_end(
+                        &mut contour.stroke,
+                        p,
+                        p,
+                        stroke_width,
+                        stroke_width - fringe_width,
+                        fringe_width,
+                        u0,
+                        u1,
+                    ),
+                    LineCap::Round => round_cap_end(&mut contour.stroke, p, p, stroke_width, ncap, u0, u1),
+                }
+
+                continue;
+            }
+
             for (i, (p0, p1)) in contour.point_pairs(&self.points).enumerate() {
                 // Add start cap
                 if !contour.closed && i == 1 {
@@ -890,12 +1216,48 @@ impl PathCache {
     }
 }
 
+// Returns true if the segments intersect or touch.
+fn segments_cross(a0: (f32, f32), a1: (f32, f32), b0: (f32, f32), b1: (f32, f32)) -> bool {
+    let side = |p0: (f32, f32), p1: (f32, f32), p: (f32, f32)| geometry::triarea2(p0.0, p0.1, p1.0, p1.1, p.0, p.1);
+
+    side(a0, a1, b0) * side(a0, a1, b1) <= 0.0 && side(b0, b1, a0) * side(b0, b1, a1) <= 0.0
+}
+
+// Returns the single point of a zero length dash, facing the direction the dash is on.
+fn dot(x: f32, y: f32, dx: f32, dy: f32) -> Point {
+    Point {
+        dx,
+        dy,
+        ..Point::new(x, y, PointFlags::CORNER)
+    }
+}
+
 fn curve_divisions(radius: f32, arc: f32, tol: f32) -> u32 {
     let da = (radius / (radius + tol)).acos() * 2.0;
 
     ((arc / da).ceil() as u32).max(2)
 }
 
+// Works with either winding since triangle strips alternate between the two.
+fn triangle_contains_point(v0: &Vertex, v1: &Vertex, v2: &Vertex, x: f32, y: f32) -> bool {
+    // Strips repeat vertices, at butt caps for example. The sign test below would accept any point on the
+    // line through such a triangle.
+    let area = (v1.x - v0.x) * (v2.y - v0.y) - (v1.y - v0.y) * (v2.x - v0.x);
+
+    if area.abs() < 1e-6 {
+        return false;
+    }
+
+    let d0 = (v1.x - v0.x) * (y - v0.y) - (v1.y - v0.y) * (x - v0.x);
+    let d1 = (v2.x - v1.x) * (y - v1.y) - (v2.y - v1.y) * (x - v1.x);
+    let d2 = (v0.x - v2.x) * (y - v2.y) - (v0.y - v2.y) * (x - v2.x);
+
+    let has_neg = d0 < 0.0 || d1 < 0.0 || d2 < 0.0;
+    let has_pos = d0 > 0.0 || d1 > 0.0 || d2 > 0.0;
+
+    !(has_neg && has_pos)
+}
+
 fn butt_cap_start(verts: &mut Vec<Vertex>, p0: &Point, p1: &Point, w: f32, d: f32, aa: f32, u0: f32, u1: f32) {
     let px = p0.x - p1.dx * d;
     let py = p0.y - p1.dy * d;
@@ -1154,6 +1516,73 @@ mod tests {
 
         assert_eq!(path_cache.contours[0].convexity, Convexity::Concave);
     }
+
+    #[test]
+    fn dashed_line_is_split_into_open_contours() {
+        let mut path = Path::new();
+        path.move_to(0.0, 0.0);
+        path.line_to(100.0, 0.0);
+
+        let transform = Transform2D::identity();
+
+        let path_cache = PathCache::new(path.verbs(), &transform, 0.25, 0.01);
+        let dashed = path_cache.dashed(&[10.0, 10.0], 5.0);
+
+        // 0..5, 15..25, 35..45, 55..65, 75..85, 95..100
+        assert_eq!(dashed.contours.len(), 6);
+        assert!(dashed.contours.iter().all(|contour| !contour.closed));
+
+        let first = &dashed.points[dashed.contours[0].point_range.clone()];
+        assert_eq!((first[0].x, first[1].x), (0.0, 5.0));
+    }
+
+    #[test]
+    fn zero_length_dashes_are_drawn_as_caps() {
+        let mut path = Path::new();
+        path.move_to(0.0, 0.0);
+        path.line_to(100.0, 0.0);
+
+        let transform = Transform2D::identity();
+
+        let path_cache = PathCache::new(path.verbs(), &transform, 0.25, 0.01);
+        let mut dotted = path_cache.dashed(&[0.0, 10.0], 0.0);
+
+        // A dot every 10 units including both ends
+        assert_eq!(dotted.contours.len(), 11);
+        assert!(dotted.contours.iter().all(|contour| contour.point_count() == 1));
+
+        dotted.expand_stroke(2.0, 0.0, LineCap::Round, LineCap::Round, LineJoin::Miter, 10.0, 0.25);
+        assert!(dotted.contours.iter().all(|contour| !contour.stroke.is_empty()));
+        assert!(dotted.stroke_contains_point(51.5, 0.0));
+        assert!(!dotted.stroke_contains_point(55.0, 0.0));
+
+        dotted.expand_stroke(2.0, 0.0, LineCap::Butt, LineCap::Butt, LineJoin::Miter, 10.0, 0.25);
+        assert!(dotted.contours.iter().all(|contour| contour.stroke.is_empty()));
+    }
+
+    #[test]
+    fn quad_overlap_respects_holes() {
+        let mut outer = Path::new();
+        outer.rect(0.0, 0.0, 100.0, 100.0);
+
+        let mut hole = Path::new();
+        hole.rect(40.0, 40.0, 20.0, 20.0);
+
+        let ring = outer.difference(&hole, FillRule::NonZero);
+        let cache = PathCache::new(ring.verbs(), &Transform2D::identity(), 0.25, 0.01);
+
+        let quad = |x: f32, y: f32| [(x, y), (x + 10.0, y), (x + 10.0, y + 10.0), (x, y + 10.0)];
+
+        assert_eq!(cache.quad_overlap(&quad(10.0, 10.0)), Overlap::Inside);
+        assert_eq!(cache.quad_overlap(&quad(45.0, 45.0)), Overlap::Outside);
+        assert_eq!(cache.quad_overlap(&quad(200.0, 10.0)), Overlap::Outside);
+        assert_eq!(cache.quad_overlap(&quad(95.0, 10.0)), Overlap::Partial);
+        assert_eq!(cache.quad_overlap(&quad(35.0, 45.0)), Overlap::Partial);
+
+        // The hole is inside a larger quad, none of the quad edges cross it
+        let around = [(30.0, 30.0), (70.0, 30.0), (70.0, 70.0), (30.0, 70.0)];
+        assert_eq!(cache.quad_overlap(&around), Overlap::Partial);
+    }
 }
 
 /*
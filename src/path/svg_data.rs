@@ -0,0 +1,380 @@
+//! Conversion between paths and SVG path data, the syntax of the `d` attribute of `<path>` elements.
+
+use std::f32::consts::PI;
+
+use crate::ErrorKind;
+
+use super::{Path, Verb};
+
+pub(crate) fn parse(data: &str) -> Result<Path, ErrorKind> {
+    let mut parser = Parser {
+        data: data.as_bytes(),
+        pos: 0,
+    };
+
+    let mut path = Path::new();
+
+    // Current point and start of the current sub-path
+    let (mut x, mut y) = (0.0, 0.0);
+    let (mut start_x, mut start_y) = (0.0, 0.0);
+
+    // Second control point of the previous cubic or the control point of the previous quadratic segment,
+    // reflected by the smooth curve commands.
+    let mut last_cubic_ctrl: Option<(f32, f32)> = None;
+    let mut last_quad_ctrl: Option<(f32, f32)> = None;
+
+    let mut command: Option<u8> = None;
+
+    // After a close the next segment starts at the start of the closed sub-path.
+    let mut needs_move = false;
+
+    loop {
+        parser.skip_separators();
+
+        let c = match parser.peek() {
+            Some(c) => c,
+            None => break,
+        };
+
+        let command_pos = parser.pos;
+
+        command = if c.is_ascii_alphabetic() {
+            parser.pos += 1;
+            Some(c)
+        } else {
+            // Repeated arguments without a command letter, a move is followed by implicit line segments.
+            match command {
+                Some(b'M') => Some(b'L'),
+                Some(b'm') => Some(b'l'),
+                Some(b'Z') | Some(b'z') | None => return Err(parser.error()),
+                other => other,
+            }
+        };
+
+        let cmd = command.unwrap_or_default();
+        let upper = cmd.to_ascii_uppercase();
+
+        // Unknown commands are errors and path data has to start with a move
+        if !b"MLHVCSQTAZ".contains(&upper) || (path.is_empty() && upper != b'M') {
+            parser.pos = command_pos;
+            return Err(parser.error());
+        }
+
+        let (ox, oy) = if cmd.is_ascii_lowercase() { (x, y) } else { (0.0, 0.0) };
+
+        if needs_move && upper != b'M' {
+            path.move_to(start_x, start_y);
+        }
+
+        needs_move = false;
+
+        let mut cubic_ctrl = None;
+        let mut quad_ctrl = None;
+
+        match upper {
+            b'M' => {
+                x = ox + parser.number()?;
+                y = oy + parser.number()?;
+                start_x = x;
+                start_y = y;
+                path.move_to(x, y);
+            }
+            b'L' => {
+                x = ox + parser.number()?;
+                y = oy + parser.number()?;
+                path.line_to(x, y);
+            }
+            b'H' => {
+                x = ox + parser.number()?;
+                path.line_to(x, y);
+            }
+            b'V' => {
+                y = oy + parser.number()?;
+                path.line_to(x, y);
+            }
+            b'C' | b'S' => {
+                let (x1, y1) = if upper == b'C' {
+                    (ox + parser.number()?, oy + parser.number()?)
+                } else {
+                    last_cubic_ctrl.map_or((x, y), |(cx, cy)| (2.0 * x - cx, 2.0 * y - cy))
+                };
+
+                let x2 = ox + parser.number()?;
+                let y2 = oy + parser.number()?;
+                x = ox + parser.number()?;
+                y = oy + parser.number()?;
+
+                path.bezier_to(x1, y1, x2, y2, x, y);
+                cubic_ctrl = Some((x2, y2));
+            }
+            b'Q' | b'T' => {
+                let (x1, y1) = if upper == b'Q' {
+                    (ox + parser.number()?, oy + parser.number()?)
+                } else {
+                    last_quad_ctrl.map_or((x, y), |(cx, cy)| (2.0 * x - cx, 2.0 * y - cy))
+                };
+
+                x = ox + parser.number()?;
+                y = oy + parser.number()?;
+
+                path.quad_to(x1, y1, x, y);
+                quad_ctrl = Some((x1, y1));
+            }
+            b'A' => {
+                let rx = parser.number()?;
+                let ry = parser.number()?;
+                let rotation = parser.number()?;
+                let large_arc = parser.flag()?;
+                let sweep = parser.flag()?;
+                let (x0, y0) = (x, y);
+                x = ox + parser.number()?;
+                y = oy + parser.number()?;
+
+                arc_to(&mut path, x0, y0, rx, ry, rotation, large_arc, sweep, x, y);
+            }
+            _ => {
+                path.close();
+                x = start_x;
+                y = start_y;
+                needs_move = true;
+            }
+        }
+
+        last_cubic_ctrl = cubic_ctrl;
+        last_quad_ctrl = quad_ctrl;
+    }
+
+    Ok(path)
+}
+
+pub(crate) fn serialize(path: &Path) -> String {
+    let mut parts = Vec::new();
+
+    for verb in path.verbs() {
+        match verb {
+            Verb::MoveTo(x, y) => parts.push(format!("M{} {}", x, y)),
+            Verb::LineTo(x, y) => parts.push(format!("L{} {}", x, y)),
+            Verb::BezierTo(c1x, c1y, c2x, c2y, x, y) => {
+                parts.push(format!("C{} {} {} {} {} {}", c1x, c1y, c2x, c2y, x, y))
+            }
+            Verb::Close => parts.push("Z".to_string()),
+            // SVG has no notion of solidity, the winding of the coordinates is all there is.
+            Verb::Solid | Verb::Hole => (),
+        }
+    }
+
+    parts.join(" ")
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_ascii_whitespace() || c == b',' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn skip_digits(&mut self) -> usize {
+        let start = self.pos;
+
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+
+        self.pos - start
+    }
+
+    fn number(&mut self) -> Result<f32, ErrorKind> {
+        self.skip_separators();
+
+        let start = self.pos;
+
+        if let Some(b'+') | Some(b'-') = self.peek() {
+            self.pos += 1;
+        }
+
+        let mut digits = self.skip_digits();
+
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            digits += self.skip_digits();
+        }
+
+        if digits == 0 {
+            self.pos = start;
+            return Err(self.error());
+        }
+
+        // An exponent marker without digits after it is not part of the number
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            let mantissa_end = self.pos;
+            self.pos += 1;
+
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.pos += 1;
+            }
+
+            if self.skip_digits() == 0 {
+                self.pos = mantissa_end;
+            }
+        }
+
+        let text = std::str::from_utf8(&self.data[start..self.pos]).map_err(|_| self.error())?;
+
+        text.parse().map_err(|_| self.error())
+    }
+
+    // Arc flags are single digits that don't need to be separated from what follows.
+    fn flag(&mut self) -> Result<bool, ErrorKind> {
+        self.skip_separators();
+
+        match self.peek() {
+            Some(b'0') => {
+                self.pos += 1;
+                Ok(false)
+            }
+            Some(b'1') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    fn error(&self) -> ErrorKind {
+        let found = match self.peek() {
+            Some(c) => format!("'{}'", c as char),
+            None => "end of data".to_string(),
+        };
+
+        ErrorKind::PathParseError(format!("unexpected {} at position {}", found, self.pos))
+    }
+}
+
+/// Adds an elliptical arc from (x0, y0) to (x, y) as cubic bezier segments, one per quarter turn at most.
+///
+/// Follows the endpoint to center parameterization from the implementation notes of the SVG spec, including
+/// the correction of out of range radii.
+#[allow(clippy::too_many_arguments)]
+fn arc_to(
+    path: &mut Path,
+    x0: f32,
+    y0: f32,
+    rx: f32,
+    ry: f32,
+    rotation: f32,
+    large_arc: bool,
+    sweep: bool,
+    x: f32,
+    y: f32,
+) {
+    if x0 == x && y0 == y {
+        return;
+    }
+
+    let mut rx = rx.abs();
+    let mut ry = ry.abs();
+
+    if rx == 0.0 || ry == 0.0 {
+        path.line_to(x, y);
+        return;
+    }
+
+    let (sin_phi, cos_phi) = rotation.to_radians().sin_cos();
+
+    // Start point in the coordinate system of the ellipse, relative to the midpoint of the chord
+    let dx2 = (x0 - x) * 0.5;
+    let dy2 = (y0 - y) * 0.5;
+    let x1p = cos_phi * dx2 + sin_phi * dy2;
+    let y1p = -sin_phi * dx2 + cos_phi * dy2;
+
+    // Scale up radii that are too small to span the end points
+    let lambda = (x1p * x1p) / (rx * rx) + (y1p * y1p) / (ry * ry);
+
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = rx * rx * ry * ry - rx * rx * y1p * y1p - ry * ry * x1p * x1p;
+    let den = rx * rx * y1p * y1p + ry * ry * x1p * x1p;
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coef = sign * (num / den).max(0.0).sqrt();
+
+    let cxp = coef * rx * y1p / ry;
+    let cyp = -coef * ry * x1p / rx;
+
+    let cx = cos_phi * cxp - sin_phi * cyp + (x0 + x) * 0.5;
+    let cy = sin_phi * cxp + cos_phi * cyp + (y0 + y) * 0.5;
+
+    let angle = |ux: f32, uy: f32, vx: f32, vy: f32| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+
+    let ux = (x1p - cxp) / rx;
+    let uy = (y1p - cyp) / ry;
+    let vx = (-x1p - cxp) / rx;
+    let vy = (-y1p - cyp) / ry;
+
+    let start_angle = angle(1.0, 0.0, ux, uy);
+    let mut sweep_angle = angle(ux, uy, vx, vy);
+
+    if !sweep && sweep_angle > 0.0 {
+        sweep_angle -= 2.0 * PI;
+    } else if sweep && sweep_angle < 0.0 {
+        sweep_angle += 2.0 * PI;
+    }
+
+    let segments = (sweep_angle.abs() / (PI * 0.5)).ceil().max(1.0) as usize;
+    let delta = sweep_angle / segments as f32;
+    let kappa = 4.0 / 3.0 * (delta / 4.0).tan();
+
+    let point = |t: f32| {
+        let (sin_t, cos_t) = t.sin_cos();
+
+        (
+            cx + rx * cos_t * cos_phi - ry * sin_t * sin_phi,
+            cy + rx * cos_t * sin_phi + ry * sin_t * cos_phi,
+        )
+    };
+
+    let tangent = |t: f32| {
+        let (sin_t, cos_t) = t.sin_cos();
+
+        (
+            -rx * sin_t * cos_phi - ry * cos_t * sin_phi,
+            -rx * sin_t * sin_phi + ry * cos_t * cos_phi,
+        )
+    };
+
+    for i in 0..segments {
+        let t0 = start_angle + delta * i as f32;
+        let t1 = t0 + delta;
+
+        let (p0x, p0y) = point(t0);
+        let (d0x, d0y) = tangent(t0);
+        let (d1x, d1y) = tangent(t1);
+
+        // End exactly on the requested end point
+        let (p1x, p1y) = if i == segments - 1 { (x, y) } else { point(t1) };
+
+        path.bezier_to(
+            p0x + d0x * kappa,
+            p0y + d0y * kappa,
+            p1x - d1x * kappa,
+            p1y - d1y * kappa,
+            p1x,
+            p1y,
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Recording of canvas calls for replaying them later, on the same or on another canvas.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use generational_arena::Index;
+
+use crate::geometry::Transform2D;
+use crate::text::TextContext;
+use crate::{
+    BlendFactor, Canvas, Color, CompositeOperation, ErrorKind, FillRule, FontId, ImageFilter, ImageId, Paint, Path,
+    Rect, Renderer,
+};
+
+/// A recorded sequence of canvas calls.
+///
+/// Pictures are recorded with [Canvas::begin_recording](struct.Canvas.html#method.begin_recording) and
+/// replayed onto any canvas with [replay](#method.replay). With the `serde` feature they can be serialized,
+/// for example to capture a frame in one program and draw it again in another.
+///
+/// Image and font ids are only meaningful on the canvas they came from, so a picture refers to images and
+/// fonts by name instead. Images are named `image0`, `image1`, ... in the order the picture first uses
+/// them, unless given a name with [set_image_name](#method.set_image_name). Fonts are named by their
+/// PostScript name, or `font0`, `font1`, ... if they have none, unless given a name with
+/// [set_font_name](#method.set_font_name). The images and fonts to replay with are looked up by these names.
+///
+/// # Example
+/// ```
+/// use std::collections::HashMap;
+/// use gpucanvas::{Canvas, Color, Paint, Path, renderer::Void};
+///
+/// let mut canvas = Canvas::new(Void).expect("Cannot create canvas");
+/// canvas.set_size(100, 100, 1.0);
+///
+/// canvas.begin_recording();
+///
+/// let mut path = Path::new();
+/// path.rect(10.0, 10.0, 50.0, 50.0);
+/// canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+///
+/// let picture = canvas.end_recording().expect("Not recording");
+///
+/// let mut other = Canvas::new(Void).expect("Cannot create canvas");
+/// other.set_size(100, 100, 1.0);
+/// picture.replay(&mut other, &HashMap::new(), &HashMap::new()).expect("Cannot replay picture");
+/// ```
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Picture {
+    commands: Vec<PictureCommand>,
+    images: Vec<String>,
+    fonts: Vec<String>,
+    // Ids the images and fonts had on the recording canvas, in the order of their names. The paints of
+    // the commands refer to them by their position, see record.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    image_ids: Vec<ImageId>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    font_ids: Vec<FontId>,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) enum PictureCommand {
+    Save,
+    Restore,
+    Reset,
+    SetGlobalAlpha(f32),
+    GlobalCompositeOperation(CompositeOperation),
+    GlobalCompositeBlendFuncSeparate {
+        src_rgb: BlendFactor,
+        dst_rgb: BlendFactor,
+        src_alpha: BlendFactor,
+        dst_alpha: BlendFactor,
+    },
+    SetTransform(Transform2D),
+    PremultiplyTransform(Transform2D),
+    ResetTransform,
+    Scissor {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    },
+    IntersectScissor {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    },
+    ResetScissor,
+    ClipPath {
+        path: Path,
+        fill_rule: FillRule,
+    },
+    ResetClip,
+    ClearRect {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        color: Color,
+    },
+    FillPath {
+        path: Path,
+        paint: Paint,
+    },
+    StrokePath {
+        path: Path,
+        paint: Paint,
+    },
+    FillText {
+        x: f32,
+        y: f32,
+        text: String,
+        paint: Paint,
+    },
+    StrokeText {
+        x: f32,
+        y: f32,
+        text: String,
+        paint: Paint,
+    },
+    FillParagraph {
+        x: f32,
+        y: f32,
+        max_width: f32,
+        text: String,
+        paint: Paint,
+    },
+    StrokeParagraph {
+        x: f32,
+        y: f32,
+        max_width: f32,
+        text: String,
+        paint: Paint,
+    },
+    FillParagraphSpans {
+        x: f32,
+        y: f32,
+        max_width: f32,
+        spans: Vec<(String, Paint)>,
+    },
+    StrokeParagraphSpans {
+        x: f32,
+        y: f32,
+        max_width: f32,
+        spans: Vec<(String, Paint)>,
+    },
+    DropShadow {
+        path: Path,
+        offset: (f32, f32),
+        blur: f32,
+        color: Color,
+    },
+    BeginLayer {
+        bounds: Rect,
+        alpha: f32,
+        op: CompositeOperation,
+    },
+    SetLayerFilters(Vec<ImageFilter>),
+    EndLayer,
+}
+
+impl Picture {
+    /// Returns the names of the images the picture draws, in the order they were first used.
+    pub fn image_names(&self) -> &[String] {
+        &self.images
+    }
+
+    /// Returns the names of the fonts the picture draws text with, in the order they were first used.
+    pub fn font_names(&self) -> &[String] {
+        &self.fonts
+    }
+
+    /// Names the image that had the given id on the canvas the picture was recorded on.
+    ///
+    /// Does nothing if the picture doesn't use the image or was deserialized.
+    pub fn set_image_name(&mut self, id: ImageId, name: &str) {
+        if let Some(index) = self.image_ids.iter().position(|image_id| *image_id == id) {
+            self.images[index] = name.to_string();
+        }
+    }
+
+    /// Names the font that had the given id on the canvas the picture was recorded on.
+    ///
+    /// Does nothing if the picture doesn't use the font or was deserialized.
+    pub fn set_font_name(&mut self, id: FontId, name: &str) {
+        if let Some(index) = self.font_ids.iter().position(|font_id| *font_id == id) {
+            self.fonts[index] = name.to_string();
+        }
+    }
+
+    /// Replays the recorded calls onto the canvas, on top of its current state.
+    ///
+    /// `images` maps the names of the images the picture uses to images of the canvas. `fonts` does the
+    /// same for fonts, names it doesn't have are looked up among the PostScript names of the fonts of the
+    /// canvas. Fails with `ErrorKind::ImageIdNotFound` if an image is missing and with
+    /// `ErrorKind::NoFontFound` if a font is.
+    pub fn replay<T: Renderer>(
+        &self,
+        canvas: &mut Canvas<T>,
+        images: &HashMap<String, ImageId>,
+        fonts: &HashMap<String, FontId>,
+    ) -> Result<(), ErrorKind> {
+        let image_ids = self
+            .images
+            .iter()
+            .map(|name| images.get(name).copied().ok_or(ErrorKind::ImageIdNotFound))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let font_ids = self
+            .fonts
+            .iter()
+            .map(|name| {
+                fonts
+                    .get(name)
+                    .copied()
+                    .or_else(|| canvas.text_context.font_by_name(name))
+                    .ok_or(ErrorKind::NoFontFound)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let resolve = |paint: &Paint| {
+            let mut paint = *paint;
+
+            paint.map_ids(
+                |id| image_ids.get(position(id.0)).copied().ok_or(ErrorKind::ImageIdNotFound),
+                |id| font_ids.get(position(id.0)).copied().ok_or(ErrorKind::NoFontFound),
+            )?;
+
+            Ok::<_, ErrorKind>(paint)
+        };
+
+        let resolve_spans = |spans: &[(String, Paint)]| {
+            spans
+                .iter()
+                .map(|(text, paint)| Ok((text.clone(), resolve(paint)?)))
+                .collect::<Result<Vec<_>, ErrorKind>>()
+        };
+
+        for command in &self.commands {
+            match command {
+                PictureCommand::Save => canvas.save(),
+                PictureCommand::Restore => canvas.restore(),
+                PictureCommand::Reset => canvas.reset(),
+                PictureCommand::SetGlobalAlpha(alpha) => canvas.set_global_alpha(*alpha),
+                PictureCommand::GlobalCompositeOperation(op) => canvas.global_composite_operation(*op),
+                PictureCommand::GlobalCompositeBlendFuncSeparate {
+                    src_rgb,
+                    dst_rgb,
+                    src_alpha,
+                    dst_alpha,
+                } => canvas.global_composite_blend_func_separate(*src_rgb, *dst_rgb, *src_alpha, *dst_alpha),
+                PictureCommand::SetTransform(transform) => canvas.set_transform(*transform),
+                PictureCommand::PremultiplyTransform(transform) => canvas.premultiply_transform(*transform),
+                PictureCommand::ResetTransform => canvas.reset_transform(),
+                PictureCommand::Scissor { x, y, w, h } => canvas.scissor(*x, *y, *w, *h),
+                PictureCommand::IntersectScissor { x, y, w, h } => canvas.intersect_scissor(*x, *y, *w, *h),
+                PictureCommand::ResetScissor => canvas.reset_scissor(),
+                PictureCommand::ClipPath { path, fill_rule } => canvas.clip_path(&mut path.clone(), *fill_rule),
+                PictureCommand::ResetClip => canvas.reset_clip(),
+                PictureCommand::ClearRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                } => canvas.clear_rect(*x, *y, *width, *height, *color),
+                PictureCommand::FillPath { path, paint } => canvas.fill_path(&mut path.clone(), resolve(paint)?),
+                PictureCommand::StrokePath { path, paint } => canvas.stroke_path(&mut path.clone(), resolve(paint)?),
+                PictureCommand::FillText { x, y, text, paint } => {
+                    canvas.fill_text(*x, *y, text, resolve(paint)?)?;
+                }
+                PictureCommand::StrokeText { x, y, text, paint } => {
+                    canvas.stroke_text(*x, *y, text, resolve(paint)?)?;
+                }
+                PictureCommand::FillParagraph {
+                    x,
+                    y,
+                    max_width,
+                    text,
+                    paint,
+                } => {
+                    canvas.fill_paragraph(*x, *y, *max_width, text, resolve(paint)?)?;
+                }
+                PictureCommand::StrokeParagraph {
+                    x,
+                    y,
+                    max_width,
+                    text,
+                    paint,
+                } => {
+                    canvas.stroke_paragraph(*x, *y, *max_width, text, resolve(paint)?)?;
+                }
+                PictureCommand::FillParagraphSpans { x, y, max_width, spans } => {
+                    let spans = resolve_spans(spans)?;
+                    canvas.fill_paragraph_spans(*x, *y, *max_width, &spans)?;
+                }
+                PictureCommand::StrokeParagraphSpans { x, y, max_width, spans } => {
+                    let spans = resolve_spans(spans)?;
+                    canvas.stroke_paragraph_spans(*x, *y, *max_width, &spans)?;
+                }
+                PictureCommand::DropShadow {
+                    path,
+                    offset,
+                    blur,
+                    color,
+                } => canvas.drop_shadow(&mut path.clone(), *offset, *blur, *color)?,
+                PictureCommand::BeginLayer { bounds, alpha, op } => canvas.begin_layer(*bounds, *alpha, *op)?,
+                PictureCommand::SetLayerFilters(filters) => canvas.set_layer_filters(filters),
+                PictureCommand::EndLayer => canvas.end_layer(),
+            }
+        }
+
+        Ok(())
+    }
+
+    // The image and font ids in the paints of recorded commands are replaced by ids whose index is the
+    // position of the image or font in the name lists.
+    pub(crate) fn record(&mut self, mut command: PictureCommand, text_context: &TextContext) {
+        let Self {
+            images,
+            fonts,
+            image_ids,
+            font_ids,
+            ..
+        } = self;
+
+        for paint in command.paints_mut() {
+            let _ = paint.map_ids::<Infallible>(
+                |id| {
+                    let index = index_of(image_ids, images, id, |index| format!("image{}", index));
+
+                    Ok(ImageId(Index::from_raw_parts(index, 0)))
+                },
+                |id| {
+                    let index = index_of(font_ids, fonts, id, |index| {
+                        text_context
+                            .font(id)
+                            .and_then(|font| font.name())
+                            .unwrap_or_else(|| format!("font{}", index))
+                    });
+
+                    Ok(FontId(Index::from_raw_parts(index, 0)))
+                },
+            );
+        }
+
+        self.commands.push(command);
+    }
+}
+
+impl PictureCommand {
+    fn paints_mut(&mut self) -> Vec<&mut Paint> {
+        match self {
+            Self::FillPath { paint, .. }
+            | Self::StrokePath { paint, .. }
+            | Self::FillText { paint, .. }
+            | Self::StrokeText { paint, .. }
+            | Self::FillParagraph { paint, .. }
+            | Self::StrokeParagraph { paint, .. } => vec![paint],
+            Self::FillParagraphSpans { spans, .. } | Self::StrokeParagraphSpans { spans, .. } => {
+                spans.iter_mut().map(|(_, paint)| paint).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn position(index: Index) -> usize {
+    index.into_raw_parts().0
+}
+
+// Returns the position of the id, adding it with a name made from its position if it's new.
+fn index_of<Id: PartialEq>(
+    ids: &mut Vec<Id>,
+    names: &mut Vec<String>,
+    id: Id,
+    name: impl FnOnce(usize) -> String,
+) -> usize {
+    ids.iter().position(|other| *other == id).unwrap_or_else(|| {
+        names.push(name(ids.len()));
+        ids.push(id);
+        ids.len() - 1
+    })
+}
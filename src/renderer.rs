@@ -1,9 +1,15 @@
 //! Module containing renderer implementations
 
+use std::fmt;
+use std::sync::Arc;
+
 use imgref::ImgVec;
 use rgb::RGBA8;
 
-use crate::{Color, CompositeOperationState, ErrorKind, FillRule, ImageId, ImageInfo, ImageSource, ImageStore, Size};
+use crate::geometry::Transform2D;
+use crate::{
+    Color, CompositeOperationState, ErrorKind, FillRule, ImageId, ImageInfo, ImageSource, ImageStore, Paint, Path, Size,
+};
 
 mod opengl;
 pub use opengl::OpenGl;
@@ -14,22 +20,48 @@ pub use mtl::{Mtl, MtlStencilTexture, MtlTexture, should_render};
 mod void;
 pub use void::Void;
 
+mod software;
+pub use software::{Software, SoftwareImage};
+
+mod recorder;
+pub use recorder::{RecordedFrame, Recorder};
+
+mod vector;
+
+mod svg_export;
+pub use svg_export::{SvgExport, SvgImage};
+
+mod pdf;
+pub use pdf::{Pdf, PdfImage};
+
 mod params;
-pub(crate) use params::Params;
+pub use params::Params;
 
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Debug, Default)]
 pub struct Drawable {
     pub(crate) fill_verts: Option<(usize, usize)>,
     pub(crate) stroke_verts: Option<(usize, usize)>,
     // pub(crate) index_verts: Option<(usize, usize)>,
 }
 
+impl Drawable {
+    /// Range (start, count) of the triangle fan vertices that fill the shape.
+    pub fn fill_verts(&self) -> Option<(usize, usize)> {
+        self.fill_verts
+    }
+
+    /// Range (start, count) of the triangle strip vertices for the stroke or the antialiased fill fringe.
+    pub fn stroke_verts(&self) -> Option<(usize, usize)> {
+        self.stroke_verts
+    }
+}
+
 // pub type GPUC = fn() -> ();
 pub trait CommandEncoder: std::fmt::Debug {
     fn encode(&self, encoder: &metal::RenderCommandEncoderRef);
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum CommandType {
     CustomCommand {
         command_encoder: std::sync::Arc<dyn CommandEncoder>,
@@ -66,6 +98,7 @@ pub enum CommandType {
     },
 }
 
+#[derive(Clone, Debug)]
 pub struct Command {
     pub(crate) cmd_type: CommandType,
     pub(crate) drawables: Vec<Drawable>,
@@ -74,6 +107,7 @@ pub struct Command {
     pub(crate) alpha_mask: Option<ImageId>,
     pub(crate) fill_rule: FillRule,
     pub(crate) composite_operation: CompositeOperationState,
+    pub(crate) vector_shape: Option<Box<VectorShape>>,
 }
 
 impl Command {
@@ -86,8 +120,77 @@ impl Command {
             alpha_mask: Default::default(),
             fill_rule: Default::default(),
             composite_operation: Default::default(),
+            vector_shape: Default::default(),
         }
     }
+
+    pub fn cmd_type(&self) -> &CommandType {
+        &self.cmd_type
+    }
+
+    pub fn drawables(&self) -> &[Drawable] {
+        &self.drawables
+    }
+
+    /// Range (start, count) of the vertices drawn as triangles, used by `Triangles` commands and for
+    /// the bounding quad of `ConcaveFill` commands.
+    pub fn triangles_verts(&self) -> Option<(usize, usize)> {
+        self.triangles_verts
+    }
+
+    pub fn image(&self) -> Option<ImageId> {
+        self.image
+    }
+
+    pub fn alpha_mask(&self) -> Option<ImageId> {
+        self.alpha_mask
+    }
+
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    pub fn composite_operation(&self) -> CompositeOperationState {
+        self.composite_operation
+    }
+}
+
+/// The outline and paint of a fill or a stroke as the canvas was given them, in device pixels. Only set for
+/// renderers that produce vector output, which write it in place of the vertices.
+#[derive(Clone, Debug)]
+pub(crate) struct VectorShape {
+    /// Outline with its curves. Filled contours are wound the way their solidity says, stroked ones keep
+    /// the direction they were drawn in.
+    pub(crate) path: Path,
+    /// Paint with its transform, line width and dashes in device pixels and the global alpha applied.
+    pub(crate) paint: Paint,
+    pub(crate) stroke: bool,
+    /// Area the shape is cut to, the scissor in the params applies as well.
+    pub(crate) clip: Option<Arc<Path>>,
+    /// Set when the shape is the outline of a glyph.
+    pub(crate) glyph: Option<VectorGlyph>,
+}
+
+/// A glyph of a font file, for renderers that embed fonts and show text instead of glyph outlines.
+#[derive(Clone)]
+pub(crate) struct VectorGlyph {
+    /// Data of the font file.
+    pub(crate) font: Arc<[u8]>,
+    pub(crate) id: u16,
+    /// The character the glyph was shaped from.
+    pub(crate) c: char,
+    /// Maps font units to device pixels.
+    pub(crate) transform: Transform2D,
+}
+
+impl fmt::Debug for VectorGlyph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VectorGlyph")
+            .field("id", &self.id)
+            .field("c", &self.c)
+            .field("transform", &self.transform)
+            .finish()
+    }
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
@@ -141,6 +244,12 @@ pub trait Renderer {
     fn flip_y() -> bool;
     fn flip_uv() -> bool;
 
+    /// Renderers that produce vector output instead of pixels return true. The canvas then generates exact
+    /// geometry without antialiasing fringes and draws all text as glyph outlines.
+    fn is_vector() -> bool {
+        false
+    }
+
     fn start_capture(&self);
     fn stop_capture(&self);
 
@@ -171,11 +280,15 @@ impl Vertex {
 }
 
 #[repr(u32)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ShaderType {
     FillGradient,
     FillImage,
     Stencil,
+    FillImageGradient,
+    FillConicGradient,
+    FilterImage,
+    BlurImage,
 }
 
 impl Default for ShaderType {
@@ -190,6 +303,22 @@ impl ShaderType {
             Self::FillGradient => 0.0,
             Self::FillImage => 1.0,
             Self::Stencil => 2.0,
+            Self::FillImageGradient => 3.0,
+            Self::FillConicGradient => 4.0,
+            Self::FilterImage => 5.0,
+            Self::BlurImage => 6.0,
+        }
+    }
+
+    pub fn from_f32(value: f32) -> Self {
+        match value as u32 {
+            1 => Self::FillImage,
+            2 => Self::Stencil,
+            3 => Self::FillImageGradient,
+            4 => Self::FillConicGradient,
+            5 => Self::FilterImage,
+            6 => Self::BlurImage,
+            _ => Self::FillGradient,
         }
     }
 }
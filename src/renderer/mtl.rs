@@ -330,11 +330,10 @@ impl Mtl {
             layer.set_opaque(false);
         }
 
-        let library = {
-            let root_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-            let library_path = root_path.join("src/renderer/mtl/shaders.metallib");
-            device.new_library_with_file(library_path).expect("library not found")
-        };
+        // Compiled from source so that the shaders can't get out of date with a prebuilt library
+        let library = device
+            .new_library_with_source(include_str!("mtl/shaders.metal"), &metal::CompileOptions::new())
+            .expect("failed to compile shaders.metal");
 
         let gpu_encoder = GPUCommandEncoder::new(device, &library);
 
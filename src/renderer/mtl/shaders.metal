@@ -27,6 +27,10 @@ struct RasterizerData {
 // float strokeThr;
 // float texType;
 // float type;
+// float hasMask;
+// float spreadMode;
+// float colorMatrix[16];
+// float lookup;
 
 #define STATIC_ASSERT(COND,MSG) typedef char static_assertion_##MSG[(!!(COND))*2-1]
 #define COMPILE_TIME_ASSERT3(X,L) STATIC_ASSERT(X,static_assertion_at_line_##L)
@@ -37,6 +41,10 @@ enum ShaderType {
     FillGradient,
     FillImage,
     Stencil,
+    FillImageGradient,
+    FillConicGradient,
+    FilterImage,
+    BlurImage,
 };
 
 // COMPILE_TIME_ASSERT(sizeof(Uniforms) == 4);
@@ -56,7 +64,11 @@ struct Uniforms {
     float texType;
     float shaderType;
     float hasMask;
-    float padding[19];
+    float spreadMode;
+    // column major, not a float4x4 which would have to be aligned to 16 bytes
+    float colorMatrix[16];
+    float lookup;
+    float padding[1];
 };
 
 COMPILE_TIME_ASSERT(sizeof(Uniforms) == 256);
@@ -64,6 +76,9 @@ COMPILE_TIME_ASSERT(sizeof(Uniforms) == 256);
 float scissorMask(constant Uniforms& uniforms, float2 p);
 float sdroundrect(constant Uniforms& uniforms, float2 pt);
 float strokeMask(constant Uniforms& uniforms, float2 ftcoord);
+float spread(constant Uniforms& uniforms, float d);
+float4 texel(constant Uniforms& uniforms, texture2d<float> texture, sampler samplr, float2 pt);
+float4 blurTaps(constant Uniforms& uniforms, texture2d<float> texture, sampler samplr, float2 pt, float offset);
 
 float scissorMask(constant Uniforms& uniforms, float2 p) {
     float2 sc = (abs((uniforms.scissorMat * float3(p, 1.0f)).xy)
@@ -92,6 +107,34 @@ float strokeMask(constant Uniforms& uniforms, float2 ftcoord) {
        * min(1.0, ftcoord.y);
 }
 
+// gradient position beyond [0..1] according to the spread mode
+float spread(constant Uniforms& uniforms, float d) {
+    if (uniforms.spreadMode == 1) {
+        return fract(d);
+    } else if (uniforms.spreadMode == 2) {
+        return 1.0 - abs(fmod(abs(d), 2.0) - 1.0);
+    }
+    return clamp(d, 0.0, 1.0);
+}
+
+// premultiplied image color
+float4 texel(constant Uniforms& uniforms, texture2d<float> texture, sampler samplr, float2 pt) {
+    float4 color = texture.sample(samplr, pt);
+    if (uniforms.texType == 1) {
+        color = float4(color.xyz * color.w, color.w);
+    }
+    else if (uniforms.texType == 2) {
+        color = float4(color.x);
+    }
+    return color;
+}
+
+// adds up the texels the offset away on both sides along the direction of a blur pass
+float4 blurTaps(constant Uniforms& uniforms, texture2d<float> texture, sampler samplr, float2 pt, float offset) {
+    float2 step = uniforms.outerCol.xy * offset;
+    return texel(uniforms, texture, samplr, pt + step) + texel(uniforms, texture, samplr, pt - step);
+}
+
 // Vertex Function
 vertex RasterizerData vertexShader(Vertex vert [[stage_in]],
                                    constant float2& viewSize [[buffer(1)]]) {
@@ -197,7 +240,7 @@ fragment float4 fragmentShaderAA(
         // MNVG_SHADER_FILLGRAD
         float2 pt = (uniforms.paintMat * float3(in.fpos, 1.0)).xy;
         // revisit d
-        float d = clamp((sdroundrect(uniforms, pt) + uniforms.feather*0.5) / uniforms.feather, 0.0, 1.0);
+        float d = spread(uniforms, (sdroundrect(uniforms, pt) + uniforms.feather*0.5) / uniforms.feather);
         // float d = saturate((uniforms.feather * 0.5 + sdroundrect(uniforms, pt))
         //                    / uniforms.feather);
         float4 color = mix(uniforms.innerCol, uniforms.outerCol, d);
@@ -216,6 +259,55 @@ fragment float4 fragmentShaderAA(
         else if (uniforms.texType == 2) {
             color = float4(color.x);
         }
+        result = color * uniforms.innerCol;
+    } else if (uniforms.shaderType == 3) {
+        // multi stop gradient, the color is looked up from a 256x1 color ramp
+        float2 pt = (uniforms.paintMat * float3(in.fpos, 1.0)).xy;
+        float d = spread(uniforms, (sdroundrect(uniforms, pt) + uniforms.feather*0.5) / uniforms.feather);
+        result = texture.sample(samplr, float2(d * 255.0/256.0 + 0.5/256.0, 0.5));
+    } else if (uniforms.shaderType == 4) {
+        // conic gradient, the angle around the center goes from 0 at the start angle to 1 after a full turn
+        float2 pt = (uniforms.paintMat * float3(in.fpos, 1.0)).xy;
+        float d = fract(atan2(pt.y, pt.x) / 6.28318530718);
+        result = texture.sample(samplr, float2(d * 255.0/256.0 + 0.5/256.0, 0.5));
+    } else if (uniforms.shaderType == 5) {
+        // image filter, sampled like an image but unpremultiplied to go through the color matrix
+        float2 pt = (uniforms.paintMat * float3(in.fpos, 1.0)).xy / uniforms.extent;
+
+        float4 color = texture.sample(samplr, pt);
+        if (uniforms.texType == 0 && color.w > 0.0) {
+            color = float4(color.xyz / color.w, color.w);
+        }
+        else if (uniforms.texType == 2) {
+            color = float4(color.x);
+        }
+
+        constant float* m = uniforms.colorMatrix;
+        float4x4 colorMatrix = float4x4(float4(m[0], m[1], m[2], m[3]),
+                                        float4(m[4], m[5], m[6], m[7]),
+                                        float4(m[8], m[9], m[10], m[11]),
+                                        float4(m[12], m[13], m[14], m[15]));
+        color = clamp(colorMatrix * color + uniforms.outerCol, 0.0, 1.0);
+
+        // each channel is looked up in its channel of a 256x1 table bound in place of the alpha mask
+        if (uniforms.lookup == 1.0) {
+            color = float4(alpha_texture.sample(alpha_samplr, float2(color.x * 255.0/256.0 + 0.5/256.0, 0.5)).x,
+                           alpha_texture.sample(alpha_samplr, float2(color.y * 255.0/256.0 + 0.5/256.0, 0.5)).y,
+                           alpha_texture.sample(alpha_samplr, float2(color.z * 255.0/256.0 + 0.5/256.0, 0.5)).z,
+                           alpha_texture.sample(alpha_samplr, float2(color.w * 255.0/256.0 + 0.5/256.0, 0.5)).w);
+        }
+
+        result = float4(color.xyz * color.w, color.w) * uniforms.innerCol;
+    } else if (uniforms.shaderType == 6) {
+        // blur pass, the color matrix holds the weights of the offsets from 0 to 15 pixels, the outer color
+        // the offset of one pixel and the radius the largest offset with a weight
+        float2 pt = (uniforms.paintMat * float3(in.fpos, 1.0)).xy / uniforms.extent;
+
+        float4 color = float4(0.0);
+        for (int i = 0; i <= int(uniforms.radius); i++) {
+            color += uniforms.colorMatrix[i] * blurTaps(uniforms, texture, samplr, pt, float(i));
+        }
+
         result = color * uniforms.innerCol;
     } else {
         // stencil
@@ -1,7 +1,7 @@
 
 precision mediump float;
 
-#define UNIFORMARRAY_SIZE 12
+#define UNIFORMARRAY_SIZE 16
 
 uniform vec4 frag[UNIFORMARRAY_SIZE];
 
@@ -19,6 +19,9 @@ uniform vec4 frag[UNIFORMARRAY_SIZE];
 #define texType int(frag[10].z)
 #define shaderType int(frag[10].w)
 #define hasMask int(frag[11].x)
+#define spreadMode int(frag[11].y)
+#define lookup int(frag[11].z)
+#define colorMatrix mat4(frag[12], frag[13], frag[14], frag[15])
 
 uniform sampler2D tex;
 uniform sampler2D masktex;
@@ -33,6 +36,13 @@ float sdroundrect(vec2 pt, vec2 ext, float rad) {
     return min(max(d.x,d.y),0.0) + length(max(d,0.0)) - rad;
 }
 
+// Gradient position beyond [0..1] according to the spread mode
+float spread(float d) {
+    if (spreadMode == 1) return fract(d);
+    if (spreadMode == 2) return 1.0 - abs(mod(d, 2.0) - 1.0);
+    return clamp(d, 0.0, 1.0);
+}
+
 // Scissoring
 float scissorMask(vec2 p) {
     vec2 sc = (abs((scissorMat * vec3(p,1.0)).xy) - scissorExt);
@@ -40,6 +50,21 @@ float scissorMask(vec2 p) {
     return clamp(sc.x,0.0,1.0) * clamp(sc.y,0.0,1.0);
 }
 
+// Premultiplied image color
+vec4 texel(vec2 pt) {
+    vec4 color = texture2D(tex, pt);
+
+    if (texType == 1) color = vec4(color.xyz * color.w, color.w);
+    if (texType == 2) color = vec4(color.x);
+
+    return color;
+}
+
+// Blur pass - adds up the texels the offset away on both sides along the direction of the pass
+vec4 blurTaps(vec2 pt, float offset) {
+    return texel(pt + outerCol.xy * offset) + texel(pt - outerCol.xy * offset);
+}
+
 #ifdef EDGE_AA
 // Stroke - from [0..1] to clipped pyramid, where the slope is 1px.
 float strokeMask() {
@@ -70,7 +95,7 @@ void main(void) {
         // Calculate gradient color using box gradient
         vec2 pt = (paintMat * vec3(fpos, 1.0)).xy;
 
-        float d = clamp((sdroundrect(pt, extent, radius) + feather*0.5) / feather, 0.0, 1.0);
+        float d = spread((sdroundrect(pt, extent, radius) + feather*0.5) / feather);
         vec4 color = mix(innerCol,outerCol,d);
 
         result = color;
@@ -92,6 +117,71 @@ void main(void) {
     } else if (shaderType == 2) {
         // Stencil fill
         result = vec4(1,1,1,1);
+    } else if (shaderType == 3) {
+        // Multi stop gradient
+
+        // Same as the gradient above, but the color is looked up from a 256x1 color ramp
+        vec2 pt = (paintMat * vec3(fpos, 1.0)).xy;
+
+        float d = spread((sdroundrect(pt, extent, radius) + feather*0.5) / feather);
+        vec4 color = texture2D(tex, vec2(d * 255.0/256.0 + 0.5/256.0, 0.5));
+
+        result = color;
+    } else if (shaderType == 4) {
+        // Conic gradient
+
+        // The angle around the center, from 0 at the start angle to 1 after a full clockwise turn
+        vec2 pt = (paintMat * vec3(fpos, 1.0)).xy;
+
+        float d = fract(atan(pt.y, pt.x) / 6.28318530718);
+        vec4 color = texture2D(tex, vec2(d * 255.0/256.0 + 0.5/256.0, 0.5));
+
+        result = color;
+    } else if (shaderType == 5) {
+        // Image filter
+
+        // Sampled like an image, but the color is unpremultiplied to go through the color matrix
+        vec2 pt = (paintMat * vec3(fpos, 1.0)).xy / extent;
+
+        vec4 color = texture2D(tex, pt);
+        if (texType == 0 && color.w > 0.0) color = vec4(color.xyz / color.w, color.w);
+        if (texType == 2) color = vec4(color.x);
+
+        color = clamp(colorMatrix * color + outerCol, 0.0, 1.0);
+
+        // Each channel is looked up in its channel of a 256x1 table
+        if (lookup == 1) {
+            color = vec4(
+                texture2D(masktex, vec2(color.x * 255.0/256.0 + 0.5/256.0, 0.5)).x,
+                texture2D(masktex, vec2(color.y * 255.0/256.0 + 0.5/256.0, 0.5)).y,
+                texture2D(masktex, vec2(color.z * 255.0/256.0 + 0.5/256.0, 0.5)).z,
+                texture2D(masktex, vec2(color.w * 255.0/256.0 + 0.5/256.0, 0.5)).w
+            );
+        }
+
+        result = vec4(color.xyz * color.w, color.w) * innerCol;
+    } else if (shaderType == 6) {
+        // Blur pass
+
+        // The color matrix holds the weights of the offsets from 0 to 15 pixels, the outer color the
+        // offset of one pixel and the radius the largest offset with a weight.
+        vec2 pt = (paintMat * vec3(fpos, 1.0)).xy / extent;
+
+        vec4 color = vec4(0.0);
+
+        for (int i = 0; i < 4; i++) {
+            float offset = float(i * 4);
+            if (offset > radius) break;
+
+            vec4 weights = frag[12 + i];
+
+            color += weights.x * blurTaps(pt, offset);
+            color += weights.y * blurTaps(pt, offset + 1.0);
+            color += weights.z * blurTaps(pt, offset + 2.0);
+            color += weights.w * blurTaps(pt, offset + 3.0);
+        }
+
+        result = color * innerCol;
     }
 
     if (hasMask == 1) {
@@ -1,16 +1,12 @@
 use super::Params;
 
-const UNIFORMARRAY_SIZE: usize = 12;
+const UNIFORMARRAY_SIZE: usize = 16;
 
 pub struct UniformArray([f32; UNIFORMARRAY_SIZE * 4]);
 
 impl Default for UniformArray {
     fn default() -> Self {
-        Self([
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-            0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        ])
+        Self([0.0; UNIFORMARRAY_SIZE * 4])
     }
 }
 
@@ -78,6 +74,18 @@ impl UniformArray {
     pub fn set_has_mask(&mut self, has_mask: f32) {
         self.0[44] = has_mask;
     }
+
+    pub fn set_spread_mode(&mut self, spread_mode: f32) {
+        self.0[45] = spread_mode;
+    }
+
+    pub fn set_lookup(&mut self, lookup: f32) {
+        self.0[46] = lookup;
+    }
+
+    pub fn set_color_matrix(&mut self, mat: [f32; 16]) {
+        self.0[48..64].copy_from_slice(&mat);
+    }
 }
 
 impl From<&Params> for UniformArray {
@@ -98,6 +106,9 @@ impl From<&Params> for UniformArray {
         arr.set_shader_type(params.shader_type);
         arr.set_tex_type(params.tex_type);
         arr.set_has_mask(params.has_mask);
+        arr.set_spread_mode(params.spread_mode);
+        arr.set_lookup(params.lookup);
+        arr.set_color_matrix(params.color_matrix);
 
         arr
     }
@@ -1,4 +1,7 @@
-use crate::{Color, ImageFlags, ImageStore, Paint, PaintFlavor, PixelFormat, Scissor, Transform2D};
+use crate::{
+    paint::GradientColors, Color, ImageFlags, ImageStore, Paint, PaintFlavor, PixelFormat, Scissor, SpreadMode,
+    Transform2D,
+};
 
 use super::ShaderType;
 #[repr(C)]
@@ -18,8 +21,11 @@ pub struct Params {
     pub(crate) tex_type: f32,
     pub(crate) shader_type: f32,
     pub(crate) has_mask: f32,
+    pub(crate) spread_mode: f32,
+    pub(crate) color_matrix: [f32; 16],
+    pub(crate) lookup: f32,
     /// needed on macos because uniforms need to be aligned to 256 bytes
-    pub(crate) padding: [f32; 19],
+    pub(crate) padding: [f32; 1],
 }
 
 const_assert!(std::mem::size_of::<Params>() == 256);
@@ -137,8 +143,7 @@ impl Params {
                 start_y,
                 end_x,
                 end_y,
-                start_color,
-                end_color,
+                colors,
             } => {
                 let large = 1e5f32;
                 let mut dx = end_x - start_x;
@@ -163,9 +168,8 @@ impl Params {
                 params.extent[1] = large + d * 0.5;
                 params.feather = 1.0f32.max(d);
 
-                params.inner_col = start_color.premultiplied().to_array();
-                params.outer_col = end_color.premultiplied().to_array();
-                params.shader_type = ShaderType::FillGradient.to_f32();
+                params.set_gradient_colors(&colors);
+                params.spread_mode = paint.spread_mode.to_f32();
             }
             PaintFlavor::BoxGradient {
                 x,
@@ -174,8 +178,7 @@ impl Params {
                 height,
                 radius,
                 feather,
-                inner_color,
-                outer_color,
+                colors,
             } => {
                 let mut transform = Transform2D::new_translation(x + width * 0.5, y + height * 0.5);
                 transform.multiply(&paint.transform);
@@ -185,17 +188,14 @@ impl Params {
                 params.extent[1] = height * 0.5;
                 params.radius = radius;
                 params.feather = feather;
-                params.inner_col = inner_color.premultiplied().to_array();
-                params.outer_col = outer_color.premultiplied().to_array();
-                params.shader_type = ShaderType::FillGradient.to_f32();
+                params.set_gradient_colors(&colors);
             }
             PaintFlavor::RadialGradient {
                 cx,
                 cy,
                 in_radius,
                 out_radius,
-                inner_color,
-                outer_color,
+                colors,
             } => {
                 let r = (in_radius + out_radius) * 0.5;
                 let f = out_radius - in_radius;
@@ -208,9 +208,97 @@ impl Params {
                 params.extent[1] = r;
                 params.radius = r;
                 params.feather = 1.0f32.max(f);
-                params.inner_col = inner_color.premultiplied().to_array();
-                params.outer_col = outer_color.premultiplied().to_array();
-                params.shader_type = ShaderType::FillGradient.to_f32();
+                params.set_gradient_colors(&colors);
+                params.spread_mode = paint.spread_mode.to_f32();
+            }
+            PaintFlavor::ConicGradient {
+                cx, cy, start_angle, ..
+            } => {
+                let mut transform = Transform2D::identity();
+                transform.rotate(start_angle);
+                transform.translate(cx, cy);
+                transform.multiply(&paint.transform);
+                inv_transform = transform.inversed();
+
+                // Conic gradients always sample the color ramp
+                params.inner_col = Color::white().to_array();
+                params.outer_col = Color::white().to_array();
+                params.shader_type = ShaderType::FillConicGradient.to_f32();
+            }
+            PaintFlavor::FilterImage {
+                id,
+                width,
+                height,
+                matrix,
+            } => {
+                // Sampled like an image paint, then filtered
+                let image = Paint {
+                    flavor: PaintFlavor::Image {
+                        id,
+                        cx: 0.0,
+                        cy: 0.0,
+                        width,
+                        height,
+                        angle: 0.0,
+                        alpha: 1.0,
+                    },
+                    ..*paint
+                };
+
+                let mut params = Self::new(images, &image, scissor, stroke_width, fringe_width, stroke_thr);
+                params.shader_type = ShaderType::FilterImage.to_f32();
+
+                for column in 0..4 {
+                    for row in 0..4 {
+                        params.color_matrix[column * 4 + row] = matrix[row * 5 + column];
+                    }
+                }
+
+                params.outer_col = [matrix[4], matrix[9], matrix[14], matrix[19]];
+
+                // The lookup table is bound in place of the alpha mask
+                params.lookup = params.has_mask;
+                params.has_mask = 0.0;
+
+                return params;
+            }
+            PaintFlavor::BlurImage {
+                id,
+                width,
+                height,
+                horizontal,
+                kernel,
+            } => {
+                // Sampled like an image paint, at offsets along the direction of the pass
+                let image = Paint {
+                    flavor: PaintFlavor::Image {
+                        id,
+                        cx: 0.0,
+                        cy: 0.0,
+                        width,
+                        height,
+                        angle: 0.0,
+                        alpha: 1.0,
+                    },
+                    ..*paint
+                };
+
+                let mut params = Self::new(images, &image, scissor, stroke_width, fringe_width, stroke_thr);
+                params.shader_type = ShaderType::BlurImage.to_f32();
+
+                // The shaders add up the texels on both sides of every offset, so offset 0 is halved
+                params.color_matrix = kernel;
+                params.color_matrix[0] *= 0.5;
+                params.radius = kernel.iter().rposition(|weight| *weight != 0.0).unwrap_or(0) as f32;
+
+                // One pixel along the direction of the pass in texture coordinates
+                params.outer_col = if horizontal {
+                    [1.0 / width, 0.0, 0.0, 0.0]
+                } else {
+                    [0.0, 1.0 / height, 0.0, 0.0]
+                };
+
+                return params;
             }
         }
 
@@ -218,4 +306,111 @@ impl Params {
 
         params
     }
+
+    // Two stop gradients are interpolated in the shader, multi stop gradients are looked up
+    // from a color ramp texture which the canvas binds as the command image.
+    fn set_gradient_colors(&mut self, colors: &GradientColors) {
+        match colors {
+            GradientColors::TwoStop { start_color, end_color } => {
+                self.inner_col = start_color.premultiplied().to_array();
+                self.outer_col = end_color.premultiplied().to_array();
+                self.shader_type = ShaderType::FillGradient.to_f32();
+            }
+            GradientColors::MultiStop { .. } => {
+                self.inner_col = Color::white().to_array();
+                self.outer_col = Color::white().to_array();
+                self.shader_type = ShaderType::FillImageGradient.to_f32();
+            }
+        }
+    }
+
+    /// Inverse of the scissor transform as a column major 3x4 matrix.
+    pub fn scissor_mat(&self) -> [f32; 12] {
+        self.scissor_mat
+    }
+
+    /// Inverse of the paint transform as a column major 3x4 matrix.
+    pub fn paint_mat(&self) -> [f32; 12] {
+        self.paint_mat
+    }
+
+    /// Premultiplied inner (or solid) color of the paint.
+    pub fn inner_color(&self) -> Color {
+        Color::rgbaf(
+            self.inner_col[0],
+            self.inner_col[1],
+            self.inner_col[2],
+            self.inner_col[3],
+        )
+    }
+
+    /// Premultiplied outer color of the paint.
+    pub fn outer_color(&self) -> Color {
+        Color::rgbaf(
+            self.outer_col[0],
+            self.outer_col[1],
+            self.outer_col[2],
+            self.outer_col[3],
+        )
+    }
+
+    pub fn scissor_extent(&self) -> [f32; 2] {
+        self.scissor_ext
+    }
+
+    pub fn scissor_scale(&self) -> [f32; 2] {
+        self.scissor_scale
+    }
+
+    pub fn extent(&self) -> [f32; 2] {
+        self.extent
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn feather(&self) -> f32 {
+        self.feather
+    }
+
+    pub fn stroke_mult(&self) -> f32 {
+        self.stroke_mult
+    }
+
+    pub fn stroke_thr(&self) -> f32 {
+        self.stroke_thr
+    }
+
+    pub fn tex_type(&self) -> f32 {
+        self.tex_type
+    }
+
+    pub fn shader_type(&self) -> ShaderType {
+        ShaderType::from_f32(self.shader_type)
+    }
+
+    pub fn has_mask(&self) -> bool {
+        self.has_mask == 1.0
+    }
+
+    pub fn spread_mode(&self) -> SpreadMode {
+        SpreadMode::from_f32(self.spread_mode)
+    }
+
+    /// Color matrix of image filters as a column major 4x4 matrix, the outer color holds the offsets.
+    pub fn color_matrix(&self) -> [f32; 16] {
+        self.color_matrix
+    }
+
+    /// Weights of the offsets from 0 to 15 pixels of a blur pass, the one of 0 is halved since both sides
+    /// of every offset are added up. The outer color holds the offset of one pixel in texture coordinates.
+    pub fn blur_kernel(&self) -> [f32; 16] {
+        self.color_matrix
+    }
+
+    /// Whether image filters look colors up in the table bound as the alpha mask.
+    pub fn has_lookup(&self) -> bool {
+        self.lookup == 1.0
+    }
 }
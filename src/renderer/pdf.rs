@@ -0,0 +1,1138 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use fnv::FnvHashSet;
+use imgref::ImgVec;
+use owned_ttf_parser::{Font as TtfFont, GlyphId, TableName};
+use rgb::RGBA8;
+
+use crate::geometry::Transform2D;
+use crate::{
+    Color, CompositeOperation, ErrorKind, FillRule, ImageFlags, ImageId, ImageInfo, ImageSource, ImageStore, LineCap,
+    LineJoin, Paint, Path, Size, Verb,
+};
+
+use super::vector::{self, Fill, Gradient, GradientShape};
+use super::{Command, CommandType, RenderTarget, Renderer, SoftwareImage, VectorGlyph, Vertex, VoidCache};
+
+mod subset;
+
+// Object numbers of the fixed objects at the start of the document. Every content stream shares the one
+// resource dictionary.
+const CATALOG: usize = 1;
+const PAGES: usize = 2;
+const PAGE: usize = 3;
+const RESOURCES: usize = 4;
+const CONTENTS: usize = 5;
+
+// Non repeating image patterns are made large enough to never tile within the page.
+const PATTERN_EXTENT: f32 = 1e6;
+
+// ToUnicode CMaps list at most 100 mappings per block.
+const CMAP_BLOCK_LEN: usize = 100;
+
+/// Renderer that writes a single page PDF document instead of pixels.
+///
+/// Works like the [SvgExport](struct.SvgExport.html) renderer: fills and strokes become path operators in
+/// device pixels with their curves, fill rules, widths, caps, joins and dashes kept. Linear and radial
+/// gradients become shading patterns and images are embedded as image XObjects. One device pixel is one
+/// point at a device pixel ratio of 1.
+///
+/// Filled text shows the glyphs of its fonts, which are embedded as TrueType subsets with a ToUnicode map so
+/// the text can be searched and copied. Fonts with CFF outlines, glyphs at variation axis values and stroked
+/// text are drawn as glyph outlines instead.
+///
+/// PDF shadings have no alpha, gradients with stops of differing opacity are drawn through a soft mask of
+/// their alpha. Shadings can't repeat, gradients are extended with their end colors. Box and conic gradients
+/// have no PDF equivalent, the pixels they cover are embedded as an image.
+///
+/// Drawing with the source over, destination over, copy and lighter composite operations is exported, lighter
+/// as the screen blend mode, the closest one PDF has. The other operations make [document](#method.document)
+/// return an error.
+///
+/// ```
+/// use gpucanvas::{renderer::Pdf, Canvas, Color, Paint, Path};
+///
+/// let mut canvas = Canvas::new(Pdf::new()).unwrap();
+/// canvas.set_size(100, 100, 1.0);
+///
+/// let mut path = Path::new();
+/// path.rect(10.0, 10.0, 50.0, 50.0);
+/// canvas.fill_path(&mut path, Paint::color(Color::black()));
+/// canvas.flush();
+///
+/// assert!(canvas.renderer_mut().document().unwrap().starts_with(b"%PDF"));
+/// ```
+#[derive(Default)]
+pub struct Pdf {
+    size: Size,
+    dpi: f32,
+    document: Vec<u8>,
+    next_id: usize,
+    // Fonts of the glyphs drawn so far, render targets keep showing them in later documents
+    fonts: Vec<PdfFont>,
+    // Set when the last flush drew with a composite operation that can't be exported
+    unsupported: bool,
+}
+
+impl Pdf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The document written by the last `Canvas::flush`.
+    ///
+    /// Returns `ErrorKind::UnsupportedCompositeOperation` if anything was drawn with a composite operation PDF
+    /// can't express, the document then leaves out what was drawn with it.
+    pub fn document(&self) -> Result<&[u8], ErrorKind> {
+        if self.unsupported {
+            Err(ErrorKind::UnsupportedCompositeOperation)
+        } else {
+            Ok(&self.document)
+        }
+    }
+
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{}{}", prefix, self.next_id)
+    }
+
+    fn draw(&mut self, content: &mut Content, images: &ImageStore<PdfImage>, size: Size, cmd: &Command) {
+        let (params, shape) = match vector::shape(cmd) {
+            Some(shape) => shape,
+            None => return,
+        };
+
+        let operation = vector::composite_operation(cmd.composite_operation());
+
+        match operation {
+            Some(CompositeOperation::SourceOver)
+            | Some(CompositeOperation::DestinationOver)
+            | Some(CompositeOperation::Copy)
+            | Some(CompositeOperation::Lighter) => (),
+            _ => {
+                self.unsupported = true;
+                return;
+            }
+        }
+
+        // PDF strokes have the same cap at both ends, other strokes are filled as outlines
+        let outline;
+
+        let (path, stroke) = if shape.stroke && shape.paint.line_cap_start != shape.paint.line_cap_end {
+            outline = shape.path.stroke_outline(&shape.paint);
+            (&outline, false)
+        } else {
+            (&shape.path, shape.stroke)
+        };
+
+        // Operators setting the stroke color are the uppercase ones of the fill color
+        let pattern_op = |pattern: &str| {
+            if stroke {
+                format!("/Pattern CS /{} SCN", pattern)
+            } else {
+                format!("/Pattern cs /{} scn", pattern)
+            }
+        };
+
+        let image = |id| images.get(id).map(|image: &PdfImage| &image.pixels);
+
+        let (color_op, alpha, mask) = match vector::fill(params, cmd, shape, size, image) {
+            Some(Fill::Color(color)) => (
+                format!(
+                    "{} {} {} {}",
+                    color.r,
+                    color.g,
+                    color.b,
+                    if stroke { "RG" } else { "rg" }
+                ),
+                color.a,
+                None,
+            ),
+            Some(Fill::Gradient(gradient)) => {
+                let pattern = self.shading(content, &gradient);
+                let alpha = gradient.stops[0].1.a;
+
+                // Shadings have no alpha, differing stop opacities are drawn as a soft mask
+                if gradient.stops.iter().all(|(_, color)| color.a == alpha) {
+                    (pattern_op(&pattern), alpha, None)
+                } else {
+                    let mask = self.alpha_mask(content, &gradient, size);
+                    (pattern_op(&pattern), 1.0, Some(mask))
+                }
+            }
+            Some(Fill::Image {
+                id,
+                transform,
+                width,
+                height,
+                alpha,
+                tex_type,
+            }) => match self.pattern(content, images, id, transform, width, height, tex_type) {
+                Some(pattern) => (pattern_op(&pattern), alpha, None),
+                None => return,
+            },
+            Some(Fill::Pixels { x, y, pixels }) => {
+                let pattern = self.pixels_pattern(content, x, y, &pixels);
+
+                (pattern_op(&pattern), 1.0, None)
+            }
+            None => return,
+        };
+
+        let mut clip = String::new();
+
+        if let Some((transform, [ex, ey])) = vector::scissor(params) {
+            let _ = writeln!(
+                clip,
+                "{} cm {} {} {} {} re W n {} cm",
+                matrix(&transform),
+                -ex,
+                -ey,
+                ex * 2.0,
+                ey * 2.0,
+                matrix(&transform.inversed())
+            );
+        }
+
+        // The contours of the clip area are marked solid or hole
+        if let Some(area) = &shape.clip {
+            write_path(&mut clip, &area.transformed(&Transform2D::identity(), true));
+            clip.push_str("W n\n");
+        }
+
+        let mut paint_ops = color_op;
+        paint_ops.push('\n');
+
+        set_opacity(&mut content.resources, &mut paint_ops, alpha);
+
+        if let Some(mask) = mask {
+            let _ = writeln!(paint_ops, "/{} gs", mask);
+        }
+
+        if operation == Some(CompositeOperation::Lighter) {
+            define(&mut content.resources, "ExtGState", "GSScreen", || {
+                "<< /BM /Screen >>".to_string()
+            });
+            paint_ops.push_str("/GSScreen gs\n");
+        }
+
+        let glyph = match &shape.glyph {
+            Some(glyph) if !stroke => self.font(content, glyph).map(|font| (glyph, font)),
+            _ => None,
+        };
+
+        let mut shape_ops = String::new();
+
+        if let Some((glyph, (font, units_per_em))) = glyph {
+            // Text space has a unit of one em
+            let mut text_matrix = Transform2D::identity();
+            text_matrix.scale(units_per_em, units_per_em);
+            text_matrix.multiply(&glyph.transform);
+
+            let _ = writeln!(
+                shape_ops,
+                "BT /{} 1 Tf {} Tm <{:04X}> Tj ET",
+                font,
+                matrix(&text_matrix),
+                glyph.id
+            );
+        } else if stroke {
+            shape_ops.push_str(&stroke_state(&shape.paint));
+            write_path(&mut shape_ops, path);
+            shape_ops.push_str("S\n");
+        } else {
+            write_path(&mut shape_ops, path);
+
+            shape_ops.push_str(match shape.paint.fill_rule {
+                FillRule::EvenOdd if !shape.stroke => "f*\n",
+                _ => "f\n",
+            });
+        }
+
+        let ops = format!("q\n{}{}{}Q\n", clip, paint_ops, shape_ops);
+
+        match operation {
+            Some(CompositeOperation::DestinationOver) => content.ops.insert_str(0, &ops),
+            Some(CompositeOperation::Copy) if !content.ops.is_empty() => {
+                // What was drawn before is masked out where the shape is, then the shape is drawn over it
+                let cutout = format!(
+                    "1 g 0 0 {} {} re f\nq\n{}0 g 0 G\n{}Q\n",
+                    size.w, size.h, clip, shape_ops
+                );
+                let mask = self.soft_mask(content, size, &format!("{} 0 R", RESOURCES), cutout);
+
+                let form = self.next_id("Fm");
+
+                content.resources.push(Resource::new(
+                    "XObject",
+                    form.clone(),
+                    format!(
+                        "<< /Type /XObject /Subtype /Form /BBox [0 0 {} {}] /Resources {} 0 R",
+                        size.w, size.h, RESOURCES
+                    ),
+                    Some(std::mem::take(&mut content.ops).into_bytes()),
+                ));
+
+                content.ops = format!("q\n/{} gs\n/{} Do\nQ\n{}", mask, form, ops);
+            }
+            _ => content.ops.push_str(&ops),
+        }
+    }
+
+    // Returns the name and the units per em of the embedded font showing the glyph, None if the glyph is to be
+    // drawn as an outline.
+    fn font(&mut self, content: &mut Content, glyph: &VectorGlyph) -> Option<(String, f32)> {
+        let index = match self.fonts.iter().position(|font| Arc::ptr_eq(&font.data, &glyph.font)) {
+            Some(index) => index,
+            None => {
+                // Only TrueType outlines are subset, fonts with CFF outlines are not embedded
+                let units_per_em = TtfFont::from_data(&glyph.font, 0)
+                    .filter(|font| font.has_table(TableName::GlyphData))
+                    .and_then(|font| font.units_per_em());
+
+                self.fonts.push(PdfFont {
+                    data: glyph.font.clone(),
+                    name: format!("F{}", self.fonts.len() + 1),
+                    units_per_em,
+                    glyphs: BTreeMap::new(),
+                });
+
+                self.fonts.len() - 1
+            }
+        };
+
+        let font = &mut self.fonts[index];
+        let units_per_em = font.units_per_em? as f32;
+
+        font.glyphs.insert(glyph.id, glyph.c);
+
+        if !content.fonts.contains(&index) {
+            content.fonts.push(index);
+        }
+
+        Some((font.name.clone(), units_per_em))
+    }
+
+    // Defines a shading pattern for the gradient and returns its name.
+    fn shading(&mut self, content: &mut Content, gradient: &Gradient) -> String {
+        let name = self.next_id("P");
+
+        let dict = format!(
+            "<< /PatternType 2 /Matrix [{}] /Shading {} >>",
+            matrix(&content.pattern_transform(&gradient.transform)),
+            shading_dict(gradient, false)
+        );
+
+        content
+            .resources
+            .push(Resource::new("Pattern", name.clone(), dict, None));
+
+        name
+    }
+
+    // Defines a graphics state with a soft mask of the alpha of the gradient and returns its name.
+    fn alpha_mask(&mut self, content: &mut Content, gradient: &Gradient, size: Size) -> String {
+        // The mask is in the space the graphics state is set in, which is device space
+        let resources = format!("<< /Shading << /Sh0 {} >> >>", shading_dict(gradient, true));
+        let ops = format!("q {} cm /Sh0 sh Q\n", matrix(&gradient.transform));
+
+        self.soft_mask(content, size, &resources, ops)
+    }
+
+    // Defines a graphics state with a luminosity soft mask drawn by the operators over the target of the size
+    // and returns its name.
+    fn soft_mask(&mut self, content: &mut Content, size: Size, resources: &str, ops: String) -> String {
+        let name = self.next_id("SM");
+
+        let mut resource = Resource::new(
+            "ExtGState",
+            name.clone(),
+            "<< /SMask << /Type /Mask /S /Luminosity /G {+1} >> >>".to_string(),
+            None,
+        );
+
+        resource.objects.push((
+            format!(
+                "<< /Type /XObject /Subtype /Form /BBox [0 0 {} {}] /Group << /S /Transparency /CS /DeviceGray >> \
+                 /Resources {}",
+                size.w, size.h, resources
+            ),
+            Some(ops.into_bytes()),
+        ));
+
+        content.resources.push(resource);
+
+        name
+    }
+
+    // Defines a tiling pattern showing the image scaled to width and height and returns its name.
+    #[allow(clippy::too_many_arguments)]
+    fn pattern(
+        &mut self,
+        content: &mut Content,
+        images: &ImageStore<PdfImage>,
+        id: ImageId,
+        mut transform: Transform2D,
+        width: f32,
+        height: f32,
+        tex_type: f32,
+    ) -> Option<String> {
+        let image = images.get(id)?;
+        let info = image.pixels.info();
+        let flags = info.flags();
+
+        // Images that were render targets are shown with what was drawn to them, as form XObjects
+        let paint_ops = if image.content.borrow().is_some() {
+            if flags.contains(ImageFlags::FLIP_Y) {
+                transform = vector::unflip(&transform, height);
+            }
+
+            content.images.push(id);
+
+            format!(
+                "{} 0 0 {} 0 0 cm /Fm{} Do",
+                width / info.width() as f32,
+                height / info.height() as f32,
+                image.id
+            )
+        } else {
+            let name = format!("Im{}", image.id);
+
+            if !content.resources.iter().any(|resource| resource.name == name) {
+                let pixels = image.pixels.pixels.borrow().to_img(false);
+                content.resources.push(image_resource(&name, &pixels, tex_type));
+            }
+
+            // Image space has its first row at the top of the unit square
+            format!("{} 0 0 {} 0 {} cm /{} Do", width, -height, height, name)
+        };
+
+        let x_step = if flags.contains(ImageFlags::REPEAT_X) {
+            width
+        } else {
+            PATTERN_EXTENT
+        };
+
+        let y_step = if flags.contains(ImageFlags::REPEAT_Y) {
+            height
+        } else {
+            PATTERN_EXTENT
+        };
+
+        let name = self.next_id("P");
+
+        let dict = format!(
+            "<< /Type /Pattern /PatternType 1 /PaintType 1 /TilingType 1 /BBox [0 0 {} {}] /XStep {} /YStep {} \
+             /Matrix [{}] /Resources {} 0 R",
+            width,
+            height,
+            x_step,
+            y_step,
+            matrix(&content.pattern_transform(&transform)),
+            RESOURCES
+        );
+
+        content.resources.push(Resource::new(
+            "Pattern",
+            name.clone(),
+            dict,
+            Some(paint_ops.into_bytes()),
+        ));
+
+        Some(name)
+    }
+
+    // Defines a tiling pattern showing premultiplied device pixels at x, y and returns its name.
+    fn pixels_pattern(&mut self, content: &mut Content, x: usize, y: usize, pixels: &ImgVec<RGBA8>) -> String {
+        let (width, height) = (pixels.width(), pixels.height());
+
+        let image = self.next_id("Im");
+        content.resources.push(image_resource(&image, pixels, 0.0));
+
+        let name = self.next_id("P");
+
+        let dict = format!(
+            "<< /Type /Pattern /PatternType 1 /PaintType 1 /TilingType 1 /BBox [0 0 {} {}] /XStep {} /YStep {} \
+             /Matrix [{}] /Resources {} 0 R",
+            width,
+            height,
+            width,
+            height,
+            matrix(&content.pattern_transform(&Transform2D::new_translation(x as f32, y as f32))),
+            RESOURCES
+        );
+
+        // Image space has its first row at the top of the unit square
+        let paint_ops = format!("{} 0 0 {} 0 {} cm /{} Do", width, -(height as f32), height, image);
+
+        content.resources.push(Resource::new(
+            "Pattern",
+            name.clone(),
+            dict,
+            Some(paint_ops.into_bytes()),
+        ));
+
+        name
+    }
+}
+
+impl Renderer for Pdf {
+    type Image = PdfImage;
+    type BufferCache = VoidCache;
+
+    fn alloc_buffer_cache(&self) -> Self::BufferCache {
+        Self::BufferCache::new()
+    }
+
+    fn view_size(&self) -> Size {
+        self.size
+    }
+
+    fn set_size(&mut self, width: u32, height: u32, dpi: f32) {
+        self.size = Size::new(width as f32, height as f32);
+        self.dpi = dpi;
+    }
+
+    fn render(
+        &mut self,
+        images: &ImageStore<PdfImage>,
+        _cache: &mut Self::BufferCache,
+        _verts: &[Vertex],
+        commands: &[Command],
+    ) {
+        self.unsupported = false;
+
+        let scale = if self.dpi > 0.0 { 1.0 / self.dpi } else { 1.0 };
+
+        // The page is drawn in device pixels with the y axis pointing down
+        let page_transform = Transform2D([scale, 0.0, 0.0, -scale, 0.0, self.size.h * scale]);
+
+        let mut screen = Content::new(page_transform);
+        let mut target = RenderTarget::Screen;
+
+        for cmd in commands {
+            if let CommandType::SetRenderTarget(new_target) = cmd.cmd_type() {
+                target = *new_target;
+                continue;
+            }
+
+            let mut image_content;
+
+            let (content, size) = match target {
+                RenderTarget::Image(id) => match images.get(id) {
+                    Some(image) => {
+                        image_content = image.content.borrow_mut();
+                        let info = image.pixels.info();
+
+                        (
+                            image_content.get_or_insert_with(|| Content::new(Transform2D::identity())),
+                            Size::new(info.width() as f32, info.height() as f32),
+                        )
+                    }
+                    None => continue,
+                },
+                _ => (&mut screen, self.size),
+            };
+
+            if let CommandType::ClearRect {
+                x,
+                y,
+                width,
+                height,
+                color,
+            } = *cmd.cmd_type()
+            {
+                // Clearing everything discards what was drawn before
+                if x == 0 && y == 0 && width as f32 >= size.w && height as f32 >= size.h {
+                    *content = Content::new(content.base);
+                }
+
+                if color.a > 0.0 {
+                    content.ops.push_str("q\n");
+                    set_opacity(&mut content.resources, &mut content.ops, color.a);
+
+                    let _ = writeln!(
+                        content.ops,
+                        "{} {} {} rg {} {} {} {} re f Q",
+                        color.r, color.g, color.b, x, y, width, height
+                    );
+                }
+
+                continue;
+            }
+
+            self.draw(content, images, size, cmd);
+        }
+
+        // Render targets shown by patterns become form XObjects, including the ones they show themselves
+        let mut resources = Vec::new();
+        let mut defined = FnvHashSet::default();
+        let mut pending = screen.images.clone();
+        let mut fonts = screen.fonts.clone();
+
+        while let Some(id) = pending.pop() {
+            if !defined.insert(id) {
+                continue;
+            }
+
+            if let Some(image) = images.get(id) {
+                if let Some(content) = &*image.content.borrow() {
+                    let info = image.pixels.info();
+
+                    resources.extend(content.resources.iter().cloned());
+                    resources.push(Resource::new(
+                        "XObject",
+                        format!("Fm{}", image.id),
+                        format!(
+                            "<< /Type /XObject /Subtype /Form /BBox [0 0 {} {}] /Resources {} 0 R",
+                            info.width(),
+                            info.height(),
+                            RESOURCES
+                        ),
+                        Some(content.ops.clone().into_bytes()),
+                    ));
+
+                    pending.extend_from_slice(&content.images);
+                    fonts.extend_from_slice(&content.fonts);
+                }
+            }
+        }
+
+        resources.extend(screen.resources.iter().cloned());
+
+        // Render targets can show glyphs drawn in earlier flushes, fonts are subset to every glyph drawn so far
+        fonts.sort_unstable();
+        fonts.dedup();
+
+        for (index, font) in fonts.into_iter().map(|index| (index, &self.fonts[index])) {
+            if let Some(resource) = font_resource(font, index) {
+                resources.push(resource);
+            }
+        }
+
+        let mut names = FnvHashSet::default();
+        resources.retain(|resource| names.insert(resource.name.clone()));
+
+        let mut page_ops = format!("{} cm\n", matrix(&page_transform));
+        page_ops.push_str(&screen.ops);
+
+        self.document = write_document(
+            self.size.w * scale,
+            self.size.h * scale,
+            page_ops.as_bytes(),
+            &resources,
+        );
+    }
+
+    fn alloc_image(&mut self, info: ImageInfo) -> Result<Self::Image, ErrorKind> {
+        self.next_id += 1;
+
+        Ok(PdfImage {
+            id: self.next_id,
+            pixels: SoftwareImage::new(info),
+            content: RefCell::new(None),
+        })
+    }
+
+    fn start_capture(&self) {}
+
+    fn stop_capture(&self) {}
+
+    fn label(&self, _images: &ImageStore<Self::Image>, _id: ImageId) -> String {
+        "labels not supported for pdf backend".to_owned()
+    }
+
+    fn set_label(&self, _images: &ImageStore<Self::Image>, _id: ImageId, _label: &str) {}
+
+    fn update_image(
+        &mut self,
+        image: &mut Self::Image,
+        data: ImageSource,
+        x: usize,
+        y: usize,
+    ) -> Result<(), ErrorKind> {
+        // New pixels replace anything that was drawn to the image
+        *image.content.get_mut() = None;
+
+        image.pixels.update(data, x, y)
+    }
+
+    fn delete_image(&mut self, _image: Self::Image) {}
+
+    fn flip_y() -> bool {
+        false
+    }
+
+    fn flip_uv() -> bool {
+        false
+    }
+
+    fn is_vector() -> bool {
+        true
+    }
+
+    fn screenshot(&mut self, _images: &ImageStore<Self::Image>) -> Result<ImgVec<RGBA8>, ErrorKind> {
+        Ok(ImgVec::new(Vec::new(), 0, 0))
+    }
+}
+
+/// Image type of the [Pdf](struct.Pdf.html) renderer.
+pub struct PdfImage {
+    id: usize,
+    pixels: SoftwareImage,
+    // Operators drawn to the image while it was a render target. Used instead of the pixels when present.
+    content: RefCell<Option<Content>>,
+}
+
+// A font of drawn glyphs, embedded when it has TrueType outlines.
+struct PdfFont {
+    data: Arc<[u8]>,
+    name: String,
+    // None when the font can't be embedded
+    units_per_em: Option<u16>,
+    // The drawn glyphs with the characters they show
+    glyphs: BTreeMap<u16, char>,
+}
+
+// Operators drawn to the page or to a render target image, with the resources they use.
+struct Content {
+    ops: String,
+    resources: Vec<Resource>,
+    // Render target images shown by patterns in this content
+    images: Vec<ImageId>,
+    // Indices of the fonts of the glyphs shown in this content
+    fonts: Vec<usize>,
+    // Maps device space to the default space of the content stream, pattern matrices are relative to it
+    base: Transform2D,
+}
+
+impl Content {
+    fn new(base: Transform2D) -> Self {
+        Self {
+            ops: String::new(),
+            resources: Vec::new(),
+            images: Vec::new(),
+            fonts: Vec::new(),
+            base,
+        }
+    }
+
+    fn pattern_transform(&self, transform: &Transform2D) -> Transform2D {
+        let mut transform = *transform;
+        transform.multiply(&self.base);
+        transform
+    }
+}
+
+// Named entry of the shared resource dictionary. The dictionary of stream objects is left open for the length.
+#[derive(Clone)]
+struct Resource {
+    category: &'static str,
+    name: String,
+    dict: String,
+    stream: Option<Vec<u8>>,
+    // Objects written after the resource, with dictionaries and optional streams like the resource. Dictionaries
+    // refer to the first one as {+1}, to the second one as {+2} and so on.
+    objects: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl Resource {
+    fn new(category: &'static str, name: String, dict: String, stream: Option<Vec<u8>>) -> Self {
+        Self {
+            category,
+            name,
+            dict,
+            stream,
+            objects: Vec::new(),
+        }
+    }
+}
+
+fn write_path(ops: &mut String, path: &Path) {
+    for verb in path.verbs() {
+        let _ = match verb {
+            Verb::MoveTo(x, y) => writeln!(ops, "{} {} m", x, y),
+            Verb::LineTo(x, y) => writeln!(ops, "{} {} l", x, y),
+            Verb::BezierTo(c1x, c1y, c2x, c2y, x, y) => writeln!(ops, "{} {} {} {} {} {} c", c1x, c1y, c2x, c2y, x, y),
+            Verb::Close => writeln!(ops, "h"),
+            Verb::Solid | Verb::Hole => Ok(()),
+        };
+    }
+}
+
+// Operators setting the line width, caps, joins and dashes of strokes.
+fn stroke_state(paint: &Paint) -> String {
+    let cap = match paint.line_cap_start {
+        LineCap::Butt => 0,
+        LineCap::Round => 1,
+        LineCap::Square => 2,
+    };
+
+    let join = match paint.line_join {
+        LineJoin::Miter => 0,
+        LineJoin::Round => 1,
+        LineJoin::Bevel => 2,
+    };
+
+    let dashes: Vec<String> = paint.line_dash[..paint.line_dash_len]
+        .iter()
+        .map(|len| len.to_string())
+        .collect();
+
+    format!(
+        "{} w {} J {} j {} M [{}] {} d\n",
+        paint.line_width,
+        cap,
+        join,
+        paint.miter_limit.max(1.0),
+        dashes.join(" "),
+        paint.line_dash_offset
+    )
+}
+
+// Sets the fill and stroke opacity with a graphics state, defining the state if needed.
+fn set_opacity(resources: &mut Vec<Resource>, ops: &mut String, alpha: f32) {
+    if alpha >= 1.0 {
+        return;
+    }
+
+    let name = format!("GS{}", (alpha * 1000.0).round() as u32);
+
+    define(resources, "ExtGState", &name, || {
+        let alpha = (alpha * 1000.0).round() / 1000.0;
+        format!("<< /ca {} /CA {} >>", alpha, alpha)
+    });
+
+    let _ = writeln!(ops, "/{} gs", name);
+}
+
+// Adds a resource without a stream unless one with the name exists.
+fn define(resources: &mut Vec<Resource>, category: &'static str, name: &str, dict: impl FnOnce() -> String) {
+    if !resources.iter().any(|resource| resource.name == name) {
+        resources.push(Resource::new(category, name.to_string(), dict(), None));
+    }
+}
+
+// The shading dictionary of the gradient in gradient space, with the colors of the stops or with their alpha as
+// gray levels.
+fn shading_dict(gradient: &Gradient, alpha: bool) -> String {
+    let (shading_type, coords) = match gradient.shape {
+        GradientShape::Linear { x0, y0, x1, y1 } => (2, format!("{} {} {} {}", x0, y0, x1, y1)),
+        GradientShape::Radial { r0, r1 } => (3, format!("0 0 {} 0 0 {}", r0, r1)),
+    };
+
+    // Stitch the stops together with linear interpolations between neighbours
+    let mut stops = gradient.stops.clone();
+
+    if stops[0].0 > 0.0 {
+        stops.insert(0, (0.0, stops[0].1));
+    }
+
+    if stops[stops.len() - 1].0 < 1.0 {
+        stops.push((1.0, stops[stops.len() - 1].1));
+    }
+
+    let components = |color: &Color| {
+        if alpha {
+            format!("[{}]", color.a)
+        } else {
+            format!("[{} {} {}]", color.r, color.g, color.b)
+        }
+    };
+
+    let mut functions = String::new();
+    let mut bounds = String::new();
+    let mut encode = String::new();
+
+    for (i, pair) in stops.windows(2).enumerate() {
+        let _ = write!(
+            functions,
+            "<< /FunctionType 2 /Domain [0 1] /C0 {} /C1 {} /N 1 >> ",
+            components(&pair[0].1),
+            components(&pair[1].1)
+        );
+
+        if i > 0 {
+            let _ = write!(bounds, "{} ", pair[0].0);
+        }
+
+        encode.push_str("0 1 ");
+    }
+
+    format!(
+        "<< /ShadingType {} /ColorSpace {} /Coords [{}] /Extend [true true] /Function << /FunctionType 3 \
+         /Domain [0 1] /Functions [{}] /Bounds [{}] /Encode [{}] >> >>",
+        shading_type,
+        if alpha { "/DeviceGray" } else { "/DeviceRGB" },
+        coords,
+        functions.trim_end(),
+        bounds.trim_end(),
+        encode.trim_end()
+    )
+}
+
+fn image_resource(name: &str, pixels: &ImgVec<RGBA8>, tex_type: f32) -> Resource {
+    let mut rgb = Vec::with_capacity(pixels.buf().len() * 3);
+    let mut alpha = Vec::with_capacity(pixels.buf().len());
+
+    for px in vector::straight_pixels(pixels.buf(), tex_type) {
+        rgb.extend_from_slice(&[px.r, px.g, px.b]);
+        alpha.push(px.a);
+    }
+
+    let dict = format!(
+        "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8",
+        pixels.width(),
+        pixels.height()
+    );
+
+    if alpha.iter().all(|a| *a == 255) {
+        return Resource::new("XObject", name.to_string(), dict, Some(rgb));
+    }
+
+    // The alpha channel is a separate image of the same size, its dictionary only differs in the color space
+    let mask_dict = dict.replace("/DeviceRGB", "/DeviceGray");
+
+    let mut resource = Resource::new(
+        "XObject",
+        name.to_string(),
+        format!("{} /SMask {{+1}}", dict),
+        Some(rgb),
+    );
+    resource.objects.push((mask_dict, Some(alpha)));
+    resource
+}
+
+// The Type 0 font showing glyphs of the font by their glyph ids, with the subset of the font file embedded.
+// Returns None if the font can't be subset.
+fn font_resource(font: &PdfFont, index: usize) -> Option<Resource> {
+    let face = TtfFont::from_data(&font.data, 0)?;
+    let file = subset::subset(&font.data, font.glyphs.keys().copied())?;
+    let bbox = subset::bounding_box(&font.data)?;
+
+    // Glyph space has 1000 units per em
+    let scale = 1000.0 / font.units_per_em? as f32;
+    let units = |value: f32| (value * scale).round();
+
+    // Subsets are named with a tag of six uppercase letters
+    let tag: String = (0..6)
+        .map(|i| (b'A' + (index / 26usize.pow(i) % 26) as u8) as char)
+        .collect();
+
+    let name: String = face
+        .post_script_name()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_graphic() && !"()<>[]{}/%#".contains(*c))
+        .collect();
+
+    let base_font = format!("{}+{}", tag, if name.is_empty() { "Font" } else { &name });
+
+    let mut widths = String::new();
+
+    for id in font.glyphs.keys() {
+        let advance = face.glyph_hor_advance(GlyphId(*id)).unwrap_or(0);
+        let _ = write!(widths, "{} [{}] ", id, units(advance as f32));
+    }
+
+    let mut resource = Resource::new(
+        "Font",
+        font.name.clone(),
+        format!(
+            "<< /Type /Font /Subtype /Type0 /BaseFont /{} /Encoding /Identity-H /DescendantFonts [{{+1}}] \
+             /ToUnicode {{+4}} >>",
+            base_font
+        ),
+        None,
+    );
+
+    resource.objects.push((
+        format!(
+            "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /{} /CIDSystemInfo << /Registry (Adobe) \
+             /Ordering (Identity) /Supplement 0 >> /FontDescriptor {{+2}} /CIDToGIDMap /Identity /W [{}] >>",
+            base_font,
+            widths.trim_end()
+        ),
+        None,
+    ));
+
+    resource.objects.push((
+        format!(
+            "<< /Type /FontDescriptor /FontName /{} /Flags 4 /FontBBox [{} {} {} {}] /ItalicAngle 0 /Ascent {} \
+             /Descent {} /CapHeight {} /StemV 80 /FontFile2 {{+3}} >>",
+            base_font,
+            units(bbox[0] as f32),
+            units(bbox[1] as f32),
+            units(bbox[2] as f32),
+            units(bbox[3] as f32),
+            units(face.ascender() as f32),
+            units(face.descender() as f32),
+            units(face.ascender() as f32)
+        ),
+        None,
+    ));
+
+    resource
+        .objects
+        .push((format!("<< /Length1 {}", file.len()), Some(file)));
+
+    resource
+        .objects
+        .push(("<<".to_string(), Some(to_unicode(&font.glyphs).into_bytes())));
+
+    Some(resource)
+}
+
+// The CMap mapping glyph ids to the characters they show, for searching and copying text.
+fn to_unicode(glyphs: &BTreeMap<u16, char>) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n/CIDSystemInfo << /Registry (Adobe) \
+         /Ordering (UCS) /Supplement 0 >> def\n/CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+
+    let glyphs: Vec<_> = glyphs.iter().collect();
+
+    for block in glyphs.chunks(CMAP_BLOCK_LEN) {
+        let _ = writeln!(cmap, "{} beginbfchar", block.len());
+
+        for (id, c) in block {
+            // Characters outside the basic multilingual plane take a surrogate pair
+            let utf16: String = c
+                .encode_utf16(&mut [0; 2])
+                .iter()
+                .map(|unit| format!("{:04X}", unit))
+                .collect();
+            let _ = writeln!(cmap, "<{:04X}> <{}>", id, utf16);
+        }
+
+        cmap.push_str("endbfchar\n");
+    }
+
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend");
+    cmap
+}
+
+fn matrix(t: &Transform2D) -> String {
+    format!("{} {} {} {} {} {}", t[0], t[1], t[2], t[3], t[4], t[5])
+}
+
+// Writes the document with a single page showing the content stream.
+fn write_document(width: f32, height: f32, page_ops: &[u8], resources: &[Resource]) -> Vec<u8> {
+    let mut writer = Writer::default();
+
+    writer.buf.extend_from_slice(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n");
+
+    // Resources are numbered after the fixed objects, followed by the objects they refer to
+    let mut numbers = Vec::with_capacity(resources.len());
+    let mut next = CONTENTS + 1;
+
+    for resource in resources {
+        numbers.push(next);
+        next += 1 + resource.objects.len();
+    }
+
+    writer.object(CATALOG, format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES).as_bytes());
+    writer.object(
+        PAGES,
+        format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>", PAGE).as_bytes(),
+    );
+    writer.object(
+        PAGE,
+        format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources {} 0 R /Contents {} 0 R >>",
+            PAGES, width, height, RESOURCES, CONTENTS
+        )
+        .as_bytes(),
+    );
+
+    let mut dict = String::from("<<");
+
+    for category in &["ExtGState", "Font", "Pattern", "XObject"] {
+        let _ = write!(dict, " /{} <<", category);
+
+        for (resource, number) in resources.iter().zip(&numbers) {
+            if resource.category == *category {
+                let _ = write!(dict, " /{} {} 0 R", resource.name, number);
+            }
+        }
+
+        dict.push_str(" >>");
+    }
+
+    dict.push_str(" >>");
+
+    writer.object(RESOURCES, dict.as_bytes());
+    writer.stream(CONTENTS, "<<", page_ops);
+
+    for (resource, number) in resources.iter().zip(&numbers) {
+        let objects = std::iter::once((&resource.dict, &resource.stream))
+            .chain(resource.objects.iter().map(|(dict, stream)| (dict, stream)));
+
+        for (i, (dict, stream)) in objects.enumerate() {
+            // Refer to the objects of the resource by their numbers
+            let mut dict = dict.clone();
+
+            for k in 1..=resource.objects.len() {
+                dict = dict.replace(&format!("{{+{}}}", k), &format!("{} 0 R", number + k));
+            }
+
+            match stream {
+                Some(stream) => writer.stream(number + i, &dict, stream),
+                None => writer.object(number + i, dict.as_bytes()),
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+    // Byte offset of each object by object number
+    offsets: Vec<(usize, usize)>,
+}
+
+impl Writer {
+    fn object(&mut self, number: usize, body: &[u8]) {
+        self.offsets.push((number, self.buf.len()));
+        self.buf.extend_from_slice(format!("{} 0 obj\n", number).as_bytes());
+        self.buf.extend_from_slice(body);
+        self.buf.extend_from_slice(b"\nendobj\n");
+    }
+
+    // Writes a stream object, dict is the start of its dictionary without the closing brackets.
+    fn stream(&mut self, number: usize, dict: &str, data: &[u8]) {
+        let mut body = format!("{} /Length {} >>\nstream\n", dict, data.len()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+
+        self.object(number, &body);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.offsets.sort_unstable();
+
+        let xref = self.buf.len();
+        let count = self.offsets.len() + 1;
+
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", count);
+
+        for (_, offset) in &self.offsets {
+            let _ = writeln!(table, "{:010} 00000 n ", offset);
+        }
+
+        let _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            count, CATALOG, xref
+        );
+
+        self.buf.extend_from_slice(table.as_bytes());
+        self.buf
+    }
+}
@@ -0,0 +1,213 @@
+//! Subsetting of TrueType fonts for embedding. Keeps the outlines of the used glyphs and the tables a PDF
+//! reader needs to draw them, glyph ids stay the same so text can refer to the glyphs of the full font.
+
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+
+// Tables a PDF reader uses from an embedded TrueType font, besides the glyph outlines.
+const KEPT_TABLES: [&[u8; 4]; 6] = [b"cvt ", b"fpgm", b"hhea", b"hmtx", b"maxp", b"prep"];
+
+// Flags of the components of composite glyphs.
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+/// Returns a font file with the outlines of the glyphs, the glyphs they are composed of and the .notdef
+/// glyph. Returns None if the font has no TrueType outlines.
+pub(super) fn subset(data: &[u8], glyphs: impl IntoIterator<Item = u16>) -> Option<Vec<u8>> {
+    let tables = tables(data)?;
+
+    let table = |tag: &[u8; 4]| tables.iter().find(|(name, _)| name == tag).map(|(_, table)| *table);
+
+    let head = table(b"head")?;
+    let glyf = table(b"glyf")?;
+    let loca = table(b"loca")?;
+    let num_glyphs = read_u16(table(b"maxp")?, 4)? as usize;
+    let long_offsets = read_u16(head, 50)? == 1;
+
+    let glyph_data = |id: u16| -> Option<&[u8]> {
+        let id = id as usize;
+
+        if id >= num_glyphs {
+            return None;
+        }
+
+        let (start, end) = if long_offsets {
+            (read_u32(loca, id * 4)? as usize, read_u32(loca, id * 4 + 4)? as usize)
+        } else {
+            (
+                read_u16(loca, id * 2)? as usize * 2,
+                read_u16(loca, id * 2 + 2)? as usize * 2,
+            )
+        };
+
+        glyf.get(start..end)
+    };
+
+    // Composite glyphs need the glyphs they are made of
+    let mut kept = BTreeSet::new();
+    let mut pending: Vec<u16> = glyphs.into_iter().chain(Some(0)).collect();
+
+    while let Some(id) = pending.pop() {
+        if (id as usize) < num_glyphs && kept.insert(id) {
+            pending.extend(components(glyph_data(id)?));
+        }
+    }
+
+    // Unused glyphs are left empty, the offsets are written long
+    let mut new_glyf = Vec::new();
+    let mut new_loca = Vec::with_capacity((num_glyphs + 1) * 4);
+
+    for id in 0..num_glyphs as u16 {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+        if kept.contains(&id) {
+            new_glyf.extend_from_slice(glyph_data(id)?);
+
+            while new_glyf.len() % 4 != 0 {
+                new_glyf.push(0);
+            }
+        }
+    }
+
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+    let mut new_head = head.to_vec();
+    // The checksum adjustment covers the whole file, readers don't check it
+    new_head.get_mut(8..12)?.copy_from_slice(&[0; 4]);
+    new_head.get_mut(50..52)?.copy_from_slice(&1u16.to_be_bytes());
+
+    let mut new_tables: Vec<(&[u8; 4], &[u8])> = vec![(b"glyf", &new_glyf), (b"head", &new_head), (b"loca", &new_loca)];
+
+    for tag in &KEPT_TABLES {
+        if let Some(table) = table(tag) {
+            new_tables.push((tag, table));
+        }
+    }
+
+    new_tables.sort_by_key(|(tag, _)| *tag);
+
+    Some(write_font(&new_tables))
+}
+
+/// Returns the bounding box of all glyphs from the head table as x min, y min, x max and y max in font units.
+pub(super) fn bounding_box(data: &[u8]) -> Option<[i16; 4]> {
+    let tables = tables(data)?;
+    let head = tables.iter().find(|(tag, _)| tag == b"head")?.1;
+
+    Some([
+        read_u16(head, 36)? as i16,
+        read_u16(head, 38)? as i16,
+        read_u16(head, 40)? as i16,
+        read_u16(head, 42)? as i16,
+    ])
+}
+
+// Returns the tags and data of the tables of the first font in the file.
+fn tables(data: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    // Collections start with the offsets of their fonts
+    let start = if data.get(0..4)? == b"ttcf" {
+        read_u32(data, 12)? as usize
+    } else {
+        0
+    };
+
+    let num_tables = read_u16(data, start + 4)? as usize;
+
+    (0..num_tables)
+        .map(|i| {
+            let record = start + 12 + i * 16;
+            let tag = data.get(record..record + 4)?.try_into().ok()?;
+            let offset = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+
+            Some((tag, data.get(offset..offset + length)?))
+        })
+        .collect()
+}
+
+// Returns the ids of the glyphs a composite glyph is made of, none for simple glyphs.
+fn components(glyph: &[u8]) -> Vec<u16> {
+    let mut ids = Vec::new();
+
+    // Composite glyphs have a negative number of contours
+    if glyph.len() < 10 || (read_u16(glyph, 0).unwrap_or(0) as i16) >= 0 {
+        return ids;
+    }
+
+    let mut offset = 10;
+
+    while let (Some(flags), Some(id)) = (read_u16(glyph, offset), read_u16(glyph, offset + 2)) {
+        ids.push(id);
+
+        offset += if flags & ARG_1_AND_2_ARE_WORDS != 0 { 8 } else { 6 };
+
+        if flags & WE_HAVE_A_SCALE != 0 {
+            offset += 2;
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            offset += 4;
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            offset += 8;
+        }
+
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+
+    ids
+}
+
+// Writes the table directory followed by the tables, which must be sorted by tag.
+fn write_font(tables: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let num_tables = tables.len() as u16;
+    let entry_selector = 15 - num_tables.leading_zeros() as u16;
+    let search_range: u16 = (1 << entry_selector) * 16;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+    font.extend_from_slice(&num_tables.to_be_bytes());
+    font.extend_from_slice(&search_range.to_be_bytes());
+    font.extend_from_slice(&entry_selector.to_be_bytes());
+    font.extend_from_slice(&(num_tables * 16 - search_range).to_be_bytes());
+
+    let mut offset = 12 + tables.len() * 16;
+
+    for (tag, table) in tables {
+        font.extend_from_slice(*tag);
+        font.extend_from_slice(&checksum(table).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(table.len() as u32).to_be_bytes());
+
+        offset += padded_len(table);
+    }
+
+    for (_, table) in tables {
+        font.extend_from_slice(table);
+        font.resize(font.len() + padded_len(table) - table.len(), 0);
+    }
+
+    font
+}
+
+fn checksum(table: &[u8]) -> u32 {
+    table.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn padded_len(table: &[u8]) -> usize {
+    (table.len() + 3) & !3
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
@@ -0,0 +1,141 @@
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use crate::{ErrorKind, ImageId, ImageInfo, ImageSource, ImageStore, Size};
+
+use super::{void::VoidImage, Command, Renderer, Vertex, Void};
+
+/// Vertices and commands that were passed to the renderer by a single `Canvas::flush`.
+#[derive(Clone, Debug, Default)]
+pub struct RecordedFrame {
+    verts: Vec<Vertex>,
+    commands: Vec<Command>,
+}
+
+impl RecordedFrame {
+    pub fn verts(&self) -> &[Vertex] {
+        &self.verts
+    }
+
+    /// Returns the vertices of a (start, count) range as found in drawables and commands.
+    pub fn verts_in(&self, (start, count): (usize, usize)) -> &[Vertex] {
+        &self.verts[start..start + count]
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+}
+
+/// Renderer that draws nothing but keeps everything the canvas sends to it.
+///
+/// Useful for testing what commands a sequence of canvas calls produces:
+///
+/// ```
+/// use gpucanvas::{renderer::{CommandType, Recorder}, Canvas, Color, Paint, Path};
+///
+/// let mut canvas = Canvas::new(Recorder::new()).unwrap();
+/// canvas.set_size(100, 100, 1.0);
+///
+/// let mut path = Path::new();
+/// path.rect(10.0, 10.0, 50.0, 50.0);
+/// canvas.fill_path(&mut path, Paint::color(Color::black()));
+/// canvas.flush();
+///
+/// let frame = canvas.renderer_mut().last_frame().unwrap();
+/// let cmd = frame.commands().last().unwrap();
+/// assert!(matches!(cmd.cmd_type(), CommandType::ConvexFill { .. }));
+/// ```
+#[derive(Default)]
+pub struct Recorder {
+    frames: Vec<RecordedFrame>,
+    size: Size,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All frames recorded so far, oldest first.
+    pub fn frames(&self) -> &[RecordedFrame] {
+        &self.frames
+    }
+
+    pub fn last_frame(&self) -> Option<&RecordedFrame> {
+        self.frames.last()
+    }
+
+    /// Removes and returns all recorded frames.
+    pub fn take_frames(&mut self) -> Vec<RecordedFrame> {
+        std::mem::take(&mut self.frames)
+    }
+}
+
+impl Renderer for Recorder {
+    type Image = VoidImage;
+    type BufferCache = crate::renderer::VoidCache;
+
+    fn alloc_buffer_cache(&self) -> Self::BufferCache {
+        Self::BufferCache::new()
+    }
+
+    fn view_size(&self) -> Size {
+        self.size
+    }
+
+    fn set_size(&mut self, width: u32, height: u32, _dpi: f32) {
+        self.size = Size::new(width as f32, height as f32);
+    }
+
+    fn render(
+        &mut self,
+        _images: &ImageStore<VoidImage>,
+        _cache: &mut Self::BufferCache,
+        verts: &[Vertex],
+        commands: &[Command],
+    ) {
+        self.frames.push(RecordedFrame {
+            verts: verts.to_vec(),
+            commands: commands.to_vec(),
+        });
+    }
+
+    fn alloc_image(&mut self, info: ImageInfo) -> Result<Self::Image, ErrorKind> {
+        Void.alloc_image(info)
+    }
+
+    fn start_capture(&self) {}
+
+    fn stop_capture(&self) {}
+
+    fn label(&self, _images: &ImageStore<Self::Image>, _id: ImageId) -> String {
+        "labels not supported for recorder backend".to_owned()
+    }
+
+    fn set_label(&self, _images: &ImageStore<Self::Image>, _id: ImageId, _label: &str) {}
+
+    fn update_image(
+        &mut self,
+        image: &mut Self::Image,
+        data: ImageSource,
+        x: usize,
+        y: usize,
+    ) -> Result<(), ErrorKind> {
+        Void.update_image(image, data, x, y)
+    }
+
+    fn delete_image(&mut self, _image: Self::Image) {}
+
+    fn flip_y() -> bool {
+        false
+    }
+
+    fn flip_uv() -> bool {
+        false
+    }
+
+    fn screenshot(&mut self, _images: &ImageStore<Self::Image>) -> Result<ImgVec<RGBA8>, ErrorKind> {
+        Ok(ImgVec::new(Vec::new(), 0, 0))
+    }
+}
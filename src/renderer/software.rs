@@ -0,0 +1,447 @@
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use crate::{
+    renderer::{ImageId, Vertex},
+    Color, ErrorKind, FillRule, ImageInfo, ImageSource, ImageStore, Size,
+};
+
+use super::{Command, CommandType, Params, RenderTarget, Renderer};
+
+mod pixmap;
+pub use pixmap::SoftwareImage;
+use pixmap::{Pixmap, Sampler};
+
+mod rasterizer;
+use rasterizer::{DrawState, Primitive, RenderPass, StencilFunc, StencilOp, StencilState};
+
+mod shader;
+use shader::FragmentShader;
+
+/// Renderer that executes all commands on the CPU into an RGBA8 framebuffer.
+///
+/// It follows the OpenGl backend step by step (stencil passes, fringe antialiasing, gradient and image
+/// shading) so `Canvas::screenshot` produces the same pixels on machines without a GPU.
+pub struct Software {
+    screen: Pixmap,
+    stencil: Vec<u8>,
+    render_target: RenderTarget,
+}
+
+impl Software {
+    pub fn new() -> Self {
+        Self {
+            screen: Pixmap::default(),
+            stencil: Vec::new(),
+            render_target: RenderTarget::Screen,
+        }
+    }
+
+    fn set_target(&mut self, images: &ImageStore<SoftwareImage>, target: RenderTarget) {
+        let (width, height) = match target {
+            RenderTarget::Screen => (self.screen.width(), self.screen.height()),
+            RenderTarget::Image(id) => images.info(id).map_or((0, 0), |info| (info.width(), info.height())),
+            RenderTarget::None => unimplemented!("rendertarget cannot be none"),
+        };
+
+        self.render_target = target;
+        self.stencil.clear();
+        self.stencil.resize(width * height, 0);
+    }
+
+    fn with_pass(&mut self, images: &ImageStore<SoftwareImage>, f: impl FnOnce(&mut RenderPass)) {
+        match self.render_target {
+            RenderTarget::Screen => f(&mut RenderPass {
+                pixmap: &mut self.screen,
+                stencil: &mut self.stencil,
+                flip_y: false,
+            }),
+            RenderTarget::Image(id) => {
+                if let Some(image) = images.get(id) {
+                    let mut pixmap = image.pixels.borrow_mut();
+
+                    f(&mut RenderPass {
+                        pixmap: &mut pixmap,
+                        stencil: &mut self.stencil,
+                        flip_y: true,
+                    });
+                }
+            }
+            RenderTarget::None => unimplemented!("rendertarget cannot be none"),
+        }
+    }
+
+    fn convex_fill(
+        pass: &mut RenderPass,
+        images: &ImageStore<SoftwareImage>,
+        verts: &[Vertex],
+        cmd: &Command,
+        params: &Params,
+    ) {
+        let state = DrawState::new(cmd.composite_operation);
+
+        with_shader(images, cmd, params, |shader| {
+            for drawable in &cmd.drawables {
+                if let Some(range) = drawable.fill_verts {
+                    pass.draw(verts, range, Primitive::TriangleFan, &state, shader);
+                }
+
+                if let Some(range) = drawable.stroke_verts {
+                    pass.draw(verts, range, Primitive::TriangleStrip, &state, shader);
+                }
+            }
+        });
+    }
+
+    fn concave_fill(
+        pass: &mut RenderPass,
+        images: &ImageStore<SoftwareImage>,
+        verts: &[Vertex],
+        cmd: &Command,
+        stencil_params: &Params,
+        fill_params: &Params,
+    ) {
+        // Write the winding of the shape to the stencil buffer
+        let mut state = DrawState::new(cmd.composite_operation);
+        state.cull_back_faces = false;
+        state.color_write = false;
+        state.stencil = Some(StencilState {
+            front_pass_op: StencilOp::IncrWrap,
+            back_pass_op: StencilOp::DecrWrap,
+            ..StencilState::new(StencilFunc::Always, 0xff, StencilOp::Keep, StencilOp::Keep)
+        });
+
+        let shader = FragmentShader {
+            params: stencil_params,
+            tex: None,
+            mask: None,
+        };
+
+        for drawable in &cmd.drawables {
+            if let Some(range) = drawable.fill_verts {
+                pass.draw(verts, range, Primitive::TriangleFan, &state, &|p, t| shader.shade(p, t));
+            }
+        }
+
+        let read_mask = match cmd.fill_rule {
+            FillRule::NonZero => 0xff,
+            FillRule::EvenOdd => 0x1,
+        };
+
+        let mut state = DrawState::new(cmd.composite_operation);
+
+        with_shader(images, cmd, fill_params, |shader| {
+            // Draw anti-aliased fringes outside of the filled area
+            state.stencil = Some(StencilState::new(
+                StencilFunc::Equal,
+                read_mask,
+                StencilOp::Keep,
+                StencilOp::Keep,
+            ));
+
+            for drawable in &cmd.drawables {
+                if let Some(range) = drawable.stroke_verts {
+                    pass.draw(verts, range, Primitive::TriangleStrip, &state, shader);
+                }
+            }
+
+            // Fill the covering quad where the stencil is set, resetting the stencil as we go
+            state.stencil = Some(StencilState::new(
+                StencilFunc::NotEqual,
+                read_mask,
+                StencilOp::Zero,
+                StencilOp::Zero,
+            ));
+
+            if let Some(range) = cmd.triangles_verts {
+                pass.draw(verts, range, Primitive::TriangleStrip, &state, shader);
+            }
+        });
+    }
+
+    fn stroke(
+        pass: &mut RenderPass,
+        images: &ImageStore<SoftwareImage>,
+        verts: &[Vertex],
+        cmd: &Command,
+        params: &Params,
+    ) {
+        let state = DrawState::new(cmd.composite_operation);
+
+        with_shader(images, cmd, params, |shader| {
+            for drawable in &cmd.drawables {
+                if let Some(range) = drawable.stroke_verts {
+                    pass.draw(verts, range, Primitive::TriangleStrip, &state, shader);
+                }
+            }
+        });
+    }
+
+    fn stencil_stroke(
+        pass: &mut RenderPass,
+        images: &ImageStore<SoftwareImage>,
+        verts: &[Vertex],
+        cmd: &Command,
+        params1: &Params,
+        params2: &Params,
+    ) {
+        let mut state = DrawState::new(cmd.composite_operation);
+
+        // Fill the stroke base without overlap
+        state.stencil = Some(StencilState::new(
+            StencilFunc::Equal,
+            0xff,
+            StencilOp::Keep,
+            StencilOp::Incr,
+        ));
+
+        with_shader(images, cmd, params2, |shader| {
+            for drawable in &cmd.drawables {
+                if let Some(range) = drawable.stroke_verts {
+                    pass.draw(verts, range, Primitive::TriangleStrip, &state, shader);
+                }
+            }
+        });
+
+        // Draw anti-aliased pixels.
+        state.stencil = Some(StencilState::new(
+            StencilFunc::Equal,
+            0xff,
+            StencilOp::Keep,
+            StencilOp::Keep,
+        ));
+
+        with_shader(images, cmd, params1, |shader| {
+            for drawable in &cmd.drawables {
+                if let Some(range) = drawable.stroke_verts {
+                    pass.draw(verts, range, Primitive::TriangleStrip, &state, shader);
+                }
+            }
+
+            // Clear stencil buffer.
+            state.color_write = false;
+            state.stencil = Some(StencilState::new(
+                StencilFunc::Always,
+                0xff,
+                StencilOp::Zero,
+                StencilOp::Zero,
+            ));
+
+            for drawable in &cmd.drawables {
+                if let Some(range) = drawable.stroke_verts {
+                    pass.draw(verts, range, Primitive::TriangleStrip, &state, shader);
+                }
+            }
+        });
+    }
+
+    fn triangles(
+        pass: &mut RenderPass,
+        images: &ImageStore<SoftwareImage>,
+        verts: &[Vertex],
+        cmd: &Command,
+        params: &Params,
+    ) {
+        let state = DrawState::new(cmd.composite_operation);
+
+        with_shader(images, cmd, params, |shader| {
+            if let Some(range) = cmd.triangles_verts {
+                pass.draw(verts, range, Primitive::Triangles, &state, shader);
+            }
+        });
+    }
+}
+
+impl Default for Software {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Binds the command's image and alpha mask and calls `f` with the resulting fragment shader.
+///
+/// Must be called while the render target is borrowed so that an image can't be sampled while it's being drawn to.
+fn with_shader(
+    images: &ImageStore<SoftwareImage>,
+    cmd: &Command,
+    params: &Params,
+    f: impl FnOnce(&dyn Fn([f32; 2], [f32; 2]) -> Option<[f32; 4]>),
+) {
+    let tex = Sampler::new(images, cmd.image);
+    let mask = Sampler::new(images, cmd.alpha_mask);
+
+    let shader = FragmentShader {
+        params,
+        tex: tex.as_ref(),
+        mask: mask.as_ref(),
+    };
+
+    f(&|fpos, ftcoord| shader.shade(fpos, ftcoord));
+}
+
+/// Shades a rectangle of device pixels with the paint of the params, the way the inside of a fill is shaded but
+/// without the scissor. `ramp` is the color ramp image of multi stop gradients. Used by the vector renderers for
+/// paints that have no equivalent in their formats.
+///
+/// Returns premultiplied pixels.
+pub(super) fn shade_rect(
+    params: &Params,
+    ramp: Option<&SoftwareImage>,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> ImgVec<RGBA8> {
+    let mut params = *params;
+    params.scissor_mat = [0.0; 12];
+    params.scissor_ext = [1.0, 1.0];
+    params.scissor_scale = [1.0, 1.0];
+    params.stroke_mult = 1.0;
+    params.stroke_thr = -1.0;
+    params.has_mask = 0.0;
+
+    let tex = ramp.and_then(Sampler::from_image);
+
+    let shader = FragmentShader {
+        params: &params,
+        tex: tex.as_ref(),
+        mask: None,
+    };
+
+    let mut pixels = Vec::with_capacity(width * height);
+
+    for row in y..y + height {
+        for col in x..x + width {
+            let fpos = [col as f32 + 0.5, row as f32 + 0.5];
+            let color = shader.shade(fpos, [0.5, 1.0]).unwrap_or([0.0; 4]);
+
+            pixels.push(pixmap::to_rgba8(color));
+        }
+    }
+
+    ImgVec::new(pixels, width, height)
+}
+
+impl Renderer for Software {
+    type Image = SoftwareImage;
+    type BufferCache = crate::renderer::VoidCache;
+
+    fn alloc_buffer_cache(&self) -> Self::BufferCache {
+        Self::BufferCache::new()
+    }
+
+    fn view_size(&self) -> Size {
+        Size::new(self.screen.width() as f32, self.screen.height() as f32)
+    }
+
+    fn set_size(&mut self, width: u32, height: u32, _dpi: f32) {
+        self.screen.resize(width as usize, height as usize);
+
+        if self.render_target == RenderTarget::Screen {
+            self.stencil.resize(self.screen.width() * self.screen.height(), 0);
+        }
+    }
+
+    fn start_capture(&self) {}
+
+    fn stop_capture(&self) {}
+
+    fn label(&self, _images: &ImageStore<Self::Image>, _id: ImageId) -> String {
+        "labels are not supported on the software renderer".to_owned()
+    }
+
+    fn set_label(&self, _images: &ImageStore<Self::Image>, _id: ImageId, _label: &str) {}
+
+    fn render(
+        &mut self,
+        images: &ImageStore<SoftwareImage>,
+        _cache: &mut Self::BufferCache,
+        verts: &[Vertex],
+        commands: &[Command],
+    ) {
+        for cmd in commands {
+            match &cmd.cmd_type {
+                CommandType::GPUTriangle | CommandType::CustomCommand { .. } => {
+                    // These encode metal commands directly and have no meaning here.
+                }
+                CommandType::Blit {
+                    source,
+                    destination_origin,
+                } => {
+                    // Copy the source first so blitting an image onto itself doesn't alias the borrow.
+                    let source = images.get(*source).map(|image| image.pixels.borrow().clone());
+
+                    if let Some(source) = source {
+                        self.with_pass(images, |pass| pass.blit(&source, *destination_origin));
+                    }
+                }
+                CommandType::ConvexFill { params } => {
+                    self.with_pass(images, |pass| Self::convex_fill(pass, images, verts, cmd, params))
+                }
+                CommandType::ConcaveFill {
+                    stencil_params,
+                    fill_params,
+                } => self.with_pass(images, |pass| {
+                    Self::concave_fill(pass, images, verts, cmd, stencil_params, fill_params)
+                }),
+                CommandType::Stroke { params } => {
+                    self.with_pass(images, |pass| Self::stroke(pass, images, verts, cmd, params))
+                }
+                CommandType::StencilStroke { params1, params2 } => self.with_pass(images, |pass| {
+                    Self::stencil_stroke(pass, images, verts, cmd, params1, params2)
+                }),
+                CommandType::Triangles { params } => {
+                    self.with_pass(images, |pass| Self::triangles(pass, images, verts, cmd, params))
+                }
+                CommandType::ClearRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                } => {
+                    let color: Color = *color;
+                    self.with_pass(images, |pass| {
+                        pass.clear_rect(*x, *y, *width, *height, color.to_array())
+                    });
+                }
+                CommandType::SetRenderTarget(target) => self.set_target(images, *target),
+            }
+        }
+    }
+
+    fn alloc_image(&mut self, info: ImageInfo) -> Result<Self::Image, ErrorKind> {
+        Ok(SoftwareImage::new(info))
+    }
+
+    fn update_image(
+        &mut self,
+        image: &mut Self::Image,
+        data: ImageSource,
+        x: usize,
+        y: usize,
+    ) -> Result<(), ErrorKind> {
+        image.update(data, x, y)
+    }
+
+    fn delete_image(&mut self, _image: Self::Image) {}
+
+    fn flip_y() -> bool {
+        false
+    }
+
+    fn flip_uv() -> bool {
+        false
+    }
+
+    fn screenshot(&mut self, images: &ImageStore<Self::Image>) -> Result<ImgVec<RGBA8>, ErrorKind> {
+        match self.render_target {
+            RenderTarget::Screen => Ok(self.screen.to_img(false)),
+            RenderTarget::Image(id) => images
+                .get(id)
+                .map(|image| image.pixels.borrow().to_img(true))
+                .ok_or(ErrorKind::ImageIdNotFound),
+            RenderTarget::None => unimplemented!("rendertarget cannot be none"),
+        }
+    }
+}
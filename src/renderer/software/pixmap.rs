@@ -0,0 +1,225 @@
+use std::cell::{Ref, RefCell};
+
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use crate::{ErrorKind, ImageFlags, ImageId, ImageInfo, ImageSource, ImageStore};
+
+/// CPU side RGBA8 pixel buffer. Used both for the screen framebuffer and for images.
+#[derive(Clone, Debug, Default)]
+pub struct Pixmap {
+    width: usize,
+    height: usize,
+    data: Vec<RGBA8>,
+}
+
+impl Pixmap {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![RGBA8::new(0, 0, 0, 0); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> RGBA8 {
+        self.data[y * self.width + x]
+    }
+
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut RGBA8 {
+        &mut self.data[y * self.width + x]
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if self.width != width || self.height != height {
+            *self = Self::new(width, height);
+        }
+    }
+
+    /// Copies the pixels into an image, optionally reversing the row order.
+    pub fn to_img(&self, flip_y: bool) -> ImgVec<RGBA8> {
+        let mut buf = Vec::with_capacity(self.data.len());
+
+        if flip_y {
+            for row in self.data.chunks(self.width.max(1)).rev() {
+                buf.extend_from_slice(row);
+            }
+        } else {
+            buf.extend_from_slice(&self.data);
+        }
+
+        ImgVec::new(buf, self.width, self.height)
+    }
+
+    /// Writes image data at x, y converting it to RGBA8.
+    ///
+    /// Gray pixels are replicated in all color channels so they can be sampled the same way as
+    /// single channel textures on the gpu backends.
+    pub fn update(&mut self, src: ImageSource, x: usize, y: usize) {
+        match src {
+            ImageSource::Gray(img) => {
+                for (row_y, row) in img.rows().enumerate() {
+                    for (row_x, px) in row.iter().enumerate() {
+                        *self.pixel_mut(x + row_x, y + row_y) = RGBA8::new(px.0, px.0, px.0, 255);
+                    }
+                }
+            }
+            ImageSource::Rgb(img) => {
+                for (row_y, row) in img.rows().enumerate() {
+                    for (row_x, px) in row.iter().enumerate() {
+                        *self.pixel_mut(x + row_x, y + row_y) = RGBA8::new(px.r, px.g, px.b, 255);
+                    }
+                }
+            }
+            ImageSource::Rgba(img) => {
+                for (row_y, row) in img.rows().enumerate() {
+                    let start = (y + row_y) * self.width + x;
+                    self.data[start..start + row.len()].copy_from_slice(row);
+                }
+            }
+        }
+    }
+}
+
+/// Image type of the [Software](struct.Software.html) renderer.
+pub struct SoftwareImage {
+    pub(crate) info: ImageInfo,
+    pub(crate) pixels: RefCell<Pixmap>,
+}
+
+impl SoftwareImage {
+    pub fn new(info: ImageInfo) -> Self {
+        Self {
+            info,
+            pixels: RefCell::new(Pixmap::new(info.width(), info.height())),
+        }
+    }
+
+    pub fn info(&self) -> ImageInfo {
+        self.info
+    }
+
+    pub fn update(&mut self, src: ImageSource, x: usize, y: usize) -> Result<(), ErrorKind> {
+        let size = src.dimensions();
+
+        if x + size.0 > self.info.width() {
+            return Err(ErrorKind::ImageUpdateOutOfBounds);
+        }
+
+        if y + size.1 > self.info.height() {
+            return Err(ErrorKind::ImageUpdateOutOfBounds);
+        }
+
+        if self.info.format() != src.format() {
+            return Err(ErrorKind::ImageUpdateWithDifferentFormat);
+        }
+
+        self.pixels.get_mut().update(src, x, y);
+
+        Ok(())
+    }
+}
+
+/// Texture lookup mirroring the sampler state the gpu backends set up from `ImageFlags`.
+pub struct Sampler<'a> {
+    pixmap: Ref<'a, Pixmap>,
+    flags: ImageFlags,
+}
+
+impl<'a> Sampler<'a> {
+    /// Returns None if the image doesn't exist or is currently being rendered to.
+    pub fn new(images: &'a ImageStore<SoftwareImage>, id: Option<ImageId>) -> Option<Self> {
+        Self::from_image(images.get(id?)?)
+    }
+
+    /// Returns None if the image is currently being rendered to.
+    pub fn from_image(image: &'a SoftwareImage) -> Option<Self> {
+        let pixmap = image.pixels.try_borrow().ok()?;
+
+        Some(Self {
+            pixmap,
+            flags: image.info.flags(),
+        })
+    }
+
+    /// Samples the image at normalized coordinates. Returns the color with components in the 0..1 range.
+    pub fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        let w = self.pixmap.width();
+        let h = self.pixmap.height();
+
+        if w == 0 || h == 0 {
+            return [0.0, 0.0, 0.0, 0.0];
+        }
+
+        let repeat_x = self.flags.contains(ImageFlags::REPEAT_X);
+        let repeat_y = self.flags.contains(ImageFlags::REPEAT_Y);
+
+        let x = u * w as f32;
+        let y = v * h as f32;
+
+        if self.flags.contains(ImageFlags::NEAREST) {
+            let x = wrap(x.floor() as i64, w, repeat_x);
+            let y = wrap(y.floor() as i64, h, repeat_y);
+
+            return to_f32(self.pixmap.pixel(x, y));
+        }
+
+        let x = x - 0.5;
+        let y = y - 0.5;
+        let fx = x - x.floor();
+        let fy = y - y.floor();
+
+        let x0 = wrap(x.floor() as i64, w, repeat_x);
+        let x1 = wrap(x.floor() as i64 + 1, w, repeat_x);
+        let y0 = wrap(y.floor() as i64, h, repeat_y);
+        let y1 = wrap(y.floor() as i64 + 1, h, repeat_y);
+
+        let c00 = to_f32(self.pixmap.pixel(x0, y0));
+        let c10 = to_f32(self.pixmap.pixel(x1, y0));
+        let c01 = to_f32(self.pixmap.pixel(x0, y1));
+        let c11 = to_f32(self.pixmap.pixel(x1, y1));
+
+        let mut res = [0.0; 4];
+
+        for i in 0..4 {
+            let top = c00[i] + (c10[i] - c00[i]) * fx;
+            let bottom = c01[i] + (c11[i] - c01[i]) * fx;
+            res[i] = top + (bottom - top) * fy;
+        }
+
+        res
+    }
+}
+
+fn wrap(coord: i64, size: usize, repeat: bool) -> usize {
+    let size = size as i64;
+
+    if repeat {
+        coord.rem_euclid(size) as usize
+    } else {
+        coord.max(0).min(size - 1) as usize
+    }
+}
+
+pub fn to_f32(px: RGBA8) -> [f32; 4] {
+    [
+        px.r as f32 / 255.0,
+        px.g as f32 / 255.0,
+        px.b as f32 / 255.0,
+        px.a as f32 / 255.0,
+    ]
+}
+
+pub fn to_rgba8(color: [f32; 4]) -> RGBA8 {
+    let c = |v: f32| (v.max(0.0).min(1.0) * 255.0 + 0.5) as u8;
+
+    RGBA8::new(c(color[0]), c(color[1]), c(color[2]), c(color[3]))
+}
@@ -0,0 +1,322 @@
+use crate::{renderer::Vertex, BlendFactor, CompositeOperationState};
+
+use super::pixmap::{to_f32, to_rgba8, Pixmap};
+
+/// How a range of vertices is assembled into triangles. Mirrors the GL primitive types.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Primitive {
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StencilFunc {
+    Always,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Incr,
+    IncrWrap,
+    DecrWrap,
+}
+
+impl StencilOp {
+    fn apply(self, value: u8) -> u8 {
+        match self {
+            Self::Keep => value,
+            Self::Zero => 0,
+            Self::Incr => value.saturating_add(1),
+            Self::IncrWrap => value.wrapping_add(1),
+            Self::DecrWrap => value.wrapping_sub(1),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct StencilState {
+    pub func: StencilFunc,
+    pub reference: u8,
+    pub read_mask: u8,
+    pub fail_op: StencilOp,
+    pub front_pass_op: StencilOp,
+    pub back_pass_op: StencilOp,
+}
+
+impl StencilState {
+    pub fn new(func: StencilFunc, read_mask: u8, fail_op: StencilOp, pass_op: StencilOp) -> Self {
+        Self {
+            func,
+            reference: 0,
+            read_mask,
+            fail_op,
+            front_pass_op: pass_op,
+            back_pass_op: pass_op,
+        }
+    }
+
+    fn test(&self, value: u8) -> bool {
+        let reference = self.reference & self.read_mask;
+        let value = value & self.read_mask;
+
+        match self.func {
+            StencilFunc::Always => true,
+            StencilFunc::Equal => reference == value,
+            StencilFunc::NotEqual => reference != value,
+        }
+    }
+}
+
+/// Fixed function state used for a single draw.
+#[derive(Copy, Clone, Debug)]
+pub struct DrawState {
+    pub cull_back_faces: bool,
+    pub color_write: bool,
+    pub blend: CompositeOperationState,
+    pub stencil: Option<StencilState>,
+}
+
+impl DrawState {
+    pub fn new(blend: CompositeOperationState) -> Self {
+        Self {
+            cull_back_faces: true,
+            color_write: true,
+            blend,
+            stencil: None,
+        }
+    }
+}
+
+/// The color and stencil buffers a draw writes to.
+pub struct RenderPass<'a> {
+    pub pixmap: &'a mut Pixmap,
+    pub stencil: &'a mut [u8],
+    /// Image render targets are stored bottom-up like gl framebuffers so that they
+    /// sample the same way (and need ImageFlags::FLIP_Y) on every backend.
+    pub flip_y: bool,
+}
+
+impl RenderPass<'_> {
+    pub fn width(&self) -> usize {
+        self.pixmap.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.pixmap.height()
+    }
+
+    fn row(&self, y: usize) -> usize {
+        if self.flip_y {
+            self.height() - 1 - y
+        } else {
+            y
+        }
+    }
+
+    /// Fills a rectangle of the color and stencil buffers. The rectangle is in canvas coordinates.
+    pub fn clear_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: [f32; 4]) {
+        let color = to_rgba8(color);
+        let maxx = (x as usize + width as usize).min(self.width());
+        let maxy = (y as usize + height as usize).min(self.height());
+
+        for py in (y as usize)..maxy {
+            let row = self.row(py);
+
+            for px in (x as usize)..maxx {
+                *self.pixmap.pixel_mut(px, row) = color;
+                self.stencil[row * self.pixmap.width() + px] = 0;
+            }
+        }
+    }
+
+    /// Copies a pixmap so that its top-left corner lands at the given canvas position.
+    pub fn blit(&mut self, source: &Pixmap, origin: (u32, u32)) {
+        let maxx = (origin.0 as usize + source.width()).min(self.width());
+        let maxy = (origin.1 as usize + source.height()).min(self.height());
+
+        for py in (origin.1 as usize)..maxy {
+            let row = self.row(py);
+
+            for px in (origin.0 as usize)..maxx {
+                *self.pixmap.pixel_mut(px, row) = source.pixel(px - origin.0 as usize, py - origin.1 as usize);
+            }
+        }
+    }
+
+    /// Rasterizes vertices[start..start + count] assembled as `primitive`.
+    ///
+    /// `shader` receives the fragment position and interpolated texture coordinate and returns the
+    /// premultiplied color of the fragment or None if the fragment is discarded.
+    pub fn draw(
+        &mut self,
+        verts: &[Vertex],
+        (start, count): (usize, usize),
+        primitive: Primitive,
+        state: &DrawState,
+        shader: &dyn Fn([f32; 2], [f32; 2]) -> Option<[f32; 4]>,
+    ) {
+        let verts = &verts[start..start + count];
+
+        match primitive {
+            Primitive::Triangles => {
+                for tri in verts.chunks_exact(3) {
+                    self.triangle(&tri[0], &tri[1], &tri[2], state, shader);
+                }
+            }
+            Primitive::TriangleStrip => {
+                for i in 2..verts.len() {
+                    // Every second triangle in a strip has its first two vertices swapped to keep the winding.
+                    if i % 2 == 0 {
+                        self.triangle(&verts[i - 2], &verts[i - 1], &verts[i], state, shader);
+                    } else {
+                        self.triangle(&verts[i - 1], &verts[i - 2], &verts[i], state, shader);
+                    }
+                }
+            }
+            Primitive::TriangleFan => {
+                for i in 2..verts.len() {
+                    self.triangle(&verts[0], &verts[i - 1], &verts[i], state, shader);
+                }
+            }
+        }
+    }
+
+    fn triangle(
+        &mut self,
+        v0: &Vertex,
+        v1: &Vertex,
+        v2: &Vertex,
+        state: &DrawState,
+        shader: &dyn Fn([f32; 2], [f32; 2]) -> Option<[f32; 4]>,
+    ) {
+        let area = edge(v0, v1, v2.x, v2.y);
+
+        if area == 0.0 || !area.is_finite() {
+            return;
+        }
+
+        // Canvas coordinates are y-down, so counter clockwise triangles in NDC have negative area here.
+        let front_facing = area < 0.0;
+
+        if state.cull_back_faces && !front_facing {
+            return;
+        }
+
+        // Normalize the winding so that inside points have positive edge functions.
+        let (v1, v2, area) = if area < 0.0 { (v2, v1, -area) } else { (v1, v2, area) };
+
+        let minx = v0.x.min(v1.x).min(v2.x).floor().max(0.0);
+        let miny = v0.y.min(v1.y).min(v2.y).floor().max(0.0);
+        let maxx = v0.x.max(v1.x).max(v2.x).ceil().min(self.width() as f32);
+        let maxy = v0.y.max(v1.y).max(v2.y).ceil().min(self.height() as f32);
+
+        if minx >= maxx || miny >= maxy {
+            return;
+        }
+
+        let width = self.pixmap.width();
+
+        for py in (miny as usize)..(maxy as usize) {
+            let y = py as f32 + 0.5;
+            let row = self.row(py);
+
+            for px in (minx as usize)..(maxx as usize) {
+                let x = px as f32 + 0.5;
+
+                let w0 = edge(v1, v2, x, y);
+                let w1 = edge(v2, v0, x, y);
+                let w2 = edge(v0, v1, x, y);
+
+                if !covers(w0, v1, v2) || !covers(w1, v2, v0) || !covers(w2, v0, v1) {
+                    continue;
+                }
+
+                let (l0, l1, l2) = (w0 / area, w1 / area, w2 / area);
+                let s = v0.u * l0 + v1.u * l1 + v2.u * l2;
+                let t = v0.v * l0 + v1.v * l1 + v2.v * l2;
+
+                let color = match shader([x, y], [s, t]) {
+                    Some(color) => color,
+                    None => continue,
+                };
+
+                if let Some(stencil) = state.stencil {
+                    let value = &mut self.stencil[row * width + px];
+
+                    if !stencil.test(*value) {
+                        *value = stencil.fail_op.apply(*value);
+                        continue;
+                    }
+
+                    let op = if front_facing {
+                        stencil.front_pass_op
+                    } else {
+                        stencil.back_pass_op
+                    };
+
+                    *value = op.apply(*value);
+                }
+
+                if state.color_write {
+                    let dst = self.pixmap.pixel_mut(px, row);
+                    *dst = to_rgba8(blend(&state.blend, color, to_f32(*dst)));
+                }
+            }
+        }
+    }
+}
+
+fn edge(a: &Vertex, b: &Vertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+// Pixel centers exactly on an edge belong to only one of the triangles sharing that edge.
+fn covers(w: f32, a: &Vertex, b: &Vertex) -> bool {
+    if w != 0.0 {
+        return w > 0.0;
+    }
+
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+
+    dy > 0.0 || (dy == 0.0 && dx < 0.0)
+}
+
+fn blend(state: &CompositeOperationState, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+    let mut res = [0.0; 4];
+
+    for i in 0..3 {
+        res[i] = src[i] * factor(state.src_rgb, src, dst, i) + dst[i] * factor(state.dst_rgb, src, dst, i);
+    }
+
+    res[3] = src[3] * factor(state.src_alpha, src, dst, 3) + dst[3] * factor(state.dst_alpha, src, dst, 3);
+
+    res
+}
+
+fn factor(factor: BlendFactor, src: [f32; 4], dst: [f32; 4], channel: usize) -> f32 {
+    match factor {
+        BlendFactor::Zero => 0.0,
+        BlendFactor::One => 1.0,
+        BlendFactor::SrcColor => src[channel],
+        BlendFactor::OneMinusSrcColor => 1.0 - src[channel],
+        BlendFactor::DstColor => dst[channel],
+        BlendFactor::OneMinusDstColor => 1.0 - dst[channel],
+        BlendFactor::SrcAlpha => src[3],
+        BlendFactor::OneMinusSrcAlpha => 1.0 - src[3],
+        BlendFactor::DstAlpha => dst[3],
+        BlendFactor::OneMinusDstAlpha => 1.0 - dst[3],
+        BlendFactor::SrcAlphaSaturate => {
+            if channel == 3 {
+                1.0
+            } else {
+                src[3].min(1.0 - dst[3])
+            }
+        }
+    }
+}
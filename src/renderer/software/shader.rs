@@ -0,0 +1,217 @@
+//! Port of the fragment shader in `opengl/main-fs.glsl` (with EDGE_AA enabled).
+
+use crate::renderer::{Params, ShaderType};
+use crate::SpreadMode;
+
+use super::pixmap::Sampler;
+
+pub struct FragmentShader<'a> {
+    pub params: &'a Params,
+    pub tex: Option<&'a Sampler<'a>>,
+    pub mask: Option<&'a Sampler<'a>>,
+}
+
+impl FragmentShader<'_> {
+    /// Returns the premultiplied fragment color or None if the fragment is discarded.
+    pub fn shade(&self, fpos: [f32; 2], ftcoord: [f32; 2]) -> Option<[f32; 4]> {
+        let p = self.params;
+
+        let scissor = self.scissor_mask(fpos);
+        let stroke_alpha = self.stroke_mask(ftcoord);
+
+        if stroke_alpha < p.stroke_thr {
+            return None;
+        }
+
+        let mut result = if p.shader_type == ShaderType::FillGradient.to_f32() {
+            // Calculate gradient color using box gradient
+            let pt = transform(&p.paint_mat, fpos);
+
+            let d = self.spread((sdroundrect(pt, p.extent, p.radius) + p.feather * 0.5) / p.feather);
+
+            mix(p.inner_col, p.outer_col, d)
+        } else if p.shader_type == ShaderType::FillImage.to_f32() {
+            let pt = transform(&p.paint_mat, fpos);
+
+            let mut color = self.tex.map_or([0.0, 0.0, 0.0, 1.0], |tex| {
+                tex.sample(pt[0] / p.extent[0], pt[1] / p.extent[1])
+            });
+
+            if p.tex_type == 1.0 {
+                color = [color[0] * color[3], color[1] * color[3], color[2] * color[3], color[3]];
+            }
+
+            if p.tex_type == 2.0 {
+                color = [color[0]; 4];
+            }
+
+            // Apply color tint and alpha.
+            mul(color, p.inner_col)
+        } else if p.shader_type == ShaderType::FillImageGradient.to_f32() {
+            // Same as the gradient above, but the color is looked up from a 256x1 color ramp
+            let pt = transform(&p.paint_mat, fpos);
+
+            let d = self.spread((sdroundrect(pt, p.extent, p.radius) + p.feather * 0.5) / p.feather);
+
+            self.tex.map_or([0.0, 0.0, 0.0, 1.0], |tex| {
+                tex.sample(d * 255.0 / 256.0 + 0.5 / 256.0, 0.5)
+            })
+        } else if p.shader_type == ShaderType::FillConicGradient.to_f32() {
+            // The angle around the center, from 0 at the start angle to 1 after a full clockwise turn
+            let pt = transform(&p.paint_mat, fpos);
+
+            let d = pt[1].atan2(pt[0]) / (2.0 * std::f32::consts::PI);
+            let d = d - d.floor();
+
+            self.tex.map_or([0.0, 0.0, 0.0, 1.0], |tex| {
+                tex.sample(d * 255.0 / 256.0 + 0.5 / 256.0, 0.5)
+            })
+        } else if p.shader_type == ShaderType::FilterImage.to_f32() {
+            // Sampled like an image, but the color is unpremultiplied to go through the color matrix
+            let pt = transform(&p.paint_mat, fpos);
+
+            let mut color = self.tex.map_or([0.0, 0.0, 0.0, 1.0], |tex| {
+                tex.sample(pt[0] / p.extent[0], pt[1] / p.extent[1])
+            });
+
+            if p.tex_type == 0.0 && color[3] > 0.0 {
+                color = [color[0] / color[3], color[1] / color[3], color[2] / color[3], color[3]];
+            }
+
+            if p.tex_type == 2.0 {
+                color = [color[0]; 4];
+            }
+
+            let m = &p.color_matrix;
+            let mut filtered = [0.0; 4];
+
+            for (row, channel) in filtered.iter_mut().enumerate() {
+                let value = m[row] * color[0] + m[4 + row] * color[1] + m[8 + row] * color[2] + m[12 + row] * color[3];
+                *channel = clamp(value + p.outer_col[row]);
+            }
+
+            // Each channel is looked up in its channel of a 256x1 table
+            if p.lookup == 1.0 {
+                if let Some(mask) = self.mask {
+                    for (i, channel) in filtered.iter_mut().enumerate() {
+                        *channel = mask.sample(*channel * 255.0 / 256.0 + 0.5 / 256.0, 0.5)[i];
+                    }
+                }
+            }
+
+            let [r, g, b, a] = filtered;
+
+            mul([r * a, g * a, b * a, a], p.inner_col)
+        } else if p.shader_type == ShaderType::BlurImage.to_f32() {
+            // The texels on both sides of every offset up to the radius, weighted by the color matrix
+            let pt = transform(&p.paint_mat, fpos);
+            let pt = [pt[0] / p.extent[0], pt[1] / p.extent[1]];
+
+            let mut color = [0.0; 4];
+
+            for (i, weight) in p.color_matrix.iter().enumerate().take(p.radius as usize + 1) {
+                let offset = i as f32;
+
+                for side in [-1.0, 1.0] {
+                    let texel = self.texel(
+                        pt[0] + side * p.outer_col[0] * offset,
+                        pt[1] + side * p.outer_col[1] * offset,
+                    );
+
+                    for (channel, value) in color.iter_mut().zip(texel) {
+                        *channel += weight * value;
+                    }
+                }
+            }
+
+            mul(color, p.inner_col)
+        } else {
+            // Stencil fill
+            [1.0; 4]
+        };
+
+        if p.has_mask == 1.0 {
+            let mask = self.mask.map_or(0.0, |mask| mask.sample(ftcoord[0], ftcoord[1])[0]);
+
+            result = scale(result, mask * scissor);
+        } else if p.shader_type != ShaderType::Stencil.to_f32() {
+            result = scale(result, stroke_alpha * scissor);
+        }
+
+        Some(result)
+    }
+
+    // Premultiplied image color at the texture coordinates
+    fn texel(&self, u: f32, v: f32) -> [f32; 4] {
+        let color = self.tex.map_or([0.0; 4], |tex| tex.sample(u, v));
+
+        if self.params.tex_type == 1.0 {
+            [color[0] * color[3], color[1] * color[3], color[2] * color[3], color[3]]
+        } else if self.params.tex_type == 2.0 {
+            [color[0]; 4]
+        } else {
+            color
+        }
+    }
+
+    // Gradient position beyond [0..1] according to the spread mode
+    fn spread(&self, d: f32) -> f32 {
+        if self.params.spread_mode == SpreadMode::Repeat.to_f32() {
+            d - d.floor()
+        } else if self.params.spread_mode == SpreadMode::Reflect.to_f32() {
+            1.0 - ((d * 0.5 - (d * 0.5).floor()) * 2.0 - 1.0).abs()
+        } else {
+            clamp(d)
+        }
+    }
+
+    fn scissor_mask(&self, p: [f32; 2]) -> f32 {
+        let params = self.params;
+        let sc = transform(&params.scissor_mat, p);
+
+        let scx = 0.5 - (sc[0].abs() - params.scissor_ext[0]) * params.scissor_scale[0];
+        let scy = 0.5 - (sc[1].abs() - params.scissor_ext[1]) * params.scissor_scale[1];
+
+        clamp(scx) * clamp(scy)
+    }
+
+    // Stroke - from [0..1] to clipped pyramid, where the slope is 1px.
+    fn stroke_mask(&self, ftcoord: [f32; 2]) -> f32 {
+        (1.0f32).min((1.0 - (ftcoord[0] * 2.0 - 1.0).abs()) * self.params.stroke_mult) * (1.0f32).min(ftcoord[1])
+    }
+}
+
+fn transform(mat: &[f32; 12], p: [f32; 2]) -> [f32; 2] {
+    [
+        mat[0] * p[0] + mat[4] * p[1] + mat[8],
+        mat[1] * p[0] + mat[5] * p[1] + mat[9],
+    ]
+}
+
+fn sdroundrect(pt: [f32; 2], ext: [f32; 2], rad: f32) -> f32 {
+    let dx = pt[0].abs() - (ext[0] - rad);
+    let dy = pt[1].abs() - (ext[1] - rad);
+
+    dx.max(dy).min(0.0) + (dx.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt() - rad
+}
+
+fn clamp(v: f32) -> f32 {
+    v.max(0.0).min(1.0)
+}
+
+fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+        a[3] + (b[3] - a[3]) * t,
+    ]
+}
+
+fn mul(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
+}
+
+fn scale(a: [f32; 4], s: f32) -> [f32; 4] {
+    [a[0] * s, a[1] * s, a[2] * s, a[3] * s]
+}
@@ -0,0 +1,692 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use fnv::FnvHashSet;
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use crate::geometry::Transform2D;
+use crate::{
+    Color, CompositeOperation, ErrorKind, FillRule, ImageFlags, ImageId, ImageInfo, ImageSource, ImageStore, LineCap,
+    LineJoin, Paint, Path, Size, SpreadMode,
+};
+
+use super::vector::{self, Fill, Gradient, GradientShape};
+use super::{Command, CommandType, Params, RenderTarget, Renderer, SoftwareImage, Vertex, VoidCache};
+
+// Non repeating image patterns are made large enough to never tile within the document.
+const PATTERN_EXTENT: f32 = 1e6;
+
+/// Renderer that writes an SVG document instead of pixels.
+///
+/// Canvas commands are turned into `<path>` elements in device pixels with their curves kept: fills keep
+/// their fill rule, strokes their width, caps, joins and dashes, and text is drawn as glyph outlines. Linear
+/// and radial gradients map to SVG gradients, images are embedded as PNG data (with the `image-loading`
+/// feature) and scissors and clip paths become clip paths. Box and conic gradients have no SVG equivalent,
+/// the pixels they cover are embedded as an image instead, or their average color without `image-loading`.
+///
+/// Drawing with the source over, destination over, copy and lighter composite operations is exported, the
+/// other operations make [document](#method.document) return an error.
+///
+/// ```
+/// use gpucanvas::{renderer::SvgExport, Canvas, Color, Paint, Path};
+///
+/// let mut canvas = Canvas::new(SvgExport::new()).unwrap();
+/// canvas.set_size(100, 100, 1.0);
+///
+/// let mut path = Path::new();
+/// path.rect(10.0, 10.0, 50.0, 50.0);
+/// canvas.fill_path(&mut path, Paint::color(Color::black()));
+/// canvas.flush();
+///
+/// assert!(canvas.renderer_mut().document().unwrap().contains("<path d=\"M"));
+/// ```
+#[derive(Default)]
+pub struct SvgExport {
+    size: Size,
+    dpi: f32,
+    document: String,
+    next_id: usize,
+    // Set when the last flush drew with a composite operation that can't be exported
+    unsupported: bool,
+}
+
+impl SvgExport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The document written by the last `Canvas::flush`.
+    ///
+    /// Returns `ErrorKind::UnsupportedCompositeOperation` if anything was drawn with a composite operation SVG
+    /// can't express, the document then leaves out what was drawn with it.
+    pub fn document(&self) -> Result<&str, ErrorKind> {
+        if self.unsupported {
+            Err(ErrorKind::UnsupportedCompositeOperation)
+        } else {
+            Ok(&self.document)
+        }
+    }
+
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{}{}", prefix, self.next_id)
+    }
+
+    fn draw(&mut self, content: &mut Content, images: &ImageStore<SvgImage>, size: Size, cmd: &Command) {
+        let (params, shape) = match vector::shape(cmd) {
+            Some(shape) => shape,
+            None => return,
+        };
+
+        let operation = vector::composite_operation(cmd.composite_operation());
+
+        let blend = match operation {
+            Some(CompositeOperation::SourceOver)
+            | Some(CompositeOperation::DestinationOver)
+            | Some(CompositeOperation::Copy) => "",
+            Some(CompositeOperation::Lighter) => r#" style="mix-blend-mode:plus-lighter""#,
+            _ => {
+                self.unsupported = true;
+                return;
+            }
+        };
+
+        // SVG strokes have the same cap at both ends, other strokes are filled as outlines
+        let outline;
+
+        let (path, stroke) = if shape.stroke && shape.paint.line_cap_start != shape.paint.line_cap_end {
+            outline = shape.path.stroke_outline(&shape.paint);
+            (&outline, false)
+        } else {
+            (&shape.path, shape.stroke)
+        };
+
+        let (prefix, geometry) = if stroke {
+            ("stroke", stroke_attributes(&shape.paint))
+        } else {
+            let fill_rule = match shape.paint.fill_rule {
+                FillRule::EvenOdd if !shape.stroke => "evenodd",
+                _ => "nonzero",
+            };
+
+            ("fill", format!(r#"fill-rule="{}""#, fill_rule))
+        };
+
+        let image = |id| images.get(id).map(|image: &SvgImage| &image.pixels);
+
+        let paint = match vector::fill(params, cmd, shape, size, image) {
+            Some(Fill::Color(color)) => color_attributes(prefix, color),
+            Some(Fill::Gradient(gradient)) => self.gradient(content, prefix, &gradient),
+            Some(Fill::Image {
+                id,
+                transform,
+                width,
+                height,
+                alpha,
+                tex_type,
+            }) => match self.pattern(content, images, id, transform, width, height, tex_type) {
+                // Images are tinted with the paint alpha only
+                Some(pattern) => format!(r#"{0}="url(#{1})" {0}-opacity="{2}""#, prefix, pattern, alpha),
+                None => return,
+            },
+            Some(Fill::Pixels { x, y, pixels }) => {
+                let average = vector::average_color(pixels.buf());
+
+                match self.pixels_pattern(content, x, y, pixels) {
+                    Some(pattern) => format!(r#"{}="url(#{})""#, prefix, pattern),
+                    None => color_attributes(prefix, average),
+                }
+            }
+            None => return,
+        };
+
+        let scissor = self.scissor(content, params);
+        let area = shape.clip.as_ref().map(|area| self.clip_area(content, area));
+
+        let data = path.to_svg_data();
+        let clips = (scissor.as_deref(), area.as_deref());
+
+        let element = path_element(&data, &format!("{} {}", geometry, paint), clips, blend);
+
+        match operation {
+            Some(CompositeOperation::DestinationOver) => content.body.insert_str(0, &element),
+            Some(CompositeOperation::Copy) if !content.body.is_empty() => {
+                // What was drawn before is masked out where the shape is, then the shape is drawn over it
+                let cutout = path_element(&data, &format!(r#"{} {}="black""#, geometry, prefix), clips, "");
+                let mask = self.next_id("mask");
+
+                let _ = writeln!(
+                    content.defs,
+                    r#"<mask id="{}" maskUnits="userSpaceOnUse" x="0" y="0" width="{}" height="{}"><rect width="{}" height="{}" fill="white"/>{}</mask>"#,
+                    mask,
+                    size.w,
+                    size.h,
+                    size.w,
+                    size.h,
+                    cutout.trim_end()
+                );
+
+                content.body = format!("<g mask=\"url(#{})\">\n{}</g>\n{}", mask, content.body, element);
+            }
+            _ => content.body.push_str(&element),
+        }
+    }
+
+    // Defines the gradient and returns the paint attributes.
+    fn gradient(&mut self, content: &mut Content, prefix: &str, gradient: &Gradient) -> String {
+        let (element, geometry) = match gradient.shape {
+            GradientShape::Linear { x0, y0, x1, y1 } => (
+                "linearGradient",
+                format!(r#"x1="{}" y1="{}" x2="{}" y2="{}""#, x0, y0, x1, y1),
+            ),
+            GradientShape::Radial { r0, r1 } => (
+                "radialGradient",
+                format!(r#"cx="0" cy="0" fx="0" fy="0" fr="{}" r="{}""#, r0, r1),
+            ),
+        };
+
+        let spread = match gradient.spread {
+            SpreadMode::Pad => "pad",
+            SpreadMode::Repeat => "repeat",
+            SpreadMode::Reflect => "reflect",
+        };
+
+        let id = self.next_id("gradient");
+
+        let _ = write!(
+            content.defs,
+            r#"<{} id="{}" gradientUnits="userSpaceOnUse" {} spreadMethod="{}" gradientTransform="{}">"#,
+            element,
+            id,
+            geometry,
+            spread,
+            matrix(&gradient.transform)
+        );
+
+        for (offset, color) in &gradient.stops {
+            let _ = write!(
+                content.defs,
+                r#"<stop offset="{}" stop-color="{}" stop-opacity="{}"/>"#,
+                offset,
+                hex(*color),
+                color.a
+            );
+        }
+
+        let _ = writeln!(content.defs, "</{}>", element);
+
+        format!(r#"{}="url(#{})""#, prefix, id)
+    }
+
+    // Defines a pattern showing the image scaled to width and height and returns its id.
+    #[allow(clippy::too_many_arguments)]
+    fn pattern(
+        &mut self,
+        content: &mut Content,
+        images: &ImageStore<SvgImage>,
+        id: ImageId,
+        mut transform: Transform2D,
+        width: f32,
+        height: f32,
+        tex_type: f32,
+    ) -> Option<String> {
+        let image = images.get(id)?;
+        let info = image.pixels.info();
+        let flags = info.flags();
+
+        // Images that were render targets are shown with what was drawn to them
+        let image_element = if image.content.borrow().is_some() {
+            if flags.contains(ImageFlags::FLIP_Y) {
+                transform = vector::unflip(&transform, height);
+            }
+
+            content.images.push(id);
+
+            format!(
+                r##"<use href="#image{}" transform="scale({} {})"/>"##,
+                image.id,
+                width / info.width() as f32,
+                height / info.height() as f32
+            )
+        } else {
+            let pixels = image.pixels.pixels.borrow().to_img(false);
+            let data = png_data_uri(pixels, tex_type)?;
+
+            format!(
+                r#"<image width="{}" height="{}" preserveAspectRatio="none" href="{}"/>"#,
+                width, height, data
+            )
+        };
+
+        let (x, pattern_width) = if flags.contains(ImageFlags::REPEAT_X) {
+            (0.0, width)
+        } else {
+            (-PATTERN_EXTENT, PATTERN_EXTENT * 2.0)
+        };
+
+        let (y, pattern_height) = if flags.contains(ImageFlags::REPEAT_Y) {
+            (0.0, height)
+        } else {
+            (-PATTERN_EXTENT, PATTERN_EXTENT * 2.0)
+        };
+
+        let id = self.next_id("pattern");
+
+        // Pattern contents are positioned relative to the tile origin
+        let _ = writeln!(
+            content.defs,
+            r#"<pattern id="{}" patternUnits="userSpaceOnUse" x="{}" y="{}" width="{}" height="{}" patternTransform="{}"><g transform="translate({} {})">{}</g></pattern>"#,
+            id,
+            x,
+            y,
+            pattern_width,
+            pattern_height,
+            matrix(&transform),
+            -x,
+            -y,
+            image_element
+        );
+
+        Some(id)
+    }
+
+    // Defines a pattern showing premultiplied device pixels at x, y and returns its id.
+    fn pixels_pattern(&mut self, content: &mut Content, x: usize, y: usize, pixels: ImgVec<RGBA8>) -> Option<String> {
+        let (width, height) = (pixels.width(), pixels.height());
+        let data = png_data_uri(pixels, 0.0)?;
+
+        let id = self.next_id("pattern");
+
+        let _ = writeln!(
+            content.defs,
+            r#"<pattern id="{}" patternUnits="userSpaceOnUse" x="{}" y="{}" width="{}" height="{}"><image width="{}" height="{}" href="{}"/></pattern>"#,
+            id, x, y, width, height, width, height, data
+        );
+
+        Some(id)
+    }
+
+    // Returns the id of the clip path for the scissor of params, defining the clip path if needed.
+    fn scissor(&mut self, content: &mut Content, params: &Params) -> Option<String> {
+        let (transform, [ex, ey]) = vector::scissor(params)?;
+
+        let key = (transform.0, [ex, ey]);
+
+        match &content.last_scissor {
+            Some((last_key, id)) if *last_key == key => Some(id.clone()),
+            _ => {
+                let id = self.next_id("clip");
+
+                let _ = writeln!(
+                    content.defs,
+                    r#"<clipPath id="{}"><rect x="{}" y="{}" width="{}" height="{}" transform="{}"/></clipPath>"#,
+                    id,
+                    -ex,
+                    -ey,
+                    ex * 2.0,
+                    ey * 2.0,
+                    matrix(&transform)
+                );
+
+                content.last_scissor = Some((key, id.clone()));
+
+                Some(id)
+            }
+        }
+    }
+
+    // Returns the id of the clip path for the clip area, defining the clip path if needed.
+    fn clip_area(&mut self, content: &mut Content, area: &Arc<Path>) -> String {
+        if let Some((last_area, id)) = &content.last_clip_area {
+            if Arc::ptr_eq(last_area, area) {
+                return id.clone();
+            }
+        }
+
+        let id = self.next_id("clip");
+
+        // The contours of the clip area are marked solid or hole
+        let _ = writeln!(
+            content.defs,
+            r#"<clipPath id="{}"><path d="{}" clip-rule="nonzero"/></clipPath>"#,
+            id,
+            area.transformed(&Transform2D::identity(), true).to_svg_data()
+        );
+
+        content.last_clip_area = Some((area.clone(), id.clone()));
+
+        id
+    }
+}
+
+impl Renderer for SvgExport {
+    type Image = SvgImage;
+    type BufferCache = VoidCache;
+
+    fn alloc_buffer_cache(&self) -> Self::BufferCache {
+        Self::BufferCache::new()
+    }
+
+    fn view_size(&self) -> Size {
+        self.size
+    }
+
+    fn set_size(&mut self, width: u32, height: u32, dpi: f32) {
+        self.size = Size::new(width as f32, height as f32);
+        self.dpi = dpi;
+    }
+
+    fn render(
+        &mut self,
+        images: &ImageStore<SvgImage>,
+        _cache: &mut Self::BufferCache,
+        _verts: &[Vertex],
+        commands: &[Command],
+    ) {
+        self.unsupported = false;
+
+        let mut screen = Content::default();
+        let mut target = RenderTarget::Screen;
+
+        for cmd in commands {
+            if let CommandType::SetRenderTarget(new_target) = cmd.cmd_type() {
+                target = *new_target;
+                continue;
+            }
+
+            let mut image_content;
+
+            let (content, size) = match target {
+                RenderTarget::Image(id) => match images.get(id) {
+                    Some(image) => {
+                        image_content = image.content.borrow_mut();
+                        let info = image.pixels.info();
+
+                        (
+                            image_content.get_or_insert_with(Default::default),
+                            Size::new(info.width() as f32, info.height() as f32),
+                        )
+                    }
+                    None => continue,
+                },
+                _ => (&mut screen, self.size),
+            };
+
+            if let CommandType::ClearRect {
+                x,
+                y,
+                width,
+                height,
+                color,
+            } = *cmd.cmd_type()
+            {
+                // Clearing everything discards what was drawn before
+                if x == 0 && y == 0 && width as f32 >= size.w && height as f32 >= size.h {
+                    *content = Content::default();
+                }
+
+                if color.a > 0.0 {
+                    let _ = writeln!(
+                        content.body,
+                        r#"<rect x="{}" y="{}" width="{}" height="{}" {}/>"#,
+                        x,
+                        y,
+                        width,
+                        height,
+                        color_attributes("fill", color)
+                    );
+                }
+
+                continue;
+            }
+
+            self.draw(content, images, size, cmd);
+        }
+
+        let mut document = String::new();
+
+        let scale = if self.dpi > 0.0 { 1.0 / self.dpi } else { 1.0 };
+
+        let _ = writeln!(
+            document,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+            self.size.w * scale,
+            self.size.h * scale,
+            self.size.w,
+            self.size.h
+        );
+
+        document.push_str("<defs>\n");
+        document.push_str(&screen.defs);
+
+        // Contents of render target images that are shown by patterns, including the ones they show themselves
+        let mut defined = FnvHashSet::default();
+        let mut pending = screen.images.clone();
+
+        while let Some(id) = pending.pop() {
+            if !defined.insert(id) {
+                continue;
+            }
+
+            if let Some(image) = images.get(id) {
+                if let Some(content) = &*image.content.borrow() {
+                    document.push_str(&content.defs);
+                    let _ = writeln!(document, r#"<g id="image{}">"#, image.id);
+                    document.push_str(&content.body);
+                    document.push_str("</g>\n");
+
+                    pending.extend_from_slice(&content.images);
+                }
+            }
+        }
+
+        document.push_str("</defs>\n");
+        document.push_str(&screen.body);
+        document.push_str("</svg>\n");
+
+        self.document = document;
+    }
+
+    fn alloc_image(&mut self, info: ImageInfo) -> Result<Self::Image, ErrorKind> {
+        self.next_id += 1;
+
+        Ok(SvgImage {
+            id: self.next_id,
+            pixels: SoftwareImage::new(info),
+            content: RefCell::new(None),
+        })
+    }
+
+    fn start_capture(&self) {}
+
+    fn stop_capture(&self) {}
+
+    fn label(&self, _images: &ImageStore<Self::Image>, _id: ImageId) -> String {
+        "labels not supported for svg export backend".to_owned()
+    }
+
+    fn set_label(&self, _images: &ImageStore<Self::Image>, _id: ImageId, _label: &str) {}
+
+    fn update_image(
+        &mut self,
+        image: &mut Self::Image,
+        data: ImageSource,
+        x: usize,
+        y: usize,
+    ) -> Result<(), ErrorKind> {
+        // New pixels replace anything that was drawn to the image
+        *image.content.get_mut() = None;
+
+        image.pixels.update(data, x, y)
+    }
+
+    fn delete_image(&mut self, _image: Self::Image) {}
+
+    fn flip_y() -> bool {
+        false
+    }
+
+    fn flip_uv() -> bool {
+        false
+    }
+
+    fn is_vector() -> bool {
+        true
+    }
+
+    fn screenshot(&mut self, _images: &ImageStore<Self::Image>) -> Result<ImgVec<RGBA8>, ErrorKind> {
+        Ok(ImgVec::new(Vec::new(), 0, 0))
+    }
+}
+
+/// Image type of the [SvgExport](struct.SvgExport.html) renderer.
+pub struct SvgImage {
+    id: usize,
+    pixels: SoftwareImage,
+    // Elements drawn to the image while it was a render target. Used instead of the pixels when present.
+    content: RefCell<Option<Content>>,
+}
+
+// Scissor transform and extent of a clip path.
+type ClipKey = ([f32; 6], [f32; 2]);
+
+// Elements drawn to the screen or to a render target image.
+#[derive(Default)]
+struct Content {
+    defs: String,
+    body: String,
+    // Render target images shown by patterns in this content
+    images: Vec<ImageId>,
+    last_scissor: Option<(ClipKey, String)>,
+    last_clip_area: Option<(Arc<Path>, String)>,
+}
+
+fn matrix(t: &Transform2D) -> String {
+    format!("matrix({} {} {} {} {} {})", t[0], t[1], t[2], t[3], t[4], t[5])
+}
+
+fn hex(color: Color) -> String {
+    let channel = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    )
+}
+
+// Paint attributes of a solid color, the prefix is fill or stroke.
+fn color_attributes(prefix: &str, color: Color) -> String {
+    if color.a >= 1.0 {
+        format!(r#"{}="{}""#, prefix, hex(color))
+    } else {
+        format!(r#"{0}="{1}" {0}-opacity="{2}""#, prefix, hex(color), color.a)
+    }
+}
+
+fn stroke_attributes(paint: &Paint) -> String {
+    let cap = match paint.line_cap_start {
+        LineCap::Butt => "butt",
+        LineCap::Round => "round",
+        LineCap::Square => "square",
+    };
+
+    let join = match paint.line_join {
+        LineJoin::Miter => "miter",
+        LineJoin::Round => "round",
+        LineJoin::Bevel => "bevel",
+    };
+
+    let mut attributes = format!(
+        r#"fill="none" stroke-width="{}" stroke-linecap="{}" stroke-linejoin="{}" stroke-miterlimit="{}""#,
+        paint.line_width,
+        cap,
+        join,
+        paint.miter_limit.max(1.0)
+    );
+
+    let dashes = &paint.line_dash[..paint.line_dash_len];
+
+    if !dashes.is_empty() {
+        let dashes: Vec<String> = dashes.iter().map(|len| len.to_string()).collect();
+
+        let _ = write!(
+            attributes,
+            r#" stroke-dasharray="{}" stroke-dashoffset="{}""#,
+            dashes.join(" "),
+            paint.line_dash_offset
+        );
+    }
+
+    attributes
+}
+
+// A path element with the clip paths of the scissor and the clip area. When both apply the path is wrapped in a
+// group, as an element can have only one clip path.
+fn path_element(data: &str, attributes: &str, clips: (Option<&str>, Option<&str>), style: &str) -> String {
+    match clips {
+        (Some(scissor), Some(area)) => format!(
+            "<g clip-path=\"url(#{})\"{}><path d=\"{}\" {} clip-path=\"url(#{})\"/></g>\n",
+            scissor, style, data, attributes, area
+        ),
+        (Some(clip), None) | (None, Some(clip)) => format!(
+            "<path d=\"{}\" {} clip-path=\"url(#{})\"{}/>\n",
+            data, attributes, clip, style
+        ),
+        (None, None) => format!("<path d=\"{}\" {}{}/>\n", data, attributes, style),
+    }
+}
+
+// Encodes the pixels as a PNG data URI.
+#[cfg(feature = "image-loading")]
+fn png_data_uri(pixels: ImgVec<RGBA8>, tex_type: f32) -> Option<String> {
+    let mut data = Vec::with_capacity(pixels.buf().len() * 4);
+
+    for px in vector::straight_pixels(pixels.buf(), tex_type) {
+        data.extend_from_slice(&[px.r, px.g, px.b, px.a]);
+    }
+
+    let mut png = Vec::new();
+
+    image::png::PngEncoder::new(&mut png)
+        .encode(
+            &data,
+            pixels.width() as u32,
+            pixels.height() as u32,
+            image::ColorType::Rgba8,
+        )
+        .ok()?;
+
+    Some(format!("data:image/png;base64,{}", base64(&png)))
+}
+
+// Images can't be embedded without an encoder, shapes filled with them are left out.
+#[cfg(not(feature = "image-loading"))]
+fn png_data_uri(_pixels: ImgVec<RGBA8>, _tex_type: f32) -> Option<String> {
+    None
+}
+
+#[cfg(feature = "image-loading")]
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
@@ -0,0 +1,327 @@
+//! Conversion of canvas commands to the shapes and paints of vector formats. Shared by the renderers
+//! that write documents instead of pixels.
+
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+use crate::geometry::Transform2D;
+use crate::paint::{GradientColors, PaintFlavor};
+use crate::{Color, CompositeOperation, CompositeOperationState, ImageId, Size, SpreadMode, Verb};
+
+use super::software::{self, SoftwareImage};
+use super::{Command, CommandType, Params, VectorShape};
+
+/// Paint of a shape, colors have straight alpha.
+pub(super) enum Fill {
+    Color(Color),
+    Gradient(Gradient),
+    /// An image scaled to width and height in paint space.
+    Image {
+        id: ImageId,
+        transform: Transform2D,
+        width: f32,
+        height: f32,
+        alpha: f32,
+        tex_type: f32,
+    },
+    /// Premultiplied device pixels of a paint that has no equivalent in vector formats, covering the shape from
+    /// x, y.
+    Pixels {
+        x: usize,
+        y: usize,
+        pixels: ImgVec<RGBA8>,
+    },
+}
+
+pub(super) struct Gradient {
+    pub shape: GradientShape,
+    /// Maps gradient space to device space.
+    pub transform: Transform2D,
+    pub stops: Vec<(f32, Color)>,
+    pub spread: SpreadMode,
+}
+
+pub(super) enum GradientShape {
+    /// Runs from the start to the end point.
+    Linear { x0: f32, y0: f32, x1: f32, y1: f32 },
+    /// Runs between two circles around the origin of gradient space.
+    Radial { r0: f32, r1: f32 },
+}
+
+/// Returns the paint parameters and the shape of a fill or a stroke.
+pub(super) fn shape(cmd: &Command) -> Option<(&Params, &VectorShape)> {
+    let params = match cmd.cmd_type() {
+        CommandType::ConvexFill { params } => params,
+        CommandType::ConcaveFill { fill_params, .. } => fill_params,
+        CommandType::Stroke { params } | CommandType::StencilStroke { params1: params, .. } => params,
+        _ => return None,
+    };
+
+    let shape = cmd.vector_shape.as_deref()?;
+
+    if shape.path.is_empty() {
+        None
+    } else {
+        Some((params, shape))
+    }
+}
+
+/// Returns the paint of a shape. `image` looks up the color ramp images of multi stop gradients.
+///
+/// Box and conic gradients have no equivalent in vector formats, they are shaded like the software renderer
+/// does for the pixels of the target `size` the shape covers. Images drawn through image filters are drawn
+/// unfiltered.
+pub(super) fn fill<'a>(
+    params: &Params,
+    cmd: &Command,
+    shape: &VectorShape,
+    size: Size,
+    image: impl FnOnce(ImageId) -> Option<&'a SoftwareImage>,
+) -> Option<Fill> {
+    let paint = &shape.paint;
+
+    let (shape_kind, transform, colors) = match paint.flavor {
+        PaintFlavor::Color(color) => return Some(Fill::Color(color)),
+        PaintFlavor::LinearGradient {
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            colors,
+        } => (
+            GradientShape::Linear {
+                x0: start_x,
+                y0: start_y,
+                x1: end_x,
+                y1: end_y,
+            },
+            paint.transform,
+            colors,
+        ),
+        PaintFlavor::RadialGradient {
+            cx,
+            cy,
+            in_radius,
+            out_radius,
+            colors,
+        } => {
+            let mut transform = Transform2D::new_translation(cx, cy);
+            transform.multiply(&paint.transform);
+
+            let shape = GradientShape::Radial {
+                r0: in_radius,
+                r1: out_radius,
+            };
+
+            (shape, transform, colors)
+        }
+        PaintFlavor::BoxGradient { .. } | PaintFlavor::ConicGradient { .. } => {
+            let ramp = cmd.image().and_then(image);
+
+            return pixels(params, ramp, shape, size);
+        }
+        PaintFlavor::Image { .. } | PaintFlavor::FilterImage { .. } | PaintFlavor::BlurImage { .. } => {
+            let [width, height] = params.extent();
+
+            return Some(Fill::Image {
+                id: cmd.image()?,
+                transform: transform_from_mat(params.paint_mat()).inversed(),
+                width,
+                height,
+                alpha: params.inner_color().a,
+                tex_type: params.tex_type(),
+            });
+        }
+    };
+
+    let mut stops = match colors {
+        GradientColors::TwoStop { start_color, end_color } => vec![(0.0, start_color), (1.0, end_color)],
+        GradientColors::MultiStop { stops, len } => stops[..len].to_vec(),
+    };
+
+    let shape_kind = match shape_kind {
+        // Radii can't be negative, start at the center with the color the gradient has there
+        GradientShape::Radial { r0, r1 } if r0 < 0.0 && r1 > 0.0 => {
+            let t = -r0 / (r1 - r0);
+
+            let mut clipped = vec![(0.0, sample_stops(&stops, t))];
+            clipped.extend(
+                stops
+                    .iter()
+                    .filter(|(offset, _)| *offset > t)
+                    .map(|(offset, color)| ((offset - t) / (1.0 - t), *color)),
+            );
+
+            stops = clipped;
+
+            GradientShape::Radial { r0: 0.0, r1 }
+        }
+        shape_kind => shape_kind,
+    };
+
+    Some(Fill::Gradient(Gradient {
+        shape: shape_kind,
+        transform,
+        stops,
+        spread: paint.spread_mode,
+    }))
+}
+
+/// Returns the operation the blend factors of a command were set up for, or None for factors set with
+/// `Canvas::global_composite_blend_func`.
+pub(super) fn composite_operation(state: CompositeOperationState) -> Option<CompositeOperation> {
+    use CompositeOperation::*;
+
+    [
+        SourceOver,
+        SourceIn,
+        SourceOut,
+        Atop,
+        DestinationOver,
+        DestinationIn,
+        DestinationOut,
+        DestinationAtop,
+        Lighter,
+        Copy,
+        Xor,
+    ]
+    .iter()
+    .copied()
+    .find(|op| CompositeOperationState::new(*op) == state)
+}
+
+/// Returns the transform from scissor space to device space and the half extents of the scissor rectangle.
+pub(super) fn scissor(params: &Params) -> Option<(Transform2D, [f32; 2])> {
+    let scissor_mat = params.scissor_mat();
+
+    // Without a scissor the matrix is left zeroed
+    if scissor_mat.iter().all(|value| *value == 0.0) {
+        return None;
+    }
+
+    Some((transform_from_mat(scissor_mat).inversed(), params.scissor_extent()))
+}
+
+/// Undoes the flip of the paint transform of images with the FLIP_Y flag, for showing what was drawn to a render
+/// target in the orientation it was drawn in.
+pub(super) fn unflip(transform: &Transform2D, height: f32) -> Transform2D {
+    let mut flip = Transform2D([1.0, 0.0, 0.0, -1.0, 0.0, height]);
+    flip.multiply(transform);
+    flip
+}
+
+/// Converts image pixels to straight alpha RGBA. The texture type tells how the shaders read them.
+pub(super) fn straight_pixels(pixels: &[RGBA8], tex_type: f32) -> Vec<RGBA8> {
+    pixels
+        .iter()
+        .map(|px| {
+            if tex_type == 2.0 {
+                // Single channel images are alpha masks of white
+                RGBA8::new(255, 255, 255, px.r)
+            } else if tex_type == 0.0 && px.a > 0 {
+                let unpremultiply = |value: u8| ((value as u32 * 255 + px.a as u32 / 2) / px.a as u32).min(255) as u8;
+                RGBA8::new(unpremultiply(px.r), unpremultiply(px.g), unpremultiply(px.b), px.a)
+            } else {
+                *px
+            }
+        })
+        .collect()
+}
+
+/// Returns the straight alpha average color of premultiplied pixels.
+pub(super) fn average_color(pixels: &[RGBA8]) -> Color {
+    let mut sum = [0.0f32; 4];
+
+    for px in pixels {
+        for (channel, value) in sum.iter_mut().zip(&[px.r, px.g, px.b, px.a]) {
+            *channel += *value as f32 / 255.0;
+        }
+    }
+
+    if sum[3] > 0.0 {
+        Color::rgbaf(
+            sum[0] / sum[3],
+            sum[1] / sum[3],
+            sum[2] / sum[3],
+            sum[3] / pixels.len() as f32,
+        )
+    } else {
+        Color::rgbaf(0.0, 0.0, 0.0, 0.0)
+    }
+}
+
+// Shades the pixels of the target the shape covers.
+fn pixels(params: &Params, ramp: Option<&SoftwareImage>, shape: &VectorShape, size: Size) -> Option<Fill> {
+    let mut bounds = [f32::MAX, f32::MAX, f32::MIN, f32::MIN];
+
+    // The control points of the curves enclose them
+    for verb in shape.path.verbs() {
+        let (points, len) = match verb {
+            Verb::MoveTo(x, y) | Verb::LineTo(x, y) => ([x, y, 0.0, 0.0, 0.0, 0.0], 2),
+            Verb::BezierTo(c1x, c1y, c2x, c2y, x, y) => ([c1x, c1y, c2x, c2y, x, y], 6),
+            Verb::Close | Verb::Solid | Verb::Hole => ([0.0; 6], 0),
+        };
+
+        for point in points[..len].chunks_exact(2) {
+            bounds = [
+                bounds[0].min(point[0]),
+                bounds[1].min(point[1]),
+                bounds[2].max(point[0]),
+                bounds[3].max(point[1]),
+            ];
+        }
+    }
+
+    // Miter joins reach furthest out of the line
+    let margin = if shape.stroke {
+        shape.paint.line_width * 0.5 * shape.paint.miter_limit.max(1.0)
+    } else {
+        0.0
+    };
+
+    let x0 = (bounds[0] - margin).floor().max(0.0) as usize;
+    let y0 = (bounds[1] - margin).floor().max(0.0) as usize;
+    let x1 = (bounds[2] + margin).ceil().min(size.w) as usize;
+    let y1 = (bounds[3] + margin).ceil().min(size.h) as usize;
+
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+
+    Some(Fill::Pixels {
+        x: x0,
+        y: y0,
+        pixels: software::shade_rect(params, ramp, x0, y0, x1 - x0, y1 - y0),
+    })
+}
+
+fn transform_from_mat(mat: [f32; 12]) -> Transform2D {
+    Transform2D([mat[0], mat[1], mat[4], mat[5], mat[8], mat[9]])
+}
+
+// Color of a gradient at t.
+fn sample_stops(stops: &[(f32, Color)], t: f32) -> Color {
+    let next = stops
+        .iter()
+        .position(|(offset, _)| *offset >= t)
+        .unwrap_or(stops.len() - 1);
+
+    if next == 0 {
+        return stops[0].1;
+    }
+
+    let (o0, c0) = stops[next - 1];
+    let (o1, c1) = stops[next];
+    let f = if o1 > o0 {
+        ((t - o0) / (o1 - o0)).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+
+    Color::rgbaf(
+        c0.r + (c1.r - c0.r) * f,
+        c0.g + (c1.g - c0.g) * f,
+        c0.b + (c1.b - c0.b) * f,
+        c0.a + (c1.a - c0.a) * f,
+    )
+}
@@ -0,0 +1,461 @@
+//! Loading and drawing of SVG documents. Requires the `svg` feature.
+//!
+//! Documents are parsed with [usvg](https://docs.rs/usvg), which resolves styles, units and basic shapes
+//! into plain paths. They are then converted into a retained list of paths and paints that can be drawn
+//! every frame with [Canvas::draw_svg](../struct.Canvas.html#method.draw_svg).
+//!
+//! Fills and strokes with colors, linear and radial gradients are supported, as are groups with opacity.
+//! Clip paths, masks, filters, patterns and embedded images are ignored.
+
+use std::path::Path as FilePath;
+
+use crate::geometry::Bounds;
+use crate::path::PathCache;
+use crate::{
+    transformed_bounds, Canvas, Color, CompositeOperation, ErrorKind, FillRule, LineCap, LineJoin, Paint, Path, Rect,
+    Renderer, Size, SpreadMode, Transform2D,
+};
+
+/// A parsed SVG document.
+#[derive(Clone, Debug)]
+pub struct Document {
+    size: Size,
+    items: Vec<Item>,
+}
+
+impl Document {
+    /// Loads and parses the SVG file at the specified path.
+    pub fn load<P: AsRef<FilePath>>(file_path: P) -> Result<Self, ErrorKind> {
+        let tree = usvg::Tree::from_file(file_path, &usvg::Options::default())?;
+
+        Ok(Self::from_tree(&tree))
+    }
+
+    /// Parses an SVG document from memory.
+    pub fn load_mem(data: &[u8]) -> Result<Self, ErrorKind> {
+        let tree = usvg::Tree::from_data(data, &usvg::Options::default())?;
+
+        Ok(Self::from_tree(&tree))
+    }
+
+    /// Converts an already parsed usvg tree.
+    pub fn from_tree(tree: &usvg::Tree) -> Self {
+        let svg = tree.svg_node();
+        let size = Size::new(svg.size.width() as f32, svg.size.height() as f32);
+        let transform = view_box_transform(&svg.view_box, size);
+
+        let mut items = Vec::new();
+        convert_children(tree, &tree.root(), transform, &mut items);
+
+        Self { size, items }
+    }
+
+    /// Size of the document in user units, as given by its `width` and `height` attributes.
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// The top level items in drawing order.
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut [Item] {
+        &mut self.items
+    }
+}
+
+/// An element of a [Document](struct.Document.html).
+// Shapes are by far the most common items, boxing them would only add an indirection.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+pub enum Item {
+    Shape(Shape),
+    Group(Group),
+}
+
+/// A path with its fill and stroke paints.
+#[derive(Clone, Debug)]
+pub struct Shape {
+    pub path: Path,
+    pub fill: Option<Paint>,
+    pub stroke: Option<Paint>,
+    /// Transform from the path coordinates to the coordinates of the enclosing group or document.
+    pub transform: Transform2D,
+    pub opacity: f32,
+}
+
+/// Items that are drawn to an offscreen image first, which is then drawn with the group opacity.
+///
+/// Groups without opacity are not kept, their items are merged into the enclosing list.
+#[derive(Clone, Debug)]
+pub struct Group {
+    /// Transform from the group coordinates to the coordinates of the enclosing group or document.
+    pub transform: Transform2D,
+    pub opacity: f32,
+    pub items: Vec<Item>,
+}
+
+impl<T> Canvas<T>
+where
+    T: Renderer + 'static,
+{
+    /// Draws an SVG document with its top left corner at the origin of the current coordinate system.
+    pub fn draw_svg(&mut self, document: &mut Document) {
+        self.draw_svg_items(&mut document.items);
+    }
+
+    fn draw_svg_items(&mut self, items: &mut [Item]) {
+        for item in items {
+            match item {
+                Item::Shape(shape) => {
+                    self.save();
+                    self.premultiply_transform(shape.transform);
+                    self.set_global_alpha(self.state().alpha * shape.opacity);
+
+                    if let Some(fill) = shape.fill {
+                        self.fill_path(&mut shape.path, fill);
+                    }
+
+                    if let Some(stroke) = shape.stroke {
+                        self.stroke_path(&mut shape.path, stroke);
+                    }
+
+                    self.restore();
+                }
+                Item::Group(group) => self.draw_svg_group(group),
+            }
+        }
+    }
+
+    fn draw_svg_group(&mut self, group: &mut Group) {
+        // Group opacity applies to the group as a whole, so it's drawn in a layer covering its items
+        self.save();
+        self.premultiply_transform(group.transform);
+        let device = self.svg_items_bounds(&mut group.items);
+        self.restore();
+
+        if device.minx > device.maxx || device.miny > device.maxy {
+            return;
+        }
+
+        let mut inverse = self.transform();
+        inverse.inverse();
+
+        let device_rect = Rect::new(
+            device.minx,
+            device.miny,
+            device.maxx - device.minx,
+            device.maxy - device.miny,
+        );
+        let bounds = transformed_bounds(&inverse, device_rect);
+
+        if self
+            .begin_layer(bounds, group.opacity, CompositeOperation::SourceOver)
+            .is_err()
+        {
+            return;
+        }
+
+        self.premultiply_transform(group.transform);
+        self.draw_svg_items(&mut group.items);
+
+        self.end_layer();
+    }
+
+    // Returns the area the items cover in device space, including strokes and the antialiasing fringe.
+    fn svg_items_bounds(&mut self, items: &mut [Item]) -> Bounds {
+        let mut bounds = Bounds::default();
+
+        for item in items {
+            self.save();
+
+            let item_bounds = match item {
+                Item::Shape(shape) => {
+                    self.premultiply_transform(shape.transform);
+
+                    let mut shape_bounds = self.path_bbox(&mut shape.path);
+                    let mut margin = 1.0;
+
+                    if let Some(stroke) = shape.stroke {
+                        // Square caps reach half the diagonal past the path, miter joins up to the miter limit
+                        let mut reach = std::f32::consts::SQRT_2;
+
+                        if stroke.line_join() == LineJoin::Miter {
+                            reach = reach.max(stroke.miter_limit());
+                        }
+
+                        margin += stroke.line_width() * 0.5 * reach * self.transform().average_scale();
+                    }
+
+                    if shape_bounds.minx <= shape_bounds.maxx && shape_bounds.miny <= shape_bounds.maxy {
+                        shape_bounds.minx -= margin;
+                        shape_bounds.miny -= margin;
+                        shape_bounds.maxx += margin;
+                        shape_bounds.maxy += margin;
+                    }
+
+                    shape_bounds
+                }
+                Item::Group(group) => {
+                    self.premultiply_transform(group.transform);
+                    self.svg_items_bounds(&mut group.items)
+                }
+            };
+
+            self.restore();
+
+            bounds.minx = bounds.minx.min(item_bounds.minx);
+            bounds.miny = bounds.miny.min(item_bounds.miny);
+            bounds.maxx = bounds.maxx.max(item_bounds.maxx);
+            bounds.maxy = bounds.maxy.max(item_bounds.maxy);
+        }
+
+        bounds
+    }
+}
+
+fn convert_children(tree: &usvg::Tree, node: &usvg::Node, transform: Transform2D, items: &mut Vec<Item>) {
+    for child in node.children() {
+        match *child.borrow() {
+            usvg::NodeKind::Path(ref path) => {
+                if let Some(shape) = convert_path(tree, path, &transform) {
+                    items.push(Item::Shape(shape));
+                }
+            }
+            usvg::NodeKind::Group(ref group) => {
+                let mut group_transform = convert_transform(&group.transform);
+                group_transform.multiply(&transform);
+
+                let opacity = group.opacity.value() as f32;
+
+                if opacity >= 1.0 {
+                    convert_children(tree, &child, group_transform, items);
+                    continue;
+                }
+
+                let mut group_items = Vec::new();
+                convert_children(tree, &child, Transform2D::identity(), &mut group_items);
+
+                // A shape with a single paint looks the same drawn with global alpha, so skip the offscreen image.
+                let single_paint = match group_items.as_slice() {
+                    [Item::Shape(shape)] => shape.fill.is_none() || shape.stroke.is_none(),
+                    _ => false,
+                };
+
+                if single_paint {
+                    if let Some(Item::Shape(mut shape)) = group_items.pop() {
+                        shape.transform.multiply(&group_transform);
+                        shape.opacity *= opacity;
+                        items.push(Item::Shape(shape));
+                    }
+                } else if !group_items.is_empty() {
+                    items.push(Item::Group(Group {
+                        transform: group_transform,
+                        opacity,
+                        items: group_items,
+                    }));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn convert_path(tree: &usvg::Tree, svg_path: &usvg::Path, parent_transform: &Transform2D) -> Option<Shape> {
+    if svg_path.visibility != usvg::Visibility::Visible {
+        return None;
+    }
+
+    let mut path = Path::new();
+
+    for segment in svg_path.data.iter() {
+        match *segment {
+            usvg::PathSegment::MoveTo { x, y } => path.move_to(x as f32, y as f32),
+            usvg::PathSegment::LineTo { x, y } => path.line_to(x as f32, y as f32),
+            usvg::PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                path.bezier_to(x1 as f32, y1 as f32, x2 as f32, y2 as f32, x as f32, y as f32)
+            }
+            usvg::PathSegment::ClosePath => path.close(),
+        }
+    }
+
+    let bounds = PathCache::new(path.verbs(), &Transform2D::identity(), 0.25, 0.01).bounds;
+    let bounds = [
+        bounds.minx,
+        bounds.miny,
+        bounds.maxx - bounds.minx,
+        bounds.maxy - bounds.miny,
+    ];
+
+    let fill = svg_path.fill.as_ref().and_then(|fill| {
+        let mut paint = convert_paint(tree, &fill.paint, fill.opacity.value() as f32, bounds)?;
+
+        paint.set_fill_rule(match fill.rule {
+            usvg::FillRule::NonZero => FillRule::NonZero,
+            usvg::FillRule::EvenOdd => FillRule::EvenOdd,
+        });
+
+        Some(paint)
+    });
+
+    let stroke = svg_path.stroke.as_ref().and_then(|stroke| {
+        let mut paint = convert_paint(tree, &stroke.paint, stroke.opacity.value() as f32, bounds)?;
+
+        paint.set_line_width(stroke.width.value() as f32);
+        paint.set_miter_limit(stroke.miterlimit.value() as f32);
+
+        paint.set_line_cap(match stroke.linecap {
+            usvg::LineCap::Butt => LineCap::Butt,
+            usvg::LineCap::Round => LineCap::Round,
+            usvg::LineCap::Square => LineCap::Square,
+        });
+
+        paint.set_line_join(match stroke.linejoin {
+            usvg::LineJoin::Miter => LineJoin::Miter,
+            usvg::LineJoin::Round => LineJoin::Round,
+            usvg::LineJoin::Bevel => LineJoin::Bevel,
+        });
+
+        if let Some(dasharray) = &stroke.dasharray {
+            let dashes: Vec<f32> = dasharray.iter().map(|len| *len as f32).collect();
+            paint.set_line_dash(&dashes);
+            paint.set_line_dash_offset(stroke.dashoffset);
+        }
+
+        Some(paint)
+    });
+
+    if fill.is_none() && stroke.is_none() {
+        return None;
+    }
+
+    let mut transform = convert_transform(&svg_path.transform);
+    transform.multiply(parent_transform);
+
+    Some(Shape {
+        path,
+        fill,
+        stroke,
+        transform,
+        opacity: 1.0,
+    })
+}
+
+// Bounds are given as [x, y, width, height] and are needed for gradients in object bounding box units.
+fn convert_paint(tree: &usvg::Tree, paint: &usvg::Paint, opacity: f32, bounds: [f32; 4]) -> Option<Paint> {
+    let id = match paint {
+        usvg::Paint::Color(color) => return Some(Paint::color(convert_color(*color, opacity))),
+        usvg::Paint::Link(id) => id,
+    };
+
+    let node = tree.defs_by_id(id)?;
+
+    let kind = node.borrow();
+
+    match *kind {
+        usvg::NodeKind::LinearGradient(ref gradient) => {
+            let stops = convert_stops(&gradient.base.stops, opacity);
+
+            let paint = Paint::linear_gradient_stops(
+                gradient.x1 as f32,
+                gradient.y1 as f32,
+                gradient.x2 as f32,
+                gradient.y2 as f32,
+                &stops,
+            );
+
+            apply_gradient_base(paint, &gradient.base, bounds)
+        }
+        usvg::NodeKind::RadialGradient(ref gradient) => {
+            // The focal point is not supported, the gradient is centered at (cx, cy).
+            let stops = convert_stops(&gradient.base.stops, opacity);
+
+            let paint = Paint::radial_gradient_stops(
+                gradient.cx as f32,
+                gradient.cy as f32,
+                0.0,
+                gradient.r.value() as f32,
+                &stops,
+            );
+
+            apply_gradient_base(paint, &gradient.base, bounds)
+        }
+        _ => None,
+    }
+}
+
+fn apply_gradient_base(mut paint: Paint, base: &usvg::BaseGradient, bounds: [f32; 4]) -> Option<Paint> {
+    paint.set_spread_mode(match base.spread_method {
+        usvg::SpreadMethod::Pad => SpreadMode::Pad,
+        usvg::SpreadMethod::Reflect => SpreadMode::Reflect,
+        usvg::SpreadMethod::Repeat => SpreadMode::Repeat,
+    });
+
+    paint.transform = convert_transform(&base.transform);
+
+    if base.units == usvg::Units::ObjectBoundingBox {
+        let [x, y, width, height] = bounds;
+
+        // Bounding box units are undefined for shapes without area.
+        if width <= 0.0 || height <= 0.0 {
+            return None;
+        }
+
+        paint.transform.multiply(&Transform2D([width, 0.0, 0.0, height, x, y]));
+    }
+
+    Some(paint)
+}
+
+fn convert_stops(stops: &[usvg::Stop], opacity: f32) -> Vec<(f32, Color)> {
+    stops
+        .iter()
+        .map(|stop| {
+            let color = convert_color(stop.color, stop.opacity.value() as f32 * opacity);
+
+            (stop.offset.value() as f32, color)
+        })
+        .collect()
+}
+
+fn convert_color(color: usvg::Color, opacity: f32) -> Color {
+    let mut new = Color::rgb(color.red, color.green, color.blue);
+    new.set_alphaf(opacity);
+    new
+}
+
+fn convert_transform(transform: &usvg::Transform) -> Transform2D {
+    Transform2D([
+        transform.a as f32,
+        transform.b as f32,
+        transform.c as f32,
+        transform.d as f32,
+        transform.e as f32,
+        transform.f as f32,
+    ])
+}
+
+// Maps the view box to the document size. Alignments other than none are centered like the default xMidYMid.
+fn view_box_transform(view_box: &usvg::ViewBox, size: Size) -> Transform2D {
+    let rect = view_box.rect;
+
+    if rect.width() <= 0.0 || rect.height() <= 0.0 {
+        return Transform2D::identity();
+    }
+
+    let (vw, vh) = (rect.width() as f32, rect.height() as f32);
+    let mut sx = size.w / vw;
+    let mut sy = size.h / vh;
+
+    if view_box.aspect.align != usvg::Align::None {
+        let s = if view_box.aspect.slice { sx.max(sy) } else { sx.min(sy) };
+        sx = s;
+        sy = s;
+    }
+
+    let tx = (size.w - vw * sx) * 0.5 - rect.x() as f32 * sx;
+    let ty = (size.h - vh * sy) * 0.5 - rect.y() as f32 * sy;
+
+    Transform2D([sx, 0.0, 0.0, sy, tx, ty])
+}
@@ -1,6 +1,7 @@
 use std::ffi::OsStr;
 use std::fs;
 use std::hash::{Hash, Hasher};
+use std::ops::Range;
 use std::path::Path as FilePath;
 
 use fnv::{FnvBuildHasher, FnvHashMap, FnvHasher};
@@ -11,6 +12,9 @@ use lru::LruCache;
 use unicode_bidi::BidiInfo;
 use unicode_segmentation::UnicodeSegmentation;
 
+use crate::geometry::Transform2D;
+use crate::path::{Overlap, PathCache};
+use crate::renderer::VectorGlyph;
 use crate::{
     Canvas, Color, ErrorKind, FillRule, ImageFlags, ImageId, ImageInfo, ImageStore, Paint, Path, PixelFormat,
     RenderTarget, Renderer,
@@ -20,8 +24,12 @@ mod atlas;
 pub use atlas::Atlas;
 
 mod font;
-use font::Font;
 pub use font::FontMetrics;
+use font::{variations_key, Font, VariationsKey};
+
+mod paragraph;
+pub(crate) use paragraph::layout_paragraph;
+pub use paragraph::{Paragraph, TextLine};
 
 const GLYPH_PADDING: u32 = 1;
 const GLYPH_MARGIN: u32 = 1;
@@ -29,7 +37,7 @@ const TEXTURE_SIZE: usize = 512;
 const LRU_CACHE_CAPACITY: usize = 1000;
 
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
-#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Baseline {
     /// The text baseline is the top of the em square.
     Top,
@@ -48,7 +56,7 @@ impl Default for Baseline {
 }
 
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
-#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Align {
     /// The text is left-aligned.
     Left,
@@ -64,6 +72,34 @@ impl Default for Align {
     }
 }
 
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TextDirection {
+    /// Paragraphs are left-to-right. Default value.
+    Ltr,
+    /// Paragraphs are right-to-left.
+    Rtl,
+    /// The direction of each paragraph is the direction of its first strong character.
+    Auto,
+}
+
+impl Default for TextDirection {
+    fn default() -> Self {
+        Self::Ltr
+    }
+}
+
+impl TextDirection {
+    // The paragraph embedding level to resolve the bidi levels with, none finds it from the text.
+    fn level(self) -> Option<unicode_bidi::Level> {
+        match self {
+            Self::Ltr => Some(unicode_bidi::Level::ltr()),
+            Self::Rtl => Some(unicode_bidi::Level::rtl()),
+            Self::Auto => None,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub enum RenderMode {
     Fill,
@@ -83,6 +119,7 @@ struct RenderedGlyphId {
     size: u32,
     line_width: u32,
     render_mode: RenderMode,
+    variations: VariationsKey,
 }
 
 impl RenderedGlyphId {
@@ -93,6 +130,7 @@ impl RenderedGlyphId {
             size: (paint.font_size * 10.0).trunc() as u32,
             line_width: (paint.line_width * 10.0).trunc() as u32,
             render_mode: mode,
+            variations: variations_key(&paint.font_variations),
         }
     }
 }
@@ -136,6 +174,9 @@ struct ShapingId {
     size: u32,
     word_hash: u64,
     font_ids: [Option<FontId>; 8],
+    direction: TextDirection,
+    features_hash: u64,
+    variations: VariationsKey,
 }
 
 impl ShapingId {
@@ -143,10 +184,18 @@ impl ShapingId {
         let mut hasher = FnvHasher::default();
         word.hash(&mut hasher);
 
+        let mut features_hasher = FnvHasher::default();
+        paint.text_script.hash(&mut features_hasher);
+        paint.text_language.hash(&mut features_hasher);
+        paint.font_features.hash(&mut features_hasher);
+
         Self {
             size: (paint.font_size * 10.0).trunc() as u32,
             word_hash: hasher.finish(),
             font_ids: paint.font_ids,
+            direction: paint.text_direction,
+            features_hash: features_hasher.finish(),
+            variations: variations_key(&paint.font_variations),
         }
     }
 }
@@ -160,7 +209,24 @@ struct FontTexture {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct FontId(Index);
+pub struct FontId(pub(crate) Index);
+
+// Serialized as the raw arena index, which only identifies the font on the canvas it was added to.
+#[cfg(feature = "serde")]
+impl serde::Serialize for FontId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.0.into_raw_parts(), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FontId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (index, generation) = serde::Deserialize::deserialize(deserializer)?;
+
+        Ok(Self(Index::from_raw_parts(index, generation)))
+    }
+}
 
 pub(crate) struct TextContext {
     fonts: Arena<Font>,
@@ -235,6 +301,14 @@ impl TextContext {
         self.fonts.get_mut(id.0)
     }
 
+    /// Finds a font by the name Font::name returns for it.
+    pub fn font_by_name(&self, name: &str) -> Option<FontId> {
+        self.fonts
+            .iter()
+            .find(|(_, font)| font.name().as_deref() == Some(name))
+            .map(|(index, _)| FontId(index))
+    }
+
     pub fn find_font<F, T>(&mut self, _text: &str, paint: &Paint, mut callback: F) -> Result<T, ErrorKind>
     where
         F: FnMut((FontId, &mut Font)) -> (bool, T),
@@ -339,6 +413,7 @@ pub(crate) fn shape(
     Err(ErrorKind::UnknownError)
 }
 
+// Shapes the text, splitting it into bidi paragraphs and the paragraphs into runs of one direction in visual order.
 fn shape_run(
     context: &mut TextContext,
     paint: &Paint,
@@ -354,80 +429,105 @@ fn shape_run(
         final_byte_index: 0,
     };
 
-    let bidi_info = BidiInfo::new(&text, Some(unicode_bidi::Level::ltr()));
+    let bidi_info = BidiInfo::new(&text, paint.text_direction.level());
 
-    if let Some(paragraph) = bidi_info.paragraphs.get(0) {
+    for paragraph in &bidi_info.paragraphs {
         let line = paragraph.range.clone();
 
-        let (levels, runs) = bidi_info.visual_runs(&paragraph, line);
+        let (levels, runs) = bidi_info.visual_runs(paragraph, line);
 
         for run in runs.iter() {
-            let sub_text = &text[run.clone()];
+            let rtl = levels[run.start].is_rtl();
 
-            if sub_text.is_empty() {
-                continue;
+            if shape_words(context, paint, text, run.clone(), rtl, max_width, &mut result) {
+                return Ok(result);
             }
+        }
 
-            let hb_direction = if levels[run.start].is_rtl() {
-                // hb::Direction::Rtl
-                rustybuzz::Direction::RightToLeft
-            } else {
-                // hb::Direction::Ltr
-                rustybuzz::Direction::LeftToRight
-            };
-
-            let mut words = Vec::new();
-            let mut word_break_reached = false;
-            let mut byte_index = run.start;
+        // Breaking text to a width stops at the end of the paragraph like at a line that's full
+        if max_width.is_some() {
+            break;
+        }
+    }
 
-            for word in sub_text.split_word_bounds() {
-                let id = ShapingId::new(paint, word);
+    Ok(result)
+}
 
-                if !context.shaped_words_cache.contains(&id) {
-                    let word = shape_word(word, hb_direction, context, paint);
-                    context.shaped_words_cache.put(id, word);
-                }
+// Shapes the words of a range of the text that has a single direction, adding their glyphs in visual order
+// to the result. Returns true when it stopped before a word that doesn't fit max_width.
+fn shape_words(
+    context: &mut TextContext,
+    paint: &Paint,
+    text: &str,
+    range: Range<usize>,
+    rtl: bool,
+    max_width: Option<f32>,
+    result: &mut TextMetrics,
+) -> bool {
+    let sub_text = &text[range.clone()];
 
-                if let Some(Ok(word)) = context.shaped_words_cache.get(&id) {
-                    let mut word = word.clone();
+    if sub_text.is_empty() {
+        return false;
+    }
 
-                    if let Some(max_width) = max_width {
-                        if result.width + word.width >= max_width {
-                            word_break_reached = true;
-                            break;
-                        }
-                    }
+    let (hb_direction, text_direction) = if rtl {
+        // hb::Direction::Rtl
+        (rustybuzz::Direction::RightToLeft, TextDirection::Rtl)
+    } else {
+        // hb::Direction::Ltr
+        (rustybuzz::Direction::LeftToRight, TextDirection::Ltr)
+    };
 
-                    result.width += word.width;
+    // Words are cached by the direction they were shaped in
+    let mut word_paint = *paint;
+    word_paint.text_direction = text_direction;
 
-                    for glyph in &mut word.glyphs {
-                        glyph.byte_index += byte_index;
-                        debug_assert!(text.get(glyph.byte_index..).is_some());
-                    }
+    let mut words = Vec::new();
+    let mut word_break_reached = false;
+    let mut byte_index = range.start;
 
-                    words.push(word);
-                }
+    for word in sub_text.split_word_bounds() {
+        let id = ShapingId::new(&word_paint, word);
 
-                byte_index += word.len();
-            }
+        if !context.shaped_words_cache.contains(&id) {
+            let word = shape_word(word, hb_direction, context, paint);
+            context.shaped_words_cache.put(id, word);
+        }
 
-            if levels[run.start].is_rtl() {
-                words.reverse();
-            }
+        if let Some(Ok(word)) = context.shaped_words_cache.get(&id) {
+            let mut word = word.clone();
 
-            for word in words {
-                result.glyphs.extend(word.glyphs.clone());
+            if let Some(max_width) = max_width {
+                if result.width + word.width >= max_width {
+                    word_break_reached = true;
+                    break;
+                }
             }
 
-            result.final_byte_index = byte_index;
+            result.width += word.width;
 
-            if word_break_reached {
-                break;
+            for glyph in &mut word.glyphs {
+                glyph.byte_index += byte_index;
+                debug_assert!(text.get(glyph.byte_index..).is_some());
             }
+
+            words.push(word);
         }
+
+        byte_index += word.len();
     }
 
-    Ok(result)
+    if rtl {
+        words.reverse();
+    }
+
+    for word in words {
+        result.glyphs.extend(word.glyphs.clone());
+    }
+
+    result.final_byte_index = byte_index;
+
+    word_break_reached
 }
 
 fn shape_word(
@@ -444,7 +544,7 @@ fn shape_word(
             // TODO: It may be faster if this is created only once and stored inside the Font struct
             // let face = hb::Face::new(font.data().clone(), 0);
             // let hb_font = hb::Font::new(face);
-            let face = match rustybuzz::Face::from_slice(&font.data(), 0) {
+            let mut face = match rustybuzz::Face::from_slice(&font.data(), 0) {
                 Some(v) => v,
                 None => {
                     eprintln!("Error: malformed font.");
@@ -452,13 +552,41 @@ fn shape_word(
                 }
             };
 
+            let variations: Vec<_> = paint
+                .font_variations
+                .iter()
+                .flatten()
+                .map(|(tag, value)| rustybuzz::Variation {
+                    tag: rustybuzz::Tag::from_bytes(tag),
+                    value: *value,
+                })
+                .collect();
+
+            face.set_variations(&variations);
+
             // let buffer = hb::UnicodeBuffer::new().add_str(word).set_direction(hb_direction);
             let mut buffer = rustybuzz::UnicodeBuffer::new();
             buffer.push_str(word);
             buffer.set_direction(hb_direction);
 
+            // Empty tags don't parse and leave the buffer to guess them
+            if let Ok(script) = paint.text_script().parse() {
+                buffer.set_script(script);
+            }
+
+            if let Ok(language) = paint.text_language().parse() {
+                buffer.set_language(language);
+            }
+
+            let features: Vec<_> = paint
+                .font_features
+                .iter()
+                .flatten()
+                .map(|(tag, value)| rustybuzz::Feature::new(rustybuzz::Tag::from_bytes(tag), *value, ..))
+                .collect();
+
             // hb::shape(&hb_font, buffer, &[])
-            rustybuzz::shape(&face, &[], buffer)
+            rustybuzz::shape(&face, &features, buffer)
         };
 
         // let output = {
@@ -505,7 +633,7 @@ fn shape_word(
                 bearing_y: 0.0,
             };
 
-            if let Some(glyph) = font.glyph(info.codepoint as u16) {
+            if let Some(glyph) = font.glyph(info.codepoint as u16, &paint.font_variations) {
                 g.width = glyph.metrics.width * scale;
                 g.height = glyph.metrics.height * scale;
                 g.bearing_x = glyph.metrics.bearing_x * scale;
@@ -541,7 +669,7 @@ fn layout(x: f32, y: f32, context: &mut TextContext, res: &mut TextMetrics, pain
         let font = context.font_mut(glyph.font_id).ok_or(ErrorKind::NoFontFound)?;
 
         // Baseline alignment
-        let metrics = font.metrics(paint.font_size);
+        let metrics = font.metrics(paint.font_size, &paint.font_variations);
 
         let alignment_offset_y = match paint.text_baseline {
             Baseline::Top => metrics.ascender(),
@@ -606,7 +734,7 @@ pub(crate) struct Quad {
 
 pub(crate) fn render_atlas<T: Renderer + 'static>(
     canvas: &mut Canvas<T>,
-    text_layout: &TextMetrics,
+    glyphs: &[ShapedGlyph],
     paint: &Paint,
     mode: RenderMode,
 ) -> Result<Vec<DrawCmd>, ErrorKind> {
@@ -628,7 +756,7 @@ pub(crate) fn render_atlas<T: Renderer + 'static>(
     //     (0.0, 1.0)
     // };
 
-    for glyph in &text_layout.glyphs {
+    for glyph in glyphs {
         let id = RenderedGlyphId::new(glyph.codepoint, glyph.font_id, paint, mode);
 
         if !canvas.text_context.rendered_glyphs.contains_key(&id) {
@@ -695,6 +823,51 @@ pub(crate) fn render_atlas<T: Renderer + 'static>(
     Ok(cmd_map.drain().map(|(_, cmd)| cmd).collect())
 }
 
+// Sorts glyphs by where the quads render_atlas draws them with lie relative to the clip area, given in
+// device space. Returns the glyphs inside of it, drawn from the atlas like unclipped text, and the ones
+// across its edge, which have to be cut to it as paths. Glyphs outside of it are left out.
+pub(crate) fn clip_glyphs(
+    glyphs: &[ShapedGlyph],
+    paint: &Paint,
+    mode: RenderMode,
+    transform: &Transform2D,
+    invscale: f32,
+    clip: &PathCache,
+) -> (Vec<ShapedGlyph>, Vec<ShapedGlyph>) {
+    let line_width = if mode == RenderMode::Stroke {
+        paint.line_width
+    } else {
+        0.0
+    };
+
+    let padding = (GLYPH_PADDING + GLYPH_MARGIN) as f32;
+
+    let mut inside = Vec::new();
+    let mut partial = Vec::new();
+
+    for glyph in glyphs {
+        let x0 = glyph.x - line_width / 2.0 - GLYPH_PADDING as f32;
+        let y0 = glyph.y - line_width / 2.0 - GLYPH_PADDING as f32;
+        let x1 = x0 + glyph.width.ceil() + line_width.ceil() + padding * 2.0;
+        let y1 = y0 + glyph.height.ceil() + line_width.ceil() + padding * 2.0;
+
+        let quad = [
+            transform.transform_point(x0 * invscale, y0 * invscale),
+            transform.transform_point(x1 * invscale, y0 * invscale),
+            transform.transform_point(x1 * invscale, y1 * invscale),
+            transform.transform_point(x0 * invscale, y1 * invscale),
+        ];
+
+        match clip.quad_overlap(&quad) {
+            Overlap::Inside => inside.push(*glyph),
+            Overlap::Partial => partial.push(*glyph),
+            Overlap::Outside => {}
+        }
+    }
+
+    (inside, partial)
+}
+
 fn render_glyph<T: Renderer>(
     canvas: &mut Canvas<T>,
     paint: &Paint,
@@ -731,7 +904,7 @@ fn render_glyph<T: Renderer>(
             .ok_or(ErrorKind::NoFontFound)?;
         let scale = font.scale(paint.font_size);
 
-        let path = if let Some(font_glyph) = font.glyph(glyph.codepoint as u16) {
+        let path = if let Some(font_glyph) = font.glyph(glyph.codepoint as u16, &paint.font_variations) {
             font_glyph.path.clone()
         } else {
             Path::new()
@@ -890,7 +1063,7 @@ fn find_texture_or_alloc<T: Renderer>(
 
 pub(crate) fn render_direct<T: Renderer>(
     canvas: &mut Canvas<T>,
-    text_layout: &TextMetrics,
+    glyphs: &[ShapedGlyph],
     paint: &Paint,
     mode: RenderMode,
     invscale: f32,
@@ -900,8 +1073,8 @@ pub(crate) fn render_direct<T: Renderer>(
 
     let mut scaled = false;
 
-    for glyph in &text_layout.glyphs {
-        let (mut path, scale) = {
+    for glyph in glyphs {
+        let (mut path, scale, font_data) = {
             let font = canvas
                 .text_context
                 .font_mut(glyph.font_id)
@@ -909,13 +1082,20 @@ pub(crate) fn render_direct<T: Renderer>(
 
             let scale = font.scale(paint.font_size);
 
-            let path = if let Some(font_glyph) = font.glyph(glyph.codepoint as u16) {
+            // Vector renderers can embed the font file, unless the glyphs are varied
+            let font_data = if T::is_vector() && !font.is_varied(&paint.font_variations) {
+                Some(font.shared_data())
+            } else {
+                None
+            };
+
+            let path = if let Some(font_glyph) = font.glyph(glyph.codepoint as u16, &paint.font_variations) {
                 font_glyph.path.clone()
             } else {
                 continue;
             };
 
-            (path, scale)
+            (path, scale, font_data)
         };
 
         canvas.save();
@@ -931,10 +1111,19 @@ pub(crate) fn render_direct<T: Renderer>(
         );
         canvas.scale(scale * invscale, -scale * invscale);
 
-        if mode == RenderMode::Stroke {
-            canvas.stroke_path(&mut path, paint);
-        } else {
-            canvas.fill_path(&mut path, paint);
+        match (mode, font_data) {
+            (RenderMode::Stroke, _) => canvas.stroke_path(&mut path, paint),
+            (RenderMode::Fill, Some(font)) => {
+                let glyph = VectorGlyph {
+                    font,
+                    id: glyph.codepoint as u16,
+                    c: glyph.c,
+                    transform: canvas.transform(),
+                };
+
+                canvas.fill_glyph(&mut path, paint, glyph);
+            }
+            (RenderMode::Fill, None) => canvas.fill_path(&mut path, paint),
         }
 
         canvas.restore();
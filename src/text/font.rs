@@ -1,8 +1,30 @@
-use fnv::FnvHashMap;
-use owned_ttf_parser::{AsFontRef, Font as TtfFont, GlyphId, OwnedFont};
+use std::sync::Arc;
+
+use fnv::{FnvBuildHasher, FnvHashMap};
+use lru::LruCache;
+use owned_ttf_parser::{AsFontRef, Font as TtfFont, GlyphId, OwnedFont, Tag};
 
 use crate::{ErrorKind, Path};
 
+// Outlines at other than the default axis values kept per font, animating an axis creates new ones every frame.
+const VARIED_GLYPHS_CAPACITY: usize = 1000;
+
+// Variation axis tags and their values, as paints store them.
+pub(crate) type Variations = [Option<([u8; 4], f32)>; 8];
+
+// Identifies the variations in cache keys.
+pub(crate) type VariationsKey = [Option<([u8; 4], u32)>; 8];
+
+pub(crate) fn variations_key(variations: &Variations) -> VariationsKey {
+    let mut key = VariationsKey::default();
+
+    for (key, variation) in key.iter_mut().zip(variations.iter()) {
+        *key = variation.map(|(tag, value)| (tag, value.to_bits()));
+    }
+
+    key
+}
+
 pub struct GlyphMetrics {
     pub width: f32,
     pub height: f32,
@@ -81,11 +103,12 @@ impl FontMetrics {
 }
 
 pub(crate) struct Font {
-    data: Vec<u8>,
+    data: Arc<[u8]>,
     owned_ttf_font: OwnedFont,
     units_per_em: u16,
     metrics: FontMetrics,
     glyphs: FnvHashMap<u16, Glyph>,
+    varied_glyphs: LruCache<(u16, VariationsKey), Glyph, FnvBuildHasher>,
 }
 
 impl Font {
@@ -113,11 +136,12 @@ impl Font {
         };
 
         Ok(Self {
-            data: data.to_owned(),
+            data: data.into(),
             owned_ttf_font,
             units_per_em,
             metrics,
             glyphs: Default::default(),
+            varied_glyphs: LruCache::with_hasher(VARIED_GLYPHS_CAPACITY, FnvBuildHasher::default()),
         })
     }
 
@@ -125,13 +149,33 @@ impl Font {
         self.data.as_ref()
     }
 
+    /// The font file, shared without copying it with renderers that embed fonts.
+    pub fn shared_data(&self) -> Arc<[u8]> {
+        self.data.clone()
+    }
+
+    /// Returns the PostScript name of the font, or its family name if it has none.
+    pub fn name(&self) -> Option<String> {
+        let font = self.font_ref();
+
+        font.post_script_name().or_else(|| font.family_name())
+    }
+
     fn font_ref(&self) -> &TtfFont<'_> {
         self.owned_ttf_font.as_font()
     }
 
-    pub fn metrics(&self, size: f32) -> FontMetrics {
+    /// Returns the metrics at the variation axis values, the ascender and descender of variable fonts
+    /// follow their metrics variations (MVAR) table.
+    pub fn metrics(&self, size: f32, variations: &Variations) -> FontMetrics {
         let mut metrics = self.metrics;
 
+        if let Some(font) = self.varied_font(variations) {
+            metrics.ascender = font.ascender() as f32;
+            metrics.descender = font.descender() as f32;
+            metrics.height = font.height() as f32;
+        }
+
         metrics.scale(self.scale(size));
 
         metrics
@@ -141,28 +185,67 @@ impl Font {
         size / self.units_per_em as f32
     }
 
-    pub fn glyph(&mut self, codepoint: u16) -> Option<&mut Glyph> {
-        if !self.glyphs.contains_key(&codepoint) {
-            let mut path = Path::new();
-
-            let id = GlyphId(codepoint);
-
-            if let Some(bbox) = self.font_ref().outline_glyph(id, &mut path) {
-                self.glyphs.insert(
-                    codepoint,
-                    Glyph {
-                        path,
-                        metrics: GlyphMetrics {
-                            width: bbox.width() as f32,
-                            height: bbox.height() as f32,
-                            bearing_x: bbox.x_min as f32,
-                            bearing_y: bbox.y_max as f32,
-                        },
-                    },
-                );
+    /// Returns the outline of the glyph at the variation axis values, axes the font doesn't have are ignored.
+    pub fn glyph(&mut self, codepoint: u16, variations: &Variations) -> Option<&mut Glyph> {
+        if !self.is_varied(variations) {
+            if !self.glyphs.contains_key(&codepoint) {
+                if let Some(glyph) = self.outline_glyph(codepoint, variations) {
+                    self.glyphs.insert(codepoint, glyph);
+                }
+            }
+
+            return self.glyphs.get_mut(&codepoint);
+        }
+
+        let key = (codepoint, variations_key(variations));
+
+        if !self.varied_glyphs.contains(&key) {
+            if let Some(glyph) = self.outline_glyph(codepoint, variations) {
+                self.varied_glyphs.put(key, glyph);
             }
         }
 
-        self.glyphs.get_mut(&codepoint)
+        self.varied_glyphs.get_mut(&key)
+    }
+
+    fn outline_glyph(&self, codepoint: u16, variations: &Variations) -> Option<Glyph> {
+        let mut path = Path::new();
+
+        let id = GlyphId(codepoint);
+
+        let bbox = match self.varied_font(variations) {
+            Some(font) => font.outline_glyph(id, &mut path),
+            None => self.font_ref().outline_glyph(id, &mut path),
+        }?;
+
+        Some(Glyph {
+            path,
+            metrics: GlyphMetrics {
+                width: bbox.width() as f32,
+                height: bbox.height() as f32,
+                bearing_x: bbox.x_min as f32,
+                bearing_y: bbox.y_max as f32,
+            },
+        })
+    }
+
+    /// Returns true if the glyphs at the variation axis values differ from the ones in the font file.
+    pub fn is_varied(&self, variations: &Variations) -> bool {
+        self.metrics.variable && variations.iter().any(Option::is_some)
+    }
+
+    // A copy of the font set to the variation axis values, None when there is nothing to vary.
+    fn varied_font(&self, variations: &Variations) -> Option<TtfFont<'_>> {
+        if !self.is_varied(variations) {
+            return None;
+        }
+
+        let mut font = self.font_ref().clone();
+
+        for (tag, value) in variations.iter().flatten() {
+            font.set_variation(Tag::from_bytes(tag), *value);
+        }
+
+        Some(font)
     }
 }
@@ -0,0 +1,385 @@
+use std::ops::Range;
+
+use unicode_bidi::BidiInfo;
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+use super::{layout, shape_run, shape_words, Align, Baseline, FontMetrics, ShapedGlyph, TextContext, TextMetrics};
+use crate::{ErrorKind, Paint};
+
+/// A single line of a laid out paragraph.
+#[derive(Clone, Debug, Default)]
+pub struct TextLine {
+    /// Bytes of the paragraph text on this line, including trailing whitespace and the line break.
+    ///
+    /// For paragraphs made of spans the range is in the text of all spans joined together.
+    pub byte_range: Range<usize>,
+    /// The left edge of the line after alignment.
+    pub x: f32,
+    /// The y coordinate of the line's baseline.
+    pub baseline: f32,
+    /// The distance from the baseline to the top of the tallest font on the line.
+    pub ascent: f32,
+    /// The distance from the baseline to the bottom of the lowest font on the line, negative below the baseline.
+    pub descent: f32,
+    /// The vertical distance this line takes, the font height times the paint's line height.
+    pub height: f32,
+    width: f32,
+    pub glyphs: Vec<ShapedGlyph>,
+    // The index of the span each range of glyphs was shaped with
+    pub(crate) runs: Vec<(usize, Range<usize>)>,
+}
+
+impl TextLine {
+    /// width of the line without trailing whitespace
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+}
+
+/// Text broken into lines that fit a maximum width, positioned from the top left corner of the paragraph.
+#[derive(Clone, Debug, Default)]
+pub struct Paragraph {
+    pub x: f32,
+    pub y: f32,
+    width: f32,
+    height: f32,
+    pub lines: Vec<TextLine>,
+}
+
+impl Paragraph {
+    pub(crate) fn scale(&mut self, scale: f32) {
+        self.x *= scale;
+        self.y *= scale;
+        self.width *= scale;
+        self.height *= scale;
+
+        for line in &mut self.lines {
+            line.x *= scale;
+            line.baseline *= scale;
+            line.ascent *= scale;
+            line.descent *= scale;
+            line.height *= scale;
+            line.width *= scale;
+
+            for glyph in &mut line.glyphs {
+                glyph.x *= scale;
+                glyph.y *= scale;
+                glyph.width *= scale;
+                glyph.height *= scale;
+            }
+        }
+    }
+
+    /// width of the widest line
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// sum of the line heights
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// Iterates over the glyphs of all lines.
+    pub fn glyphs(&self) -> impl Iterator<Item = &ShapedGlyph> {
+        self.lines.iter().flat_map(|line| line.glyphs.iter())
+    }
+
+    // Returns the glyphs of all lines shaped with the span at the index.
+    pub(crate) fn span_glyphs(&self, span: usize) -> Vec<ShapedGlyph> {
+        self.lines
+            .iter()
+            .flat_map(|line| {
+                line.runs
+                    .iter()
+                    .filter(move |(index, _)| *index == span)
+                    .flat_map(move |(_, range)| line.glyphs[range.clone()].iter())
+            })
+            .copied()
+            .collect()
+    }
+}
+
+// A part of the paragraph text with its own paint.
+struct Span {
+    range: Range<usize>,
+    paint: Paint,
+}
+
+// Breaks the text of the spans into lines no wider than max_width and positions them below each other
+// starting at y. All spans on a line share its baseline, and the text align of the first span aligns the lines
+// inside max_width, or around x like single line text when max_width is infinite.
+pub(crate) fn layout_paragraph(
+    x: f32,
+    y: f32,
+    context: &mut TextContext,
+    spans: &[(&str, Paint)],
+    max_width: f32,
+) -> Result<Paragraph, ErrorKind> {
+    let mut text = String::new();
+    let mut span_ranges = Vec::with_capacity(spans.len());
+
+    for (span_text, paint) in spans {
+        let start = text.len();
+        text.push_str(span_text);
+
+        span_ranges.push(Span {
+            range: start..text.len(),
+            paint: *paint,
+        });
+    }
+
+    let mut paragraph = Paragraph {
+        x,
+        y,
+        ..Default::default()
+    };
+
+    let align = match spans.first().map(|(_, paint)| paint.text_align) {
+        Some(Align::Center) => 0.5,
+        Some(Align::Right) => 1.0,
+        _ => 0.0,
+    };
+
+    let anchor_x = if max_width.is_finite() {
+        x + max_width * align
+    } else {
+        x
+    };
+
+    // The runs of each line are ordered after it is broken, the first span sets the base direction
+    let base_level = spans.first().and_then(|(_, paint)| paint.text_direction.level());
+    let bidi_info = BidiInfo::new(&text, base_level);
+
+    let mut cursor_y = y;
+
+    for byte_range in break_lines(context, &span_ranges, &text, max_width)? {
+        let content_end = byte_range.start + text[byte_range.clone()].trim_end().len();
+
+        let mut line = TextLine {
+            byte_range,
+            ..Default::default()
+        };
+
+        let mut cursor_x = 0.0;
+        let (mut ascent, mut descent, mut height) = (0.0f32, 0.0f32, 0.0f32);
+
+        let mut add_font = |font_metrics: FontMetrics, paint: &Paint| {
+            ascent = ascent.max(font_metrics.ascender());
+            descent = descent.min(font_metrics.descender());
+            height = height.max(font_metrics.height() * paint.line_height);
+        };
+
+        for (index, range, rtl) in visual_pieces(&bidi_info, &span_ranges, line.byte_range.start..content_end) {
+            let mut paint = span_ranges[index].paint;
+            paint.text_align = Align::Left;
+            paint.text_baseline = Baseline::Alphabetic;
+
+            let mut metrics = TextMetrics::default();
+            shape_words(context, &paint, &text, range, rtl, None, &mut metrics);
+            layout(cursor_x, 0.0, context, &mut metrics, &paint)?;
+
+            for glyph in &metrics.glyphs {
+                if let Some(font) = context.font(glyph.font_id) {
+                    add_font(font.metrics(paint.font_size, &paint.font_variations), &paint);
+                }
+            }
+
+            cursor_x += metrics.width();
+
+            let glyph_start = line.glyphs.len();
+            line.glyphs.extend(metrics.glyphs);
+            line.runs.push((index, glyph_start..line.glyphs.len()));
+        }
+
+        if line.glyphs.is_empty() {
+            // Empty lines take the height of the font of the span they are in
+            let index = span_ranges
+                .iter()
+                .position(|span| span.range.end > line.byte_range.start)
+                .or_else(|| span_ranges.len().checked_sub(1));
+
+            if let Some(index) = index {
+                let paint = span_ranges[index].paint;
+                add_font(
+                    context.find_font("", &paint, |(_, font)| {
+                        (false, font.metrics(paint.font_size, &paint.font_variations))
+                    })?,
+                    &paint,
+                );
+            }
+        }
+
+        // Leading is split evenly above and below the line like in CSS
+        let baseline = cursor_y + ascent + (height - (ascent - descent)) / 2.0;
+
+        line.x = anchor_x - cursor_x * align;
+        line.baseline = baseline;
+        line.ascent = ascent;
+        line.descent = descent;
+        line.height = height;
+        line.width = cursor_x;
+
+        for glyph in &mut line.glyphs {
+            glyph.x += line.x;
+            glyph.y += baseline;
+        }
+
+        paragraph.width = paragraph.width.max(line.width);
+        paragraph.lines.push(line);
+
+        cursor_y += height;
+    }
+
+    paragraph.height = cursor_y - y;
+
+    Ok(paragraph)
+}
+
+// Returns the byte ranges of the lines, breaking at the unicode line break opportunities (UAX #14) so that
+// each line without its trailing whitespace fits max_width. Text that can't be broken any other way is
+// broken between glyph clusters.
+fn break_lines(
+    context: &mut TextContext,
+    spans: &[Span],
+    text: &str,
+    max_width: f32,
+) -> Result<Vec<Range<usize>>, ErrorKind> {
+    let mut lines = Vec::new();
+
+    let mut line_start = 0;
+    let mut line_end = 0;
+    let mut line_width = 0.0;
+    let mut segment_start = 0;
+
+    for (segment_end, opportunity) in linebreaks(text) {
+        let content_end = segment_start + text[segment_start..segment_end].trim_end().len();
+
+        let clusters = cluster_advances(context, spans, text, segment_start..segment_end)?;
+
+        let width: f32 = clusters.iter().map(|(_, advance)| advance).sum();
+        let content_width: f32 = clusters
+            .iter()
+            .filter(|(index, _)| *index < content_end)
+            .map(|(_, advance)| advance)
+            .sum();
+
+        if line_end > line_start && line_width + content_width > max_width {
+            lines.push(line_start..line_end);
+            line_start = line_end;
+            line_width = 0.0;
+        }
+
+        if line_width + content_width > max_width {
+            for (index, advance) in clusters {
+                if index > line_start && index < content_end && line_width + advance > max_width {
+                    lines.push(line_start..index);
+                    line_start = index;
+                    line_width = 0.0;
+                }
+
+                line_width += advance;
+            }
+        } else {
+            line_width += width;
+        }
+
+        line_end = segment_end;
+        segment_start = segment_end;
+
+        if opportunity == BreakOpportunity::Mandatory && line_end > line_start {
+            lines.push(line_start..line_end);
+            line_start = line_end;
+            line_width = 0.0;
+        }
+    }
+
+    // A line break at the end of the text starts an empty last line like in a text editor
+    if text.ends_with(['\n', '\r', '\u{b}', '\u{c}', '\u{85}', '\u{2028}', '\u{2029}']) {
+        lines.push(text.len()..text.len());
+    }
+
+    Ok(lines)
+}
+
+// Returns the byte index in the text and the advance of each cluster of the shaped range in logical order.
+// Each part of the range is shaped with the paint of the span it is in.
+fn cluster_advances(
+    context: &mut TextContext,
+    spans: &[Span],
+    text: &str,
+    range: Range<usize>,
+) -> Result<Vec<(usize, f32)>, ErrorKind> {
+    let mut clusters: Vec<(usize, f32)> = Vec::new();
+
+    for (index, range) in pieces(spans, range) {
+        let paint = &spans[index].paint;
+
+        let mut glyphs = shape_run(context, paint, &text[range.clone()], None)?.glyphs;
+        glyphs.sort_by_key(|glyph| glyph.byte_index);
+
+        for glyph in glyphs {
+            let byte_index = range.start + glyph.byte_index;
+            let advance = glyph.advance_x + paint.letter_spacing;
+
+            match clusters.last_mut() {
+                Some((index, width)) if *index == byte_index => *width += advance,
+                _ => clusters.push((byte_index, advance)),
+            }
+        }
+    }
+
+    Ok(clusters)
+}
+
+// Splits the line into the runs of one direction in visual order, and the runs at span boundaries.
+// Returns the parts with the index of their span and whether they are right-to-left.
+fn visual_pieces(bidi_info: &BidiInfo, spans: &[Span], line: Range<usize>) -> Vec<(usize, Range<usize>, bool)> {
+    let mut res = Vec::new();
+
+    let paragraph = bidi_info
+        .paragraphs
+        .iter()
+        .find(|paragraph| paragraph.range.contains(&line.start));
+
+    let paragraph = match paragraph {
+        Some(paragraph) => paragraph,
+        None => return res,
+    };
+
+    // Lines end at paragraph separators, which only leaves a separator that wasn't trimmed as whitespace
+    let line = line.start..line.end.min(paragraph.range.end);
+
+    if line.is_empty() {
+        return res;
+    }
+
+    let (levels, runs) = bidi_info.visual_runs(paragraph, line);
+
+    for run in runs {
+        let rtl = levels[run.start].is_rtl();
+        let start = res.len();
+
+        res.extend(pieces(spans, run).map(|(index, range)| (index, range, rtl)));
+
+        if rtl {
+            res[start..].reverse();
+        }
+    }
+
+    res
+}
+
+// Splits the byte range at span boundaries, returning the non empty parts with the index of their span.
+fn pieces(spans: &[Span], range: Range<usize>) -> impl Iterator<Item = (usize, Range<usize>)> + '_ {
+    spans.iter().enumerate().filter_map(move |(index, span)| {
+        let start = span.range.start.max(range.start);
+        let end = span.range.end.min(range.end);
+
+        if start < end {
+            Some((index, start..end))
+        } else {
+            None
+        }
+    })
+}
@@ -0,0 +1,208 @@
+//! Golden image testing utilities.
+//!
+//! Scenes are rendered with the software renderer and compared against reference PNGs stored in
+//! `tests/references`. Run the tests with `GPUCANVAS_BLESS=1` to (re)generate the references.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::path::{Path as FilePath, PathBuf};
+
+use gpucanvas::{renderer::Software, Canvas, Color};
+use imgref::ImgVec;
+use rgb::RGBA8;
+
+const REFERENCES_DIR: &str = "tests/references";
+const BLESS_ENV: &str = "GPUCANVAS_BLESS";
+
+/// How much a rendering is allowed to deviate from its reference image.
+#[derive(Copy, Clone, Debug)]
+pub struct Tolerance {
+    /// Maximum absolute difference of any channel for a pixel to be considered equal.
+    pub per_channel: u8,
+    /// Percentage (0..100) of pixels that may exceed `per_channel` before the comparison fails.
+    pub max_differing_percent: f32,
+}
+
+impl Tolerance {
+    pub fn exact() -> Self {
+        Self {
+            per_channel: 0,
+            max_differing_percent: 0.0,
+        }
+    }
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            per_channel: 2,
+            max_differing_percent: 0.5,
+        }
+    }
+}
+
+/// Result of comparing two images of the same size.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    pub differing_pixels: usize,
+    pub max_channel_difference: u8,
+    pub diff: ImgVec<RGBA8>,
+}
+
+impl Comparison {
+    pub fn differing_percent(&self) -> f32 {
+        let total = self.diff.width() * self.diff.height();
+
+        if total == 0 {
+            0.0
+        } else {
+            self.differing_pixels as f32 * 100.0 / total as f32
+        }
+    }
+}
+
+/// Renders a scene on a white background and returns the screenshot.
+pub fn render(width: u32, height: u32, scene: impl FnOnce(&mut Canvas<Software>)) -> ImgVec<RGBA8> {
+    let mut canvas = Canvas::new(Software::new()).expect("Cannot create canvas");
+    canvas.set_size(width, height, 1.0);
+    canvas.clear_rect(0, 0, width, height, Color::white());
+
+    scene(&mut canvas);
+
+    canvas.screenshot().expect("Cannot take screenshot")
+}
+
+/// Compares `actual` against `expected` pixel by pixel.
+///
+/// The diff image shows pixels that exceed the tolerance in red on top of a faded copy of `actual`.
+pub fn compare(actual: &ImgVec<RGBA8>, expected: &ImgVec<RGBA8>, per_channel: u8) -> Comparison {
+    assert_eq!(
+        (actual.width(), actual.height()),
+        (expected.width(), expected.height()),
+        "image sizes differ"
+    );
+
+    let mut differing_pixels = 0;
+    let mut max_channel_difference = 0;
+    let mut diff = Vec::with_capacity(actual.width() * actual.height());
+
+    for (a, e) in actual.pixels().zip(expected.pixels()) {
+        let difference = [a.r, a.g, a.b, a.a]
+            .iter()
+            .zip([e.r, e.g, e.b, e.a].iter())
+            .map(|(a, e)| a.max(e) - a.min(e))
+            .max()
+            .unwrap_or(0);
+
+        max_channel_difference = max_channel_difference.max(difference);
+
+        if difference > per_channel {
+            differing_pixels += 1;
+            diff.push(RGBA8::new(255, 0, 0, 255));
+        } else {
+            let luma = ((a.r as u32 + a.g as u32 + a.b as u32) / 3) as u8;
+            let faded = 255 - (255 - luma) / 4;
+            diff.push(RGBA8::new(faded, faded, faded, 255));
+        }
+    }
+
+    Comparison {
+        differing_pixels,
+        max_channel_difference,
+        diff: ImgVec::new(diff, actual.width(), actual.height()),
+    }
+}
+
+/// Asserts that `actual` matches the reference image `tests/references/<name>.png`.
+///
+/// On mismatch `<name>.actual.png` and `<name>.diff.png` are written next to the reference.
+pub fn assert_golden(name: &str, actual: &ImgVec<RGBA8>, tolerance: Tolerance) {
+    let reference = reference_path(name, "png");
+
+    if env::var_os(BLESS_ENV).is_some() {
+        save_png(&reference, actual);
+        return;
+    }
+
+    let expected = match load_png(&reference) {
+        Some(expected) => expected,
+        None => panic!(
+            "reference image {} not found, run with {}=1 to create it",
+            reference.display(),
+            BLESS_ENV
+        ),
+    };
+
+    let actual_path = reference_path(name, "actual.png");
+    let diff_path = reference_path(name, "diff.png");
+
+    if (actual.width(), actual.height()) != (expected.width(), expected.height()) {
+        save_png(&actual_path, actual);
+
+        panic!(
+            "{}: size {}x{} does not match reference size {}x{}, actual image written to {}",
+            name,
+            actual.width(),
+            actual.height(),
+            expected.width(),
+            expected.height(),
+            actual_path.display()
+        );
+    }
+
+    let comparison = compare(actual, &expected, tolerance.per_channel);
+
+    if comparison.differing_percent() > tolerance.max_differing_percent {
+        save_png(&actual_path, actual);
+        save_png(&diff_path, &comparison.diff);
+
+        panic!(
+            "{}: {} pixels ({:.2}%) differ by more than {} (max difference {}), allowed {:.2}%. See {} and {}",
+            name,
+            comparison.differing_pixels,
+            comparison.differing_percent(),
+            tolerance.per_channel,
+            comparison.max_channel_difference,
+            tolerance.max_differing_percent,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+
+    // Don't leave stale failure output around once the test passes again.
+    let _ = std::fs::remove_file(actual_path);
+    let _ = std::fs::remove_file(diff_path);
+}
+
+fn reference_path(name: &str, extension: &str) -> PathBuf {
+    FilePath::new(env!("CARGO_MANIFEST_DIR"))
+        .join(REFERENCES_DIR)
+        .join(format!("{}.{}", name, extension))
+}
+
+fn load_png(path: &FilePath) -> Option<ImgVec<RGBA8>> {
+    let img = image::open(path).ok()?.to_rgba();
+    let (width, height) = img.dimensions();
+
+    let buf = img.pixels().map(|px| RGBA8::new(px[0], px[1], px[2], px[3])).collect();
+
+    Some(ImgVec::new(buf, width as usize, height as usize))
+}
+
+fn save_png(path: &FilePath, img: &ImgVec<RGBA8>) {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).expect("Cannot create references directory");
+    }
+
+    let buf: Vec<u8> = img.pixels().flat_map(|px| vec![px.r, px.g, px.b, px.a]).collect();
+
+    image::save_buffer(
+        path,
+        &buf,
+        img.width() as u32,
+        img.height() as u32,
+        image::ColorType::Rgba8,
+    )
+    .expect("Cannot write png");
+}
@@ -0,0 +1,413 @@
+#![cfg(feature = "image-loading")]
+
+use gpucanvas::{Color, FillRule, LineCap, LineJoin, Paint, Path, Solidity, SpreadMode};
+
+mod common;
+use common::{assert_golden, render, Tolerance};
+
+#[test]
+fn antialiased_shapes() {
+    let img = render(120, 80, |canvas| {
+        let mut path = Path::new();
+        path.circle(30.0, 40.0, 22.5);
+        canvas.fill_path(&mut path, Paint::color(Color::rgb(30, 60, 200)));
+
+        canvas.save();
+        canvas.translate(85.0, 40.0);
+        canvas.rotate(0.4);
+
+        let mut path = Path::new();
+        path.rect(-20.0, -20.0, 40.0, 40.0);
+        canvas.fill_path(&mut path, Paint::color(Color::rgb(200, 40, 40)));
+
+        canvas.restore();
+
+        let mut path = Path::new();
+        path.move_to(5.0, 75.0);
+        path.line_to(115.0, 65.0);
+        let mut paint = Paint::color(Color::black());
+        paint.set_line_width(1.0);
+        canvas.stroke_path(&mut path, paint);
+    });
+
+    assert_golden("antialiased_shapes", &img, Tolerance::default());
+}
+
+#[test]
+fn line_joins_and_caps() {
+    let joins = [LineJoin::Miter, LineJoin::Round, LineJoin::Bevel];
+    let caps = [LineCap::Butt, LineCap::Round, LineCap::Square];
+
+    let img = render(180, 70, |canvas| {
+        for (i, (join, cap)) in joins.iter().zip(caps.iter()).enumerate() {
+            let x = 15.0 + i as f32 * 60.0;
+
+            let mut path = Path::new();
+            path.move_to(x, 55.0);
+            path.line_to(x + 15.0, 15.0);
+            path.line_to(x + 30.0, 55.0);
+
+            let mut paint = Paint::color(Color::rgb(40, 40, 40));
+            paint.set_line_width(8.0);
+            paint.set_line_join(*join);
+            paint.set_line_cap(*cap);
+            canvas.stroke_path(&mut path, paint);
+        }
+    });
+
+    assert_golden("line_joins_and_caps", &img, Tolerance::default());
+}
+
+#[test]
+fn gradients() {
+    let img = render(180, 60, |canvas| {
+        let mut path = Path::new();
+        path.rect(5.0, 5.0, 50.0, 50.0);
+        canvas.fill_path(
+            &mut path,
+            Paint::linear_gradient(5.0, 5.0, 55.0, 55.0, Color::rgb(255, 0, 0), Color::rgb(0, 0, 255)),
+        );
+
+        let mut path = Path::new();
+        path.circle(90.0, 30.0, 25.0);
+        path.circle(90.0, 30.0, 8.0);
+        path.solidity(Solidity::Hole);
+        canvas.fill_path(
+            &mut path,
+            Paint::radial_gradient(90.0, 30.0, 8.0, 25.0, Color::rgb(255, 255, 0), Color::rgb(0, 128, 0)),
+        );
+
+        let mut path = Path::new();
+        path.rounded_rect(125.0, 5.0, 50.0, 50.0, 6.0);
+        canvas.fill_path(
+            &mut path,
+            Paint::box_gradient(
+                130.0,
+                10.0,
+                40.0,
+                40.0,
+                6.0,
+                10.0,
+                Color::rgb(0, 0, 0),
+                Color::rgba(0, 0, 0, 0),
+            ),
+        );
+    });
+
+    assert_golden("gradients", &img, Tolerance::default());
+}
+
+#[test]
+fn dashed_strokes() {
+    let img = render(160, 100, |canvas| {
+        let mut path = Path::new();
+        path.move_to(10.0, 15.0);
+        path.line_to(150.0, 15.0);
+
+        let mut paint = Paint::color(Color::black());
+        paint.set_line_width(4.0);
+        paint.set_line_dash(&[12.0, 6.0]);
+        canvas.stroke_path(&mut path, paint);
+
+        // Dots from zero length dashes with round caps
+        let mut path = Path::new();
+        path.move_to(10.0, 35.0);
+        path.line_to(150.0, 35.0);
+
+        let mut paint = Paint::color(Color::rgb(200, 40, 40));
+        paint.set_line_width(6.0);
+        paint.set_line_cap(LineCap::Round);
+        paint.set_line_dash(&[0.0, 12.0]);
+        canvas.stroke_path(&mut path, paint);
+
+        // Dashes going around corners and through the start of a closed contour
+        let mut path = Path::new();
+        path.rect(15.0, 55.0, 60.0, 35.0);
+
+        let mut paint = Paint::color(Color::rgb(30, 60, 200));
+        paint.set_line_width(3.0);
+        paint.set_line_dash(&[20.0, 5.0, 5.0]);
+        paint.set_line_dash_offset(10.0);
+        canvas.stroke_path(&mut path, paint);
+
+        let mut path = Path::new();
+        path.circle(120.0, 72.0, 20.0);
+
+        let mut paint = Paint::color(Color::rgb(30, 140, 60));
+        paint.set_line_width(3.0);
+        paint.set_line_cap(LineCap::Square);
+        paint.set_line_dash(&[8.0]);
+        canvas.stroke_path(&mut path, paint);
+    });
+
+    assert_golden("dashed_strokes", &img, Tolerance::default());
+}
+
+#[test]
+fn multi_stop_gradients() {
+    let rainbow = [
+        (0.0, Color::rgb(255, 0, 0)),
+        (0.25, Color::rgb(255, 255, 0)),
+        (0.5, Color::rgb(0, 255, 0)),
+        (0.75, Color::rgb(0, 0, 255)),
+        (1.0, Color::rgb(128, 0, 128)),
+    ];
+
+    let img = render(180, 100, |canvas| {
+        let mut path = Path::new();
+        path.rect(5.0, 5.0, 170.0, 20.0);
+        canvas.fill_path(&mut path, Paint::linear_gradient_stops(5.0, 0.0, 175.0, 0.0, &rainbow));
+
+        // Stops sharing an offset make hard transitions, unsorted stops are sorted
+        let mut path = Path::new();
+        path.rect(5.0, 30.0, 170.0, 10.0);
+        canvas.fill_path(
+            &mut path,
+            Paint::linear_gradient_stops(
+                5.0,
+                0.0,
+                175.0,
+                0.0,
+                &[
+                    (0.5, Color::rgb(0, 0, 0)),
+                    (0.0, Color::rgb(255, 0, 0)),
+                    (0.5, Color::rgb(255, 255, 255)),
+                    (1.0, Color::rgb(0, 0, 255)),
+                ],
+            ),
+        );
+
+        let mut path = Path::new();
+        path.circle(35.0, 70.0, 25.0);
+        canvas.fill_path(&mut path, Paint::radial_gradient_stops(35.0, 70.0, 5.0, 25.0, &rainbow));
+
+        let mut path = Path::new();
+        path.rect(70.0, 45.0, 50.0, 50.0);
+        canvas.fill_path(
+            &mut path,
+            Paint::box_gradient_stops(
+                80.0,
+                55.0,
+                30.0,
+                30.0,
+                4.0,
+                16.0,
+                &[
+                    (0.0, Color::rgb(30, 60, 200)),
+                    (0.5, Color::rgb(255, 255, 255)),
+                    (1.0, Color::rgba(30, 60, 200, 0)),
+                ],
+            ),
+        );
+
+        // Stroke with a transform and global alpha
+        canvas.save();
+        canvas.set_global_alpha(0.5);
+        canvas.translate(150.0, 70.0);
+        canvas.rotate(0.5);
+
+        let mut path = Path::new();
+        path.rect(-15.0, -15.0, 30.0, 30.0);
+        let mut paint = Paint::linear_gradient_stops(-15.0, 0.0, 15.0, 0.0, &rainbow);
+        paint.set_line_width(6.0);
+        canvas.stroke_path(&mut path, paint);
+
+        canvas.restore();
+    });
+
+    assert_golden("multi_stop_gradients", &img, Tolerance::default());
+}
+
+#[test]
+fn conic_gradients() {
+    let img = render(180, 80, |canvas| {
+        let mut path = Path::new();
+        path.circle(40.0, 40.0, 35.0);
+        canvas.fill_path(
+            &mut path,
+            Paint::conic_gradient(
+                40.0,
+                40.0,
+                0.0,
+                &[
+                    (0.0, Color::rgb(255, 0, 0)),
+                    (1.0 / 3.0, Color::rgb(0, 255, 0)),
+                    (2.0 / 3.0, Color::rgb(0, 0, 255)),
+                    (1.0, Color::rgb(255, 0, 0)),
+                ],
+            ),
+        );
+
+        // Progress ring starting at the top
+        let mut path = Path::new();
+        path.circle(130.0, 40.0, 28.0);
+        let mut paint = Paint::conic_gradient(
+            130.0,
+            40.0,
+            -std::f32::consts::FRAC_PI_2,
+            &[
+                (0.0, Color::rgb(30, 60, 200)),
+                (0.7, Color::rgb(30, 200, 60)),
+                (0.7, Color::rgb(220, 220, 220)),
+            ],
+        );
+        paint.set_line_width(10.0);
+        canvas.stroke_path(&mut path, paint);
+    });
+
+    assert_golden("conic_gradients", &img, Tolerance::default());
+}
+
+#[test]
+fn gradient_spread_modes() {
+    let modes = [SpreadMode::Pad, SpreadMode::Repeat, SpreadMode::Reflect];
+
+    let img = render(180, 100, |canvas| {
+        for (i, mode) in modes.iter().enumerate() {
+            let x = 5.0 + i as f32 * 60.0;
+
+            let mut path = Path::new();
+            path.rect(x, 5.0, 50.0, 20.0);
+            let mut paint = Paint::linear_gradient(
+                x + 20.0,
+                0.0,
+                x + 30.0,
+                0.0,
+                Color::rgb(255, 0, 0),
+                Color::rgb(0, 0, 255),
+            );
+            paint.set_spread_mode(*mode);
+            canvas.fill_path(&mut path, paint);
+
+            let mut path = Path::new();
+            path.rect(x, 30.0, 50.0, 20.0);
+            let mut paint = Paint::linear_gradient_stops(
+                x + 20.0,
+                0.0,
+                x + 30.0,
+                0.0,
+                &[
+                    (0.0, Color::rgb(255, 255, 0)),
+                    (0.5, Color::rgb(0, 128, 0)),
+                    (1.0, Color::rgb(0, 0, 0)),
+                ],
+            );
+            paint.set_spread_mode(*mode);
+            canvas.fill_path(&mut path, paint);
+
+            let mut path = Path::new();
+            path.circle(x + 25.0, 77.0, 20.0);
+            let mut paint = Paint::radial_gradient(
+                x + 25.0,
+                77.0,
+                4.0,
+                10.0,
+                Color::rgb(255, 255, 255),
+                Color::rgb(30, 60, 200),
+            );
+            paint.set_spread_mode(*mode);
+            canvas.fill_path(&mut path, paint);
+        }
+    });
+
+    assert_golden("gradient_spread_modes", &img, Tolerance::default());
+}
+
+#[test]
+fn path_booleans() {
+    let mut circle = Path::new();
+    circle.circle(25.0, 25.0, 18.0);
+
+    let mut square = Path::new();
+    square.rect(25.0, 15.0, 22.0, 28.0);
+
+    // Self intersecting star, its center is only inside with the NonZero rule
+    let mut star = Path::new();
+    star.move_to(25.0, 3.0);
+    star.line_to(38.0, 45.0);
+    star.line_to(4.0, 19.0);
+    star.line_to(46.0, 19.0);
+    star.line_to(12.0, 45.0);
+    star.close();
+
+    let results = [
+        circle.union(&square, FillRule::NonZero),
+        circle.intersect(&square, FillRule::NonZero),
+        circle.difference(&square, FillRule::NonZero),
+        circle.xor(&square, FillRule::NonZero),
+        square.difference(&star, FillRule::NonZero),
+        square.difference(&star, FillRule::EvenOdd),
+    ];
+
+    let img = render(300, 110, |canvas| {
+        for (i, result) in results.iter().enumerate() {
+            canvas.save();
+            canvas.translate((i % 3) as f32 * 100.0 + 20.0, (i / 3) as f32 * 55.0 + 3.0);
+
+            let mut path = result.clone();
+
+            canvas.fill_path(&mut path, Paint::color(Color::rgb(30, 60, 200)));
+
+            let mut paint = Paint::color(Color::black());
+            paint.set_line_width(1.0);
+            canvas.stroke_path(&mut path, paint);
+
+            canvas.restore();
+        }
+    });
+
+    assert_golden("path_booleans", &img, Tolerance::default());
+}
+
+#[test]
+fn stroke_outlines() {
+    let mut zigzag = Path::new();
+    zigzag.move_to(5.0, 40.0);
+    zigzag.line_to(20.0, 10.0);
+    zigzag.line_to(35.0, 40.0);
+    zigzag.line_to(50.0, 10.0);
+
+    let styles = [
+        (LineJoin::Miter, LineCap::Butt),
+        (LineJoin::Round, LineCap::Round),
+        (LineJoin::Bevel, LineCap::Square),
+    ];
+
+    let mut outlines: Vec<Path> = styles
+        .iter()
+        .map(|(join, cap)| {
+            let mut paint = Paint::color(Color::black());
+            paint.set_line_width(8.0);
+            paint.set_line_join(*join);
+            paint.set_line_cap(*cap);
+            zigzag.stroke_outline(&paint)
+        })
+        .collect();
+
+    let mut circle = Path::new();
+    circle.circle(25.0, 25.0, 16.0);
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_line_width(6.0);
+    paint.set_line_dash(&[10.0, 6.0]);
+    outlines.push(circle.stroke_outline(&paint));
+
+    let img = render(240, 60, |canvas| {
+        for (i, outline) in outlines.iter().enumerate() {
+            canvas.save();
+            canvas.translate(i as f32 * 60.0 + 3.0, 5.0);
+
+            let mut path = outline.clone();
+
+            canvas.fill_path(&mut path, Paint::color(Color::rgb(30, 60, 200)));
+
+            let mut paint = Paint::color(Color::black());
+            paint.set_line_width(1.0);
+            canvas.stroke_path(&mut path, paint);
+
+            canvas.restore();
+        }
+    });
+
+    assert_golden("stroke_outlines", &img, Tolerance::default());
+}
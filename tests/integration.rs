@@ -1,4 +1,10 @@
-use gpucanvas::{renderer::Void, Baseline, Canvas, Color, FillRule, Paint, Path, Solidity};
+use std::collections::HashMap;
+
+use gpucanvas::{
+    renderer::{CommandType, Pdf, Recorder, Software, SvgExport, Void},
+    Baseline, Canvas, Color, CompositeOperation, ErrorKind, FillRule, Filter, ImageFilter, ImageFlags, ImageId,
+    ImageSource, LineCap, Paint, Path, PixelFormat, Rect, Solidity, TextDirection, Transform2D, Verb,
+};
 
 #[test]
 fn path_with_single_move_to() {
@@ -144,6 +150,144 @@ fn path_contains_point() {
     assert!(canvas.contains_point(&mut path, 50.0, 5.0, FillRule::NonZero));
 }
 
+#[test]
+fn path_stroke_contains_point() {
+    let mut canvas = Canvas::new(Void).unwrap();
+    canvas.set_size(100, 100, 1.0);
+
+    let mut path = Path::new();
+    path.move_to(10.0, 50.0);
+    path.line_to(90.0, 50.0);
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_line_width(4.0);
+
+    assert!(canvas.stroke_contains_point(&mut path, &paint, 50.0, 51.5));
+    assert!(!canvas.stroke_contains_point(&mut path, &paint, 50.0, 53.0));
+    assert!(!canvas.stroke_contains_point(&mut path, &paint, 8.0, 50.0));
+
+    // Butt caps have no area beyond the end points, not even on the line through them
+    assert!(!canvas.stroke_contains_point(&mut path, &paint, 10.0, 0.0));
+
+    // Square caps extend past the end points by half the line width
+    paint.set_line_cap(LineCap::Square);
+    assert!(canvas.stroke_contains_point(&mut path, &paint, 8.5, 50.0));
+
+    // Scaling the canvas scales the stroke too
+    canvas.scale(2.0, 2.0);
+    assert!(canvas.stroke_contains_point(&mut path, &paint, 100.0, 103.0));
+    assert!(!canvas.stroke_contains_point(&mut path, &paint, 100.0, 105.0));
+}
+
+#[test]
+fn line_dash_patterns_longer_than_16_are_ignored() {
+    let mut paint = Paint::color(Color::black());
+
+    paint.set_line_dash(&[1.0, 2.0, 3.0]);
+    assert_eq!(paint.line_dash(), &[1.0, 2.0, 3.0, 1.0, 2.0, 3.0]);
+
+    // Nine entries would be repeated to 18
+    paint.set_line_dash(&[1.0; 9]);
+    assert_eq!(paint.line_dash(), &[1.0, 2.0, 3.0, 1.0, 2.0, 3.0]);
+
+    paint.set_line_dash(&[1.0; 17]);
+    assert_eq!(paint.line_dash().len(), 6);
+
+    paint.set_line_dash(&[2.0; 16]);
+    assert_eq!(paint.line_dash(), &[2.0; 16]);
+}
+
+#[test]
+fn path_booleans_with_empty_path() {
+    let mut square = Path::new();
+    square.rect(10.0, 10.0, 50.0, 50.0);
+
+    let empty = Path::new();
+
+    assert!(square.intersect(&empty, FillRule::NonZero).is_empty());
+    assert!(empty.difference(&square, FillRule::NonZero).is_empty());
+    assert!(!square.union(&empty, FillRule::NonZero).is_empty());
+    assert!(!empty.xor(&square, FillRule::EvenOdd).is_empty());
+}
+
+#[test]
+fn path_booleans_flatten_small_paths_finely() {
+    let mut circle = Path::new();
+    circle.circle(2.0, 2.0, 1.0);
+
+    let union = circle.union(&Path::new(), FillRule::NonZero);
+
+    let points: Vec<(f32, f32)> = union
+        .verbs()
+        .filter_map(|verb| match verb {
+            Verb::MoveTo(x, y) | Verb::LineTo(x, y) => Some((x, y)),
+            _ => None,
+        })
+        .collect();
+
+    assert!(points.len() > 16);
+
+    // The middle of every edge stays close to the circle
+    for (i, (x0, y0)) in points.iter().enumerate() {
+        let (x1, y1) = points[(i + 1) % points.len()];
+        let (mx, my) = ((x0 + x1) * 0.5, (y0 + y1) * 0.5);
+
+        assert!(((mx - 2.0).powi(2) + (my - 2.0).powi(2)).sqrt() > 0.99);
+    }
+}
+
+#[test]
+fn stroke_outline_of_thin_stroke_keeps_round_caps() {
+    let mut path = Path::new();
+    path.move_to(0.0, 0.0);
+    path.line_to(10.0, 0.0);
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_line_width(0.2);
+    paint.set_line_cap(LineCap::Round);
+
+    let outline = path.stroke_outline(&paint);
+
+    let cap: Vec<(f32, f32)> = outline
+        .verbs()
+        .filter_map(|verb| match verb {
+            Verb::MoveTo(x, y) | Verb::LineTo(x, y) if x < -0.01 => Some((x, y)),
+            _ => None,
+        })
+        .collect();
+
+    assert!(cap.len() > 4);
+
+    for (x, y) in cap {
+        assert!((x.hypot(y) - 0.1).abs() < 0.001);
+    }
+}
+
+#[test]
+fn path_svg_data_round_trip() {
+    let path = Path::from_svg_data("M10,10 h20 v20 H10 z m5-2 q5 -5 10 0 t10 0 s5 5 10 0 A5 5 0 0 1 55 8").unwrap();
+
+    let verbs: Vec<Verb> = path.verbs().collect();
+    assert!(matches!(verbs[0], Verb::MoveTo(x, y) if x == 10.0 && y == 10.0));
+    assert!(matches!(verbs[3], Verb::LineTo(x, y) if x == 10.0 && y == 30.0));
+    assert!(matches!(verbs[4], Verb::Close));
+
+    // Relative commands after a close are relative to the start of the closed sub-path
+    assert!(matches!(verbs[5], Verb::MoveTo(x, y) if x == 15.0 && y == 8.0));
+
+    // Half circle arcs are split in two curves ending on the end point
+    assert!(matches!(verbs[verbs.len() - 1], Verb::BezierTo(.., x, y) if x == 55.0 && y == 8.0));
+    assert!(matches!(verbs[verbs.len() - 2], Verb::BezierTo(..)));
+
+    let data = path.to_svg_data();
+    assert_eq!(Path::from_svg_data(&data).unwrap().to_svg_data(), data);
+
+    assert!(Path::from_svg_data("").unwrap().is_empty());
+    assert!(Path::from_svg_data("L10 10").is_err());
+    assert!(Path::from_svg_data("M10 10 L20").is_err());
+    assert!(Path::from_svg_data("M10 10 X").is_err());
+}
+
 #[test]
 fn text_location_respects_scale() {
     let mut canvas = Canvas::new(Void).unwrap();
@@ -161,3 +305,1161 @@ fn text_location_respects_scale() {
     assert_eq!(res.x, 100.0);
     assert_eq!(res.y, 100.0);
 }
+
+#[test]
+fn paragraph_breaks_lines_to_fit_width() {
+    let mut canvas = Canvas::new(Void).unwrap();
+
+    canvas
+        .add_font("examples/assets/Roboto-Regular.ttf")
+        .expect("Font not found");
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_font_size(20.0);
+
+    let text = "The quick brown fox jumps over the lazy dog\nand a supercalifragilisticexpialidocious word";
+    let max_width = 120.0;
+
+    let paragraph = canvas.measure_paragraph(10.0, 10.0, max_width, text, paint).unwrap();
+
+    assert!(paragraph.lines.len() > 4);
+    assert_eq!(paragraph.lines.first().unwrap().byte_range.start, 0);
+    assert_eq!(paragraph.lines.last().unwrap().byte_range.end, text.len());
+
+    for (line, next) in paragraph.lines.iter().zip(paragraph.lines.iter().skip(1)) {
+        assert_eq!(line.byte_range.end, next.byte_range.start);
+        assert!(next.baseline > line.baseline);
+        assert!((next.baseline - line.baseline - line.height).abs() < 0.01);
+    }
+
+    for line in &paragraph.lines {
+        let line_text = &text[line.byte_range.clone()];
+
+        assert!(line.width() <= max_width, "{:?} is too wide", line_text);
+        assert!(line.ascent > 0.0 && line.descent < 0.0);
+        assert!(line.baseline - line.ascent >= 10.0);
+
+        // Words are only split when they are wider than the line
+        if line.byte_range.end < text.len() && !line_text.ends_with(char::is_whitespace) {
+            assert!(text.find("supercal").unwrap() < line.byte_range.end);
+            assert!(text.find(" word").unwrap() > line.byte_range.end);
+        }
+    }
+
+    let after_newline = text.find('\n').unwrap() + 1;
+    let starts: Vec<_> = paragraph.lines.iter().map(|line| line.byte_range.start).collect();
+    assert!(starts.contains(&after_newline));
+
+    // The line height multiplier spreads the same lines further apart
+    paint.set_line_height(2.0);
+    let spread = canvas.measure_paragraph(10.0, 10.0, max_width, text, paint).unwrap();
+
+    assert_eq!(spread.lines.len(), paragraph.lines.len());
+    assert!((spread.height() - paragraph.height() * 2.0).abs() < 0.01);
+
+    let filled = canvas.fill_paragraph(10.0, 10.0, max_width, text, paint).unwrap();
+    assert_eq!(filled.glyphs().count(), spread.glyphs().count());
+}
+
+#[test]
+fn paragraph_ends_with_empty_line_after_trailing_newline() {
+    let mut canvas = Canvas::new(Void).unwrap();
+
+    canvas
+        .add_font("examples/assets/Roboto-Regular.ttf")
+        .expect("Font not found");
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_font_size(20.0);
+
+    let single = canvas.measure_paragraph(0.0, 0.0, 200.0, "Hello", paint).unwrap();
+    let paragraph = canvas.measure_paragraph(0.0, 0.0, 200.0, "Hello\n", paint).unwrap();
+
+    assert_eq!(single.lines.len(), 1);
+    assert_eq!(paragraph.lines.len(), 2);
+
+    let first = &paragraph.lines[0];
+    let last = &paragraph.lines[1];
+
+    assert_eq!(first.byte_range, 0..6);
+    assert_eq!(last.byte_range, 6..6);
+    assert_eq!(last.glyphs.len(), 0);
+    assert_eq!(last.width(), 0.0);
+
+    // The empty line is as tall as the one above it
+    assert!((last.height - first.height).abs() < 0.01);
+    assert!((last.baseline - first.baseline - first.height).abs() < 0.01);
+    assert!((paragraph.height() - single.height() * 2.0).abs() < 0.01);
+
+    let crlf = canvas.measure_paragraph(0.0, 0.0, 200.0, "Hello\r\n", paint).unwrap();
+    assert_eq!(crlf.lines.len(), 2);
+    assert_eq!(crlf.lines[1].byte_range, 7..7);
+}
+
+#[test]
+fn paragraph_spans_share_baseline_and_break_across_spans() {
+    let mut canvas = Canvas::new(Void).unwrap();
+
+    let regular = canvas.add_font("examples/assets/Roboto-Regular.ttf").unwrap();
+    let bold = canvas.add_font("examples/assets/Roboto-Bold.ttf").unwrap();
+
+    let mut small = Paint::color(Color::black());
+    small.set_font(&[regular]);
+    small.set_font_size(16.0);
+
+    let mut large = Paint::color(Color::rgb(255, 0, 0));
+    large.set_font(&[bold]);
+    large.set_font_size(32.0);
+
+    let spans = [("Some small text then ", small), ("BIG", large), ("ger words", small)];
+    let text: String = spans.iter().map(|(text, _)| *text).collect();
+
+    let paragraph = canvas.measure_paragraph_spans(0.0, 0.0, 260.0, &spans).unwrap();
+    let large_ascender = canvas.measure_font(large).unwrap().ascender();
+
+    assert!(paragraph.lines.len() > 1);
+    assert_eq!(paragraph.lines.last().unwrap().byte_range.end, text.len());
+
+    // "BIGger" is a single word split over two spans
+    let join = text.find("ger").unwrap();
+    assert!(paragraph.lines.iter().all(|line| line.byte_range.start != join));
+
+    let line = paragraph
+        .lines
+        .iter()
+        .find(|line| line.byte_range.contains(&join))
+        .unwrap();
+
+    assert!((line.ascent - large_ascender).abs() < 0.01);
+
+    // Glyphs of both sizes on the line stand on its baseline
+    for glyph in &line.glyphs {
+        assert!(line.byte_range.contains(&glyph.byte_index));
+        assert!((glyph.y + glyph.bearing_y - line.baseline).abs() < 0.01);
+    }
+
+    let big = text.find("BIG").unwrap();
+    assert!(line.glyphs.iter().any(|glyph| glyph.byte_index < big));
+    assert!(paragraph.lines.last().unwrap().ascent < large_ascender);
+
+    let filled = canvas.fill_paragraph_spans(0.0, 0.0, 260.0, &spans).unwrap();
+    assert_eq!(filled.glyphs().count(), paragraph.glyphs().count());
+}
+
+#[test]
+fn bidi_paragraphs_follow_base_direction() {
+    let mut canvas = Canvas::new(Void).unwrap();
+
+    canvas.add_font("examples/assets/amiri-regular.ttf").unwrap();
+
+    let text = "\u{645}\u{631}\u{62d}\u{628}\u{627} abc\nabc \u{645}\u{631}\u{62d}\u{628}\u{627}";
+    let newline = text.find('\n').unwrap();
+
+    // Text after the first paragraph separator is shaped too
+    let metrics = canvas
+        .measure_text(0.0, 0.0, text, Paint::color(Color::black()))
+        .unwrap();
+    assert!(metrics.glyphs.iter().any(|glyph| glyph.byte_index > newline));
+
+    // Returns whether the latin text of each line is left of the arabic text
+    let mut latin_first = |direction| {
+        let mut paint = Paint::color(Color::black());
+        paint.set_text_direction(direction);
+
+        let paragraph = canvas.measure_paragraph(0.0, 0.0, 1000.0, text, paint).unwrap();
+        assert_eq!(paragraph.lines.len(), 2);
+
+        paragraph
+            .lines
+            .iter()
+            .map(|line| {
+                let x = |c: char| {
+                    line.glyphs
+                        .iter()
+                        .find(|glyph| text[glyph.byte_index..].starts_with(c))
+                        .unwrap()
+                        .x
+                };
+                x('a') < x('\u{645}')
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(latin_first(TextDirection::Ltr), vec![false, true]);
+    assert_eq!(latin_first(TextDirection::Rtl), vec![true, false]);
+    assert_eq!(latin_first(TextDirection::Auto), vec![true, true]);
+}
+
+#[test]
+fn font_features_and_language_change_shaping() {
+    let mut canvas = Canvas::new(Void).unwrap();
+
+    let font = canvas.add_font("examples/assets/amiri-regular.ttf").unwrap();
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_font(&[font]);
+
+    let mut glyphs = |text: &str, paint: Paint| {
+        let metrics = canvas.measure_text(0.0, 0.0, text, paint).unwrap();
+        metrics.glyphs.iter().map(|glyph| glyph.codepoint).collect::<Vec<_>>()
+    };
+
+    let ligated = glyphs("office", paint);
+
+    let mut unligated = paint;
+    unligated.set_font_features(&[("liga", 0)]);
+    assert_eq!(unligated.font_features().collect::<Vec<_>>(), vec![("liga", 0)]);
+
+    assert_eq!(ligated.len(), 4);
+    assert_eq!(glyphs("office", unligated).len(), 6);
+
+    let mut proportional = paint;
+    proportional.set_font_features(&[("pnum", 1)]);
+    assert_ne!(glyphs("2021", paint), glyphs("2021", proportional));
+
+    // Turkish keeps the dotted i apart from the f
+    let mut turkish = paint;
+    turkish.set_text_language("tr");
+    turkish.set_text_script("Latn");
+    assert_eq!(turkish.text_language(), "tr");
+
+    assert_eq!(glyphs("fi", paint).len(), 1);
+    assert_eq!(glyphs("fi", turkish).len(), 2);
+}
+
+#[test]
+fn font_variations_are_ignored_by_static_fonts() {
+    let mut canvas = Canvas::new(Void).unwrap();
+
+    let font = canvas.add_font("examples/assets/Roboto-Regular.ttf").unwrap();
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_font(&[font]);
+
+    let mut heavy = paint;
+    heavy.set_font_variations(&[("wght", 650.0), ("wdth", 85.0)]);
+    assert_eq!(
+        heavy.font_variations().collect::<Vec<_>>(),
+        vec![("wght", 650.0), ("wdth", 85.0)]
+    );
+
+    let regular = canvas.fill_text(0.0, 0.0, "Variable", paint).unwrap();
+    let varied = canvas.fill_text(0.0, 0.0, "Variable", heavy).unwrap();
+
+    assert_eq!(varied.width(), regular.width());
+    assert_eq!(varied.glyphs.len(), regular.glyphs.len());
+
+    for (a, b) in varied.glyphs.iter().zip(&regular.glyphs) {
+        assert_eq!((a.codepoint, a.x, a.width), (b.codepoint, b.x, b.width));
+    }
+}
+
+#[test]
+fn font_variations_change_advances_and_outlines() {
+    let mut canvas = Canvas::new(Void).unwrap();
+
+    let font = canvas.add_font("examples/assets/AdobeVFPrototype-Subset.otf").unwrap();
+
+    let mut light = Paint::color(Color::black());
+    light.set_font(&[font]);
+    light.set_font_size(100.0);
+    light.set_font_variations(&[("wght", 200.0)]);
+
+    let mut black = light;
+    black.set_font_variations(&[("wght", 900.0)]);
+
+    assert!(canvas.measure_font(light).unwrap().variable());
+
+    let light_text = canvas.measure_text(0.0, 0.0, "$", light).unwrap();
+    let black_text = canvas.measure_text(0.0, 0.0, "$", black).unwrap();
+
+    let (thin, bold) = (&light_text.glyphs[0], &black_text.glyphs[0]);
+
+    // The heavy weights substitute a dollar sign without the stroke through the bold one, and all of the
+    // weights are wider and further apart
+    assert_ne!(thin.codepoint, bold.codepoint);
+    assert!(bold.advance_x > thin.advance_x, "{} {}", bold.advance_x, thin.advance_x);
+    assert!(bold.width > thin.width, "{} {}", bold.width, thin.width);
+    assert!(black_text.width() > light_text.width());
+
+    // Values in between are cached apart from both ends
+    let mut regular = light;
+    regular.set_font_variations(&[("wght", 400.0)]);
+    let regular_text = canvas.measure_text(0.0, 0.0, "$", regular).unwrap();
+
+    assert!(regular_text.glyphs[0].advance_x > thin.advance_x);
+    assert!(regular_text.glyphs[0].advance_x < bold.advance_x);
+    assert_eq!(
+        canvas.measure_text(0.0, 0.0, "$", light).unwrap().glyphs[0].width,
+        thin.width
+    );
+}
+
+#[test]
+fn software_renderer_screenshot() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(20, 20, 1.0);
+    canvas.clear_rect(0, 0, 20, 20, Color::white());
+
+    let mut path = Path::new();
+    path.rect(5.0, 5.0, 10.0, 10.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    let screenshot = canvas.screenshot().unwrap();
+
+    assert_eq!(screenshot.width(), 20);
+    assert_eq!(screenshot.height(), 20);
+    assert_eq!(screenshot[(10usize, 10usize)], rgb::RGBA8::new(255, 0, 0, 255));
+    assert_eq!(screenshot[(2usize, 2usize)], rgb::RGBA8::new(255, 255, 255, 255));
+}
+
+#[test]
+fn recorder_captures_concave_fill() {
+    let mut canvas = Canvas::new(Recorder::new()).unwrap();
+    canvas.set_size(100, 100, 1.0);
+    canvas.flush();
+
+    let mut path = Path::new();
+    path.move_to(50.0, 0.0);
+    path.line_to(21.0, 90.0);
+    path.line_to(98.0, 35.0);
+    path.line_to(2.0, 35.0);
+    path.line_to(79.0, 90.0);
+    path.close();
+
+    let mut paint = Paint::color(Color::rgb(100, 100, 100));
+    paint.set_fill_rule(FillRule::EvenOdd);
+    canvas.fill_path(&mut path, paint);
+    canvas.flush();
+
+    let frame = canvas.renderer_mut().last_frame().unwrap();
+    assert_eq!(frame.commands().len(), 1);
+
+    let cmd = &frame.commands()[0];
+    assert!(matches!(cmd.cmd_type(), CommandType::ConcaveFill { .. }));
+    assert_eq!(cmd.drawables().len(), 1);
+    assert_eq!(cmd.fill_rule(), FillRule::EvenOdd);
+
+    let fill_verts = cmd.drawables()[0].fill_verts().unwrap();
+    assert_eq!(frame.verts_in(fill_verts).len(), 5);
+}
+
+#[test]
+fn svg_export_has_exact_geometry() {
+    let mut canvas = Canvas::new(SvgExport::new()).unwrap();
+    canvas.set_size(100, 100, 1.0);
+
+    let mut path = Path::new();
+    path.rect(10.0, 10.0, 50.0, 50.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgba(255, 0, 0, 128)));
+
+    let mut path = Path::new();
+    path.move_to(10.0, 80.0);
+    path.line_to(90.0, 80.0);
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_line_width(4.0);
+    canvas.stroke_path(&mut path, paint);
+    canvas.flush();
+
+    let document = canvas.renderer_mut().document().unwrap();
+
+    assert!(document.starts_with("<svg"));
+    assert!(document.contains(r##"<path d="M10 10 L10 60 L60 60 L60 10 Z" fill-rule="nonzero" fill="#ff0000""##));
+
+    // The stroke keeps its line, with the width and style as attributes
+    assert!(document.contains(
+        r##"<path d="M10 80 L90 80" fill="none" stroke-width="4" stroke-linecap="butt" stroke-linejoin="miter" stroke-miterlimit="10" stroke="#000000"/>"##
+    ));
+}
+
+#[test]
+fn svg_export_keeps_curves_and_paints() {
+    let mut canvas = Canvas::new(SvgExport::new()).unwrap();
+    canvas.set_size(100, 100, 1.0);
+
+    let mut path = Path::new();
+    path.circle(50.0, 50.0, 40.0);
+
+    let stops = [
+        (0.0, Color::rgb(255, 0, 0)),
+        (0.3, Color::rgb(0, 255, 0)),
+        (1.0, Color::rgb(0, 0, 255)),
+    ];
+    canvas.fill_path(&mut path, Paint::linear_gradient_stops(10.0, 0.0, 90.0, 0.0, &stops));
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_line_width(2.0);
+    paint.set_line_dash(&[4.0, 2.0]);
+    canvas.global_composite_operation(CompositeOperation::Lighter);
+    canvas.stroke_path(&mut path, paint);
+    canvas.flush();
+
+    let document = canvas.renderer_mut().document().unwrap();
+
+    assert!(document.contains(r#"<path d="M10 50 C10 72.09139 27.90861 90 50 90 C"#));
+    assert!(document.contains(r#"x1="10" y1="0" x2="90" y2="0""#));
+    assert!(document.contains(r##"<stop offset="0.3" stop-color="#00ff00" stop-opacity="1"/>"##));
+    assert!(document.contains(
+        r##"stroke-dasharray="4 2" stroke-dashoffset="0" stroke="#000000" style="mix-blend-mode:plus-lighter"/>"##
+    ));
+
+    // Conic gradients are embedded as the pixels they cover
+    canvas.global_composite_operation(CompositeOperation::SourceOver);
+    canvas.fill_path(&mut path, Paint::conic_gradient(50.0, 50.0, 0.0, &stops));
+    canvas.flush();
+
+    let document = canvas.renderer_mut().document().unwrap();
+
+    assert!(document.contains(r#"<pattern id="pattern"#));
+    assert!(document.contains(
+        r#"x="10" y="10" width="80" height="80"><image width="80" height="80" href="data:image/png;base64,"#
+    ));
+
+    canvas.global_composite_operation(CompositeOperation::Xor);
+    canvas.fill_path(&mut path, Paint::color(Color::black()));
+    canvas.flush();
+
+    assert!(matches!(
+        canvas.renderer_mut().document(),
+        Err(ErrorKind::UnsupportedCompositeOperation)
+    ));
+}
+
+#[test]
+fn pdf_export_writes_page() {
+    let mut canvas = Canvas::new(Pdf::new()).unwrap();
+    canvas.set_size(200, 100, 2.0);
+
+    let mut path = Path::new();
+    path.rect(10.0, 10.0, 50.0, 50.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgba(255, 0, 0, 128)));
+    canvas.flush();
+
+    let document = canvas.renderer_mut().document().unwrap();
+    let contains = |needle: &[u8]| document.windows(needle.len()).any(|window| window == needle);
+
+    assert!(document.starts_with(b"%PDF-1.4"));
+    assert!(document.ends_with(b"%%EOF\n"));
+    assert!(contains(b"/MediaBox [0 0 100 50]"));
+    assert!(contains(b"10 10 m\n10 60 l\n60 60 l\n60 10 l\nh\nf\n"));
+
+    // The cross reference table is where the trailer says it is
+    let trailer = String::from_utf8_lossy(&document[document.len() - 32..]).into_owned();
+    let start = trailer.rsplit("startxref\n").next().unwrap();
+    let offset: usize = start.lines().next().unwrap().parse().unwrap();
+    assert!(document[offset..].starts_with(b"xref"));
+}
+
+#[test]
+fn pdf_export_embeds_fonts_and_strokes() {
+    let mut canvas = Canvas::new(Pdf::new()).unwrap();
+    canvas.set_size(200, 100, 1.0);
+    canvas.add_font("examples/assets/Roboto-Regular.ttf").unwrap();
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_font_size(20.0);
+    canvas.fill_text(10.0, 30.0, "Hi", paint).unwrap();
+
+    let mut path = Path::new();
+    path.move_to(10.0, 80.0);
+    path.line_to(90.0, 80.0);
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_line_width(4.0);
+    paint.set_line_cap(LineCap::Round);
+    paint.set_line_dash(&[4.0, 2.0]);
+    canvas.stroke_path(&mut path, paint);
+
+    // Stops of differing alpha need a soft mask
+    let mut path = Path::new();
+    path.rect(100.0, 10.0, 80.0, 80.0);
+    let paint = Paint::linear_gradient(100.0, 0.0, 180.0, 0.0, Color::black(), Color::rgba(0, 0, 0, 0));
+    canvas.global_composite_operation(CompositeOperation::Lighter);
+    canvas.fill_path(&mut path, paint);
+    canvas.flush();
+
+    let document = canvas.renderer_mut().document().unwrap();
+    let contains = |needle: &[u8]| document.windows(needle.len()).any(|window| window == needle);
+
+    assert!(contains(b"/Subtype /CIDFontType2"));
+    assert!(contains(b"/FontFile2"));
+    assert!(contains(b"/ToUnicode"));
+    assert!(contains(b"> Tj ET\n"));
+    assert!(contains(b"beginbfchar\n<002B> <0048>\n<004C> <0069>\nendbfchar"));
+
+    assert!(contains(b"4 w 1 J 0 j 10 M [4 2] 0 d\n10 80 m\n90 80 l\nS\n"));
+
+    assert!(contains(b"/SMask << /Type /Mask /S /Luminosity"));
+    assert!(contains(b"/BM /Screen"));
+
+    canvas.global_composite_operation(CompositeOperation::Xor);
+    canvas.fill_path(&mut path, Paint::color(Color::black()));
+    canvas.flush();
+
+    assert!(matches!(
+        canvas.renderer_mut().document(),
+        Err(ErrorKind::UnsupportedCompositeOperation)
+    ));
+}
+
+#[test]
+fn layer_in_image_render_target() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(20, 20, 1.0);
+    canvas.clear_rect(0, 0, 20, 20, Color::white());
+
+    // Larger than the canvas, the layer covers the image instead of being cut to the canvas size
+    let image = canvas
+        .create_image_empty(
+            40,
+            40,
+            PixelFormat::Rgba8,
+            ImageFlags::PREMULTIPLIED | ImageFlags::FLIP_Y,
+        )
+        .unwrap();
+
+    canvas.push_render_target(gpucanvas::RenderTarget::Image(image));
+    canvas.clear_rect(0, 0, 40, 40, Color::rgb(0, 0, 255));
+    canvas
+        .begin_layer(Rect::new(0.0, 0.0, 40.0, 40.0), 1.0, CompositeOperation::SourceOver)
+        .unwrap();
+
+    // States saved in the layer and not restored are dropped by end_layer
+    canvas.save();
+    canvas.translate(5.0, 0.0);
+    canvas.save();
+
+    let mut path = Path::new();
+    path.rect(20.0, 5.0, 10.0, 30.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    canvas.end_layer();
+    canvas.pop_render_target();
+
+    assert_eq!(canvas.transform(), Transform2D::identity());
+
+    let mut path = Path::new();
+    path.rect(0.0, 0.0, 20.0, 20.0);
+    canvas.fill_path(&mut path, Paint::image(image, 0.0, 0.0, 20.0, 20.0, 0.0, 1.0));
+
+    let screenshot = canvas.screenshot().unwrap();
+
+    assert_eq!(screenshot[(15usize, 10usize)], rgb::RGBA8::new(255, 0, 0, 255));
+    assert_eq!(screenshot[(5usize, 10usize)], rgb::RGBA8::new(0, 0, 255, 255));
+}
+
+#[test]
+fn layer_applies_alpha_to_group() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(40, 40, 1.0);
+    canvas.clear_rect(0, 0, 40, 40, Color::white());
+
+    canvas.translate(2.0, 2.0);
+    canvas
+        .begin_layer(Rect::new(3.0, 3.0, 30.0, 20.0), 0.5, CompositeOperation::SourceOver)
+        .unwrap();
+
+    // Overlapping shapes, the second one partly outside of the layer
+    let mut path = Path::new();
+    path.rect(5.0, 5.0, 20.0, 10.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    let mut path = Path::new();
+    path.rect(15.0, 5.0, 20.0, 30.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    canvas.end_layer();
+
+    let screenshot = canvas.screenshot().unwrap();
+
+    let close = |a: rgb::RGBA8, b: rgb::RGBA8| {
+        (a.r as i32 - b.r as i32).abs() <= 2
+            && (a.g as i32 - b.g as i32).abs() <= 2
+            && (a.b as i32 - b.b as i32).abs() <= 2
+    };
+
+    let half_red = rgb::RGBA8::new(255, 128, 128, 255);
+    assert!(close(screenshot[(10usize, 10usize)], half_red));
+    assert!(close(screenshot[(20usize, 10usize)], half_red));
+    assert!(close(screenshot[(30usize, 20usize)], half_red));
+
+    // Clipped to the layer bounds
+    assert_eq!(screenshot[(30usize, 30usize)], rgb::RGBA8::new(255, 255, 255, 255));
+
+    assert_eq!(canvas.transform(), Transform2D::new_translation(2.0, 2.0));
+}
+
+#[test]
+fn layer_stays_open_across_flush() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(40, 40, 1.0);
+    canvas.clear_rect(0, 0, 40, 40, Color::white());
+
+    canvas
+        .begin_layer(Rect::new(0.0, 0.0, 40.0, 40.0), 0.5, CompositeOperation::SourceOver)
+        .unwrap();
+
+    let mut path = Path::new();
+    path.rect(5.0, 5.0, 20.0, 10.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    // A debug screenshot in the middle of the layer flushes the canvas
+    canvas.screenshot().unwrap();
+
+    let mut path = Path::new();
+    path.rect(15.0, 5.0, 20.0, 10.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    canvas.end_layer();
+
+    let screenshot = canvas.screenshot().unwrap();
+
+    // Both shapes are in the layer and blend with each other before its alpha applies
+    let half_red = rgb::RGBA8::new(255, 128, 128, 255);
+    assert_eq!(screenshot[(10usize, 10usize)], half_red);
+    assert_eq!(screenshot[(20usize, 10usize)], half_red);
+    assert_eq!(screenshot[(30usize, 10usize)], half_red);
+    assert_eq!(screenshot[(10usize, 30usize)], rgb::RGBA8::new(255, 255, 255, 255));
+}
+
+#[test]
+fn clip_path_limits_drawing() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(40, 40, 1.0);
+    canvas.clear_rect(0, 0, 40, 40, Color::white());
+
+    let red = rgb::RGBA8::new(255, 0, 0, 255);
+    let white = rgb::RGBA8::new(255, 255, 255, 255);
+
+    canvas.save();
+
+    let mut circle = Path::new();
+    circle.circle(20.0, 20.0, 15.0);
+    canvas.clip_path(&mut circle, FillRule::NonZero);
+
+    // Nested clips intersect, this cuts off the left half of the circle
+    let mut right = Path::new();
+    right.rect(20.0, 0.0, 40.0, 40.0);
+    canvas.clip_path(&mut right, FillRule::NonZero);
+
+    let mut path = Path::new();
+    path.rect(0.0, 0.0, 40.0, 30.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    // Strokes are clipped too
+    let mut path = Path::new();
+    path.move_to(0.0, 32.0);
+    path.line_to(40.0, 32.0);
+    let mut paint = Paint::color(Color::rgb(255, 0, 0));
+    paint.set_line_width(2.0);
+    canvas.stroke_path(&mut path, paint);
+
+    canvas.restore();
+
+    // The clip is part of the saved state
+    let mut path = Path::new();
+    path.rect(0.0, 38.0, 40.0, 2.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    let screenshot = canvas.screenshot().unwrap();
+
+    assert_eq!(screenshot[(25usize, 20usize)], red);
+    assert_eq!(screenshot[(15usize, 20usize)], white);
+    assert_eq!(screenshot[(37usize, 20usize)], white);
+    assert_eq!(screenshot[(22usize, 32usize)], red);
+    assert_eq!(screenshot[(38usize, 32usize)], white);
+    assert_eq!(screenshot[(5usize, 39usize)], red);
+}
+
+#[test]
+fn clipped_paths_follow_style_and_clip_changes() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(40, 40, 1.0);
+    canvas.clear_rect(0, 0, 40, 40, Color::white());
+
+    let red = rgb::RGBA8::new(255, 0, 0, 255);
+
+    let mut clip = Path::new();
+    clip.rect(0.0, 0.0, 20.0, 40.0);
+    canvas.clip_path(&mut clip, FillRule::NonZero);
+
+    // The same path drawn again with a wider stroke and then under another clip
+    let mut path = Path::new();
+    path.move_to(0.0, 20.0);
+    path.line_to(40.0, 20.0);
+
+    let mut paint = Paint::color(Color::rgb(0, 0, 255));
+    paint.set_line_width(2.0);
+    canvas.stroke_path(&mut path, paint);
+
+    let mut paint = Paint::color(Color::rgb(255, 0, 0));
+    paint.set_line_width(10.0);
+    canvas.stroke_path(&mut path, paint);
+
+    canvas.reset_clip();
+    let mut clip = Path::new();
+    clip.rect(20.0, 0.0, 20.0, 40.0);
+    canvas.clip_path(&mut clip, FillRule::NonZero);
+    canvas.stroke_path(&mut path, paint);
+
+    let screenshot = canvas.screenshot().unwrap();
+
+    assert_eq!(screenshot[(10usize, 16usize)], red);
+    assert_eq!(screenshot[(10usize, 20usize)], red);
+    assert_eq!(screenshot[(30usize, 16usize)], red);
+}
+
+#[test]
+fn clipped_text_matches_unclipped_inside_the_clip() {
+    let draw = |clip: bool| {
+        let mut canvas = Canvas::new(Software::new()).unwrap();
+        canvas.set_size(120, 40, 1.0);
+        canvas.clear_rect(0, 0, 120, 40, Color::white());
+        canvas.add_font("examples/assets/Roboto-Regular.ttf").unwrap();
+
+        if clip {
+            let mut path = Path::new();
+            path.rect(0.0, 0.0, 60.0, 40.0);
+            canvas.clip_path(&mut path, FillRule::NonZero);
+        }
+
+        let mut paint = Paint::color(Color::black());
+        paint.set_font_size(20.0);
+        canvas.fill_text(5.0, 28.0, "Hello world", paint).unwrap();
+
+        canvas.screenshot().unwrap()
+    };
+
+    let unclipped = draw(false);
+    let clipped = draw(true);
+
+    let white = rgb::RGBA8::new(255, 255, 255, 255);
+
+    // Glyphs inside the clip are drawn from the atlas like unclipped text
+    for y in 0..40usize {
+        for x in 0..40usize {
+            assert_eq!(clipped[(x, y)], unclipped[(x, y)]);
+        }
+
+        for x in 60..120usize {
+            assert_eq!(clipped[(x, y)], white);
+        }
+    }
+
+    assert!((60..120usize).any(|x| (0..40usize).any(|y| unclipped[(x, y)] != white)));
+}
+
+#[test]
+fn intersect_rotated_scissor_is_exact() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(40, 40, 1.0);
+    canvas.clear_rect(0, 0, 40, 40, Color::white());
+
+    let red = rgb::RGBA8::new(255, 0, 0, 255);
+    let white = rgb::RGBA8::new(255, 255, 255, 255);
+
+    // A diamond around the center of the canvas
+    canvas.translate(20.0, 20.0);
+    canvas.rotate(std::f32::consts::FRAC_PI_4);
+    canvas.scissor(-10.0, -10.0, 20.0, 20.0);
+
+    canvas.reset_transform();
+    canvas.intersect_scissor(0.0, 0.0, 30.0, 40.0);
+
+    let mut path = Path::new();
+    path.rect(0.0, 0.0, 40.0, 40.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    let screenshot = canvas.screenshot().unwrap();
+
+    assert_eq!(screenshot[(20usize, 20usize)], red);
+    assert_eq!(screenshot[(20usize, 8usize)], red);
+    // Inside the bounds of the diamond but outside the diamond
+    assert_eq!(screenshot[(8usize, 8usize)], white);
+    assert_eq!(screenshot[(8usize, 31usize)], white);
+    // Outside the intersected rectangle
+    assert_eq!(screenshot[(32usize, 20usize)], white);
+
+    canvas.reset_scissor();
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    let screenshot = canvas.screenshot().unwrap();
+
+    assert_eq!(screenshot[(8usize, 8usize)], red);
+}
+
+#[test]
+fn gaussian_blur_spreads_edges() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(64, 8, 1.0);
+
+    // Opaque white on the left half, transparent on the right
+    let mut pixels = vec![rgb::RGBA8::new(0, 0, 0, 0); 64 * 8];
+
+    for row in pixels.chunks_mut(64) {
+        for pixel in &mut row[..32] {
+            *pixel = rgb::RGBA8::new(255, 255, 255, 255);
+        }
+    }
+
+    let pixels = imgref::ImgVec::new(pixels, 64, 8);
+    let src = canvas
+        .create_image(ImageSource::Rgba(pixels.as_ref()), ImageFlags::empty())
+        .unwrap();
+
+    let flags = ImageFlags::PREMULTIPLIED | ImageFlags::FLIP_Y;
+    let dst = canvas.create_image_empty(64, 8, PixelFormat::Rgba8, flags).unwrap();
+
+    let mut path = Path::new();
+    path.rect(0.0, 0.0, 64.0, 8.0);
+
+    // The narrow blur is done at full resolution, the wide one at a lower resolution
+    for &sigma in &[1.0, 10.0] {
+        canvas.filter_image(src, dst, Filter::GaussianBlur { sigma }).unwrap();
+
+        canvas.clear_rect(0, 0, 64, 8, Color::black());
+        canvas.fill_path(&mut path, Paint::image(dst, 0.0, 0.0, 64.0, 8.0, 0.0, 1.0));
+
+        let screenshot = canvas.screenshot().unwrap();
+        let row: Vec<u8> = (0..64).map(|x| screenshot[(x as usize, 4usize)].g).collect();
+
+        // Half way at the edge and falling off smoothly to either side
+        assert!((row[31] as i32 + row[32] as i32 - 255).abs() <= 2, "{:?}", row);
+        assert!(row.windows(2).all(|pair| pair[0] >= pair[1]), "{:?}", row);
+        assert_eq!(row[0], 255);
+        assert_eq!(row[63], 0);
+
+        // Along the gaussian the edge is smeared into
+        for (x, &value) in row.iter().enumerate() {
+            let expected = 255.0 * (1.0 - normal_cdf((x as f32 + 0.5 - 32.0) / sigma));
+            assert!((value as f32 - expected).abs() <= 3.0, "{} {:?}", x, row);
+        }
+    }
+
+    canvas.delete_image(dst);
+
+    assert!(matches!(
+        canvas.filter_image(src, dst, Filter::GaussianBlur { sigma: 1.0 }),
+        Err(ErrorKind::ImageIdNotFound)
+    ));
+}
+
+// Abramowitz and Stegun 7.1.26, good to about 1e-7
+fn normal_cdf(x: f32) -> f32 {
+    let z = x.abs() / std::f32::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * z);
+    let poly = t * (0.2548296 + t * (-0.28449672 + t * (1.4214138 + t * (-1.4531521 + t * 1.0614054))));
+    let erf = 1.0 - poly * (-z * z).exp();
+
+    0.5 * (1.0 + erf.copysign(x))
+}
+
+#[test]
+fn image_filters_change_colors() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(3, 1, 1.0);
+
+    let pixels = imgref::ImgVec::new(
+        vec![
+            rgb::RGBA8::new(255, 0, 0, 255),
+            rgb::RGBA8::new(40, 80, 200, 128),
+            rgb::RGBA8::new(0, 0, 0, 0),
+        ],
+        3,
+        1,
+    );
+    let src = canvas
+        .create_image(ImageSource::Rgba(pixels.as_ref()), ImageFlags::NEAREST)
+        .unwrap();
+
+    let flags = ImageFlags::PREMULTIPLIED | ImageFlags::FLIP_Y | ImageFlags::NEAREST;
+    let dst = canvas.create_image_empty(3, 1, PixelFormat::Rgba8, flags).unwrap();
+
+    let mut path = Path::new();
+    path.rect(0.0, 0.0, 3.0, 1.0);
+
+    let mut filtered = |canvas: &mut Canvas<Software>, filters: &[ImageFilter]| {
+        canvas.apply_image_filters(src, dst, filters).unwrap();
+
+        canvas.clear_rect(0, 0, 3, 1, Color::black());
+        canvas.fill_path(&mut path, Paint::image(dst, 0.0, 0.0, 3.0, 1.0, 0.0, 1.0));
+
+        canvas.screenshot().unwrap().into_buf()
+    };
+
+    let rgb = |r, g, b| rgb::RGBA8::new(r, g, b, 255);
+    let black = rgb(0, 0, 0);
+
+    assert_eq!(filtered(&mut canvas, &[]), [rgb(255, 0, 0), rgb(20, 40, 100), black]);
+
+    // Filters apply to the unpremultiplied colors
+    assert_eq!(
+        filtered(&mut canvas, &[ImageFilter::Invert(1.0)]),
+        [rgb(0, 255, 255), rgb(108, 88, 28), black]
+    );
+    assert_eq!(
+        filtered(&mut canvas, &[ImageFilter::Grayscale(1.0)]),
+        [rgb(54, 54, 54), rgb(40, 40, 40), black]
+    );
+
+    // Chained filters are applied one after the other, each clamping the colors
+    let chain = [ImageFilter::Brightness(2.0), ImageFilter::Brightness(0.5)];
+    assert_eq!(filtered(&mut canvas, &chain), [rgb(128, 0, 0), rgb(20, 40, 64), black]);
+
+    let table: Vec<_> = (0..=255).map(|i| [255 - i as u8, i as u8, 128, 255]).collect();
+    assert_eq!(
+        filtered(&mut canvas, &[ImageFilter::LookupTable(table)]),
+        [rgb(0, 0, 128), rgb(215, 80, 128), rgb(255, 0, 128)]
+    );
+
+    assert!(matches!(
+        canvas.apply_image_filters(src, dst, &[ImageFilter::LookupTable(vec![[0; 4]; 16])]),
+        Err(ErrorKind::GeneralError(_))
+    ));
+
+    // Filtering an image into itself reads it before it's drawn to
+    canvas.apply_image_filters(src, dst, &[]).unwrap();
+    canvas
+        .apply_image_filters(dst, dst, &[ImageFilter::Invert(1.0)])
+        .unwrap();
+    canvas.apply_image_filters(dst, dst, &[]).unwrap();
+
+    canvas.clear_rect(0, 0, 3, 1, Color::black());
+    canvas.fill_path(&mut path, Paint::image(dst, 0.0, 0.0, 3.0, 1.0, 0.0, 1.0));
+
+    assert_eq!(
+        canvas.screenshot().unwrap().into_buf(),
+        [rgb(0, 255, 255), rgb(108, 88, 28), black]
+    );
+
+    // Layers are drawn through their filters
+    canvas.clear_rect(0, 0, 3, 1, Color::white());
+    canvas
+        .begin_layer(Rect::new(0.0, 0.0, 3.0, 1.0), 1.0, CompositeOperation::SourceOver)
+        .unwrap();
+    canvas.set_layer_filters(&[ImageFilter::Invert(1.0)]);
+
+    let mut path = Path::new();
+    path.rect(0.0, 0.0, 2.0, 1.0);
+    canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+    canvas.end_layer();
+
+    let white = rgb(255, 255, 255);
+    assert_eq!(
+        canvas.screenshot().unwrap().into_buf(),
+        [rgb(0, 255, 255), rgb(0, 255, 255), white]
+    );
+}
+
+#[test]
+fn drop_shadow_is_blurred_and_offset() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(60, 40, 1.0);
+    canvas.clear_rect(0, 0, 60, 40, Color::white());
+
+    let mut path = Path::new();
+    path.rect(15.0, 10.0, 20.0, 10.0);
+
+    canvas
+        .drop_shadow(&mut path, (5.0, 5.0), 2.0, Color::rgb(0, 0, 0))
+        .unwrap();
+
+    let screenshot = canvas.screenshot().unwrap();
+    let gray = |x: usize, y: usize| screenshot[(x, y)].g;
+
+    // Dark in the middle of the moved shape, fading out over its edges
+    assert!(gray(30, 20) < 10);
+    assert!(gray(20, 20) > 100 && gray(20, 20) < 155);
+    assert!(gray(40, 20) > 100 && gray(40, 20) < 155);
+    assert!(gray(30, 15) > 100 && gray(30, 15) < 155);
+    assert!(gray(14, 20) > 245);
+    assert_eq!(gray(5, 5), 255);
+    assert_eq!(gray(50, 35), 255);
+}
+
+#[test]
+fn picture_replays_drawing() {
+    let pixels = imgref::ImgVec::new(vec![rgb::RGBA8::new(0, 0, 255, 255); 4 * 4], 4, 4);
+
+    let draw = |canvas: &mut Canvas<Software>, image: ImageId| {
+        canvas.clear_rect(0, 0, 40, 40, Color::white());
+        canvas.save();
+        canvas.translate(5.0, 5.0);
+        canvas.scissor(0.0, 0.0, 20.0, 30.0);
+
+        let mut path = Path::new();
+        path.rect(0.0, 0.0, 30.0, 10.0);
+        canvas.fill_path(&mut path, Paint::color(Color::rgb(255, 0, 0)));
+
+        let mut path = Path::new();
+        path.rect(0.0, 15.0, 30.0, 10.0);
+        canvas.fill_path(&mut path, Paint::image(image, 0.0, 15.0, 4.0, 4.0, 0.0, 1.0));
+        canvas.restore();
+
+        let mut path = Path::new();
+        path.move_to(0.0, 35.0);
+        path.line_to(40.0, 35.0);
+        canvas.stroke_path(&mut path, Paint::color(Color::black()));
+    };
+
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(40, 40, 1.0);
+    let image = canvas
+        .create_image(
+            ImageSource::Rgba(pixels.as_ref()),
+            ImageFlags::REPEAT_X | ImageFlags::REPEAT_Y,
+        )
+        .unwrap();
+
+    canvas.begin_recording();
+    draw(&mut canvas, image);
+    let mut picture = canvas.end_recording().unwrap();
+    let expected = canvas.screenshot().unwrap();
+
+    assert_eq!(picture.image_names(), ["image0"]);
+    picture.set_image_name(image, "pattern");
+
+    let mut other = Canvas::new(Software::new()).unwrap();
+    other.set_size(40, 40, 1.0);
+
+    assert!(matches!(
+        picture.replay(&mut other, &HashMap::new(), &HashMap::new()),
+        Err(ErrorKind::ImageIdNotFound)
+    ));
+
+    // Create another image first so that the ids differ between the canvases
+    other
+        .create_image_empty(1, 1, PixelFormat::Rgba8, ImageFlags::empty())
+        .unwrap();
+    let other_image = other
+        .create_image(
+            ImageSource::Rgba(pixels.as_ref()),
+            ImageFlags::REPEAT_X | ImageFlags::REPEAT_Y,
+        )
+        .unwrap();
+
+    let mut images = HashMap::new();
+    images.insert("pattern".to_string(), other_image);
+
+    picture.replay(&mut other, &images, &HashMap::new()).unwrap();
+
+    assert_eq!(other.screenshot().unwrap().buf(), expected.buf());
+}
+
+#[test]
+fn picture_replays_text_with_named_fonts() {
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(60, 30, 1.0);
+    let font = canvas.add_font("examples/assets/Roboto-Regular.ttf").unwrap();
+
+    let mut paint = Paint::color(Color::black());
+    paint.set_font(&[font]);
+    paint.set_font_size(16.0);
+
+    canvas.clear_rect(0, 0, 60, 30, Color::white());
+    canvas.begin_recording();
+    canvas.fill_text(5.0, 20.0, "Text", paint).unwrap();
+    let mut picture = canvas.end_recording().unwrap();
+    let expected = canvas.screenshot().unwrap();
+
+    assert_eq!(picture.font_names(), ["Roboto-Regular"]);
+    picture.set_font_name(font, "body");
+
+    let mut other = Canvas::new(Software::new()).unwrap();
+    other.set_size(60, 30, 1.0);
+    other.clear_rect(0, 0, 60, 30, Color::white());
+
+    assert!(matches!(
+        picture.replay(&mut other, &HashMap::new(), &HashMap::new()),
+        Err(ErrorKind::NoFontFound)
+    ));
+
+    other.add_font("examples/assets/Roboto-Bold.ttf").unwrap();
+    let other_font = other.add_font("examples/assets/Roboto-Regular.ttf").unwrap();
+
+    let mut fonts = HashMap::new();
+    fonts.insert("body".to_string(), other_font);
+
+    picture.replay(&mut other, &HashMap::new(), &fonts).unwrap();
+
+    assert_eq!(other.screenshot().unwrap().buf(), expected.buf());
+}
+
+#[cfg(feature = "svg")]
+#[test]
+fn svg_document_with_group_opacity() {
+    use gpucanvas::svg::{Document, Item};
+
+    let data = br##"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 10 10">
+        <rect width="10" height="10" fill="white"/>
+        <g opacity="0.5">
+            <rect width="5" height="10" fill="#ff0000"/>
+            <rect x="5" width="5" height="10" fill="#0000ff" stroke="#0000ff"/>
+        </g>
+    </svg>"##;
+
+    let mut document = Document::load_mem(data).unwrap();
+
+    assert_eq!(document.size().w, 20.0);
+    assert_eq!(document.items().len(), 2);
+    assert!(matches!(document.items()[1], Item::Group(_)));
+
+    let mut canvas = Canvas::new(Software::new()).unwrap();
+    canvas.set_size(20, 20, 1.0);
+    canvas.clear_rect(0, 0, 20, 20, Color::black());
+    canvas.draw_svg(&mut document);
+
+    let screenshot = canvas.screenshot().unwrap();
+
+    let close = |a: rgb::RGBA8, b: rgb::RGBA8| {
+        (a.r as i32 - b.r as i32).abs() <= 2
+            && (a.g as i32 - b.g as i32).abs() <= 2
+            && (a.b as i32 - b.b as i32).abs() <= 2
+    };
+
+    assert!(close(
+        screenshot[(5usize, 10usize)],
+        rgb::RGBA8::new(255, 128, 128, 255)
+    ));
+    assert!(close(
+        screenshot[(15usize, 10usize)],
+        rgb::RGBA8::new(128, 128, 255, 255)
+    ));
+}
+
+#[cfg(feature = "svg")]
+#[test]
+fn svg_group_layer_covers_its_items() {
+    use gpucanvas::svg::Document;
+
+    let data = br##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+        <g opacity="0.5">
+            <rect x="10" y="10" width="5" height="5" fill="#ff0000" stroke="#0000ff" stroke-width="2"/>
+        </g>
+    </svg>"##;
+
+    let mut document = Document::load_mem(data).unwrap();
+
+    let mut canvas = Canvas::new(Recorder::new()).unwrap();
+    canvas.set_size(100, 100, 1.0);
+    canvas.draw_svg(&mut document);
+    canvas.flush();
+
+    let frame = canvas.renderer_mut().last_frame().unwrap();
+
+    // The last drawing composites the layer, it covers the rect and its miter joins
+    let cmd = frame
+        .commands()
+        .iter()
+        .rev()
+        .find(|cmd| !cmd.drawables().is_empty())
+        .unwrap();
+    let fill_verts = cmd.drawables()[0].fill_verts().unwrap();
+
+    for vert in frame.verts_in(fill_verts) {
+        assert!(vert.x >= 4.0 && vert.x <= 21.0);
+        assert!(vert.y >= 4.0 && vert.y <= 21.0);
+    }
+}